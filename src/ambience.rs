@@ -0,0 +1,171 @@
+// Procedural ambient soundscape. Layers are synthesized rather than looped
+// from a track; the game sets each layer's level from what is on screen and
+// the audio thread eases toward it.
+
+use bevy::audio::{Decodable, Source};
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 44_100;
+const LEVEL_SMOOTHING: f32 = 1.0 / (SAMPLE_RATE as f32 * 0.8); // Level changes ease in over about a second
+const DRIPS_PER_SECOND: f32 = 1.5; // Drip rate at full level
+const DRIP_SECONDS: f32 = 0.06; // Length of one drip
+
+// The layers mixed into the soundscape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbientLayer {
+    Wind,   // Filtered noise with slow gusts
+    Rumble, // Low rumble of machinery
+    Drips,  // Water dripping in cavities
+}
+
+impl AmbientLayer {
+    pub const ALL: [AmbientLayer; 3] = [AmbientLayer::Wind, AmbientLayer::Rumble, AmbientLayer::Drips];
+
+    pub fn name(&self) -> &str {
+        match self {
+            AmbientLayer::Wind => "Wind",
+            AmbientLayer::Rumble => "Rumble",
+            AmbientLayer::Drips => "Drips",
+        }
+    }
+}
+
+// Target levels shared between the game and the audio thread, one per layer
+// plus the master volume, each 0.0 to 1.0
+#[derive(Debug, Clone, Default)]
+pub struct AmbientLevels(Arc<[AtomicU32; 4]>);
+
+impl AmbientLevels {
+    const MASTER: usize = 3;
+
+    pub fn set(&self, layer: AmbientLayer, level: f32) {
+        self.store(layer as usize, level);
+    }
+
+    pub fn get(&self, layer: AmbientLayer) -> f32 {
+        self.load(layer as usize)
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.store(Self::MASTER, volume);
+    }
+
+    fn store(&self, index: usize, level: f32) {
+        self.0[index].store(level.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    fn load(&self, index: usize) -> f32 {
+        f32::from_bits(self.0[index].load(Ordering::Relaxed))
+    }
+}
+
+// Endless audio source mixing the ambient layers at the shared levels
+#[derive(Asset, TypePath, Clone)]
+pub struct AmbientSound {
+    pub levels: AmbientLevels,
+}
+
+impl Decodable for AmbientSound {
+    type DecoderItem = f32;
+    type Decoder = AmbientDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        AmbientDecoder {
+            levels: self.levels.clone(),
+            current: [0.0; 4],
+            time: 0.0,
+            rng: 0x9E37_79B9,
+            wind: 0.0,
+            rumble: [0.0; 2],
+            drip: None,
+        }
+    }
+}
+
+pub struct AmbientDecoder {
+    levels: AmbientLevels,
+    current: [f32; 4], // Levels eased toward the shared targets
+    time: f64, // Seconds played; f64 so oscillator phases stay exact over long sessions
+    rng: u32,
+    wind: f32,        // Low-pass filter state of the wind noise
+    rumble: [f32; 2], // Brown noise and its low-pass filter state
+    drip: Option<(f32, f32)>, // Seconds into the current drip and its base pitch
+}
+
+impl AmbientDecoder {
+    /// White noise from -1.0 to 1.0 (xorshift, cheap enough for the audio thread)
+    fn noise(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        self.rng as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// Position through the current cycle of an oscillator, 0.0 to 1.0
+    fn phase(&self, frequency: f64) -> f32 {
+        (self.time * frequency).fract() as f32
+    }
+}
+
+impl Iterator for AmbientDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let dt = 1.0 / SAMPLE_RATE as f32;
+        self.time += f64::from(dt);
+        for (index, level) in self.current.iter_mut().enumerate() {
+            *level += (self.levels.load(index) - *level) * LEVEL_SMOOTHING;
+        }
+        let [wind_level, rumble_level, drip_level, volume] = self.current;
+
+        // Wind: noise through a low-pass whose cutoff rises with each gust
+        let gust = 0.5 + 0.3 * (self.phase(0.13) * TAU).sin() + 0.2 * (self.phase(0.31) * TAU).sin();
+        let white = self.noise();
+        self.wind += (white - self.wind) * (0.01 + 0.04 * gust);
+        let wind = self.wind * gust * 9.0;
+
+        // Rumble: leaky brown noise, low-passed, over a slowly pulsing hum
+        let white = self.noise();
+        self.rumble[0] = (self.rumble[0] + white * 0.02) * 0.998;
+        self.rumble[1] += (self.rumble[0] - self.rumble[1]) * 0.01;
+        let hum = (self.phase(48.0) * TAU).sin() * (0.6 + 0.4 * (self.phase(1.7) * TAU).sin());
+        let rumble = self.rumble[1] * 2.5 + hum * 0.1;
+
+        // Drips: short decaying tones that slide upward, at random intervals
+        if self.drip.is_none() && (self.noise() * 0.5 + 0.5) < drip_level * DRIPS_PER_SECOND * dt {
+            let pitch = 900.0 + (self.noise() * 0.5 + 0.5) * 700.0;
+            self.drip = Some((0.0, pitch));
+        }
+        let mut drip = 0.0;
+        if let Some((age, pitch)) = self.drip {
+            let frequency = pitch * (1.0 + age / DRIP_SECONDS);
+            drip = (age * frequency * TAU).sin() * (-age * 60.0).exp();
+            self.drip = (age + dt < DRIP_SECONDS).then_some((age + dt, pitch));
+        }
+
+        let mix = wind * wind_level + rumble * rumble_level + drip * drip_level;
+        Some((mix * volume * 0.3).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for AmbientDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,258 @@
+// Determinism audit mode.
+//
+// Every N ticks the map and the unit state are hashed and appended to a log:
+//   regolith_voxel --seed 42 --audit 60
+//   regolith_voxel --seed 42 --audit 60 --audit-against regolith-audit.log --audit-log second.log
+// Two runs of the same seed and inputs should write identical logs. With
+// --audit-against, each hash is checked against an earlier log as it is
+// written, and the first divergence dumps the differing map region (or the
+// unit state) to regolith-audit-divergence.txt. Frames advance by a fixed
+// step in this mode so movement doesn't depend on frame timing.
+
+use super::{EquipmentTreeState, Stockpile};
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use regolith_voxel::world::{arg_value, MineralMap, MineralType};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Duration;
+
+const DEFAULT_LOG: &str = "regolith-audit.log";
+const DIVERGENCE_DUMP: &str = "regolith-audit-divergence.txt";
+const REGION_CELLS: usize = 32; // Side of the square regions hashed separately
+const STEP_SECS: f64 = 1.0 / 60.0; // Fixed frame step while auditing
+
+pub struct AuditPlugin {
+    interval: u64,
+    log_path: String,
+    reference_path: Option<String>,
+}
+
+impl AuditPlugin {
+    /// Audit settings from `--audit <ticks>`, `--audit-log <file>` and
+    /// `--audit-against <file>`, or None when auditing is off
+    pub fn from_args() -> Option<Self> {
+        let interval = match arg_value("--audit")?.parse() {
+            Ok(interval) if interval > 0 => interval,
+            _ => {
+                eprintln!("--audit needs a tick interval above zero");
+                return None;
+            }
+        };
+        Some(Self {
+            interval,
+            log_path: arg_value("--audit-log").unwrap_or_else(|| DEFAULT_LOG.to_string()),
+            reference_path: arg_value("--audit-against"),
+        })
+    }
+}
+
+impl Plugin for AuditPlugin {
+    fn build(&self, app: &mut App) {
+        let log = match File::create(&self.log_path) {
+            Ok(file) => BufWriter::new(file),
+            Err(err) => {
+                warn!("Determinism audit disabled, could not create {}: {}", self.log_path, err);
+                return;
+            }
+        };
+        let reference = self.reference_path.as_ref().and_then(|path| match std::fs::read_to_string(path) {
+            Ok(text) => Some(text.lines().filter_map(AuditRecord::parse).map(|record| (record.tick, record)).collect()),
+            Err(err) => {
+                warn!("Could not read audit log {}: {}", path, err);
+                None
+            }
+        });
+        info!("Determinism audit every {} ticks, logging to {}", self.interval, self.log_path);
+
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(STEP_SECS)))
+            .insert_resource(DeterminismAudit {
+                interval: self.interval,
+                tick: 0,
+                log,
+                reference,
+                diverged: false,
+            })
+            .add_systems(Last, audit_world_state);
+    }
+}
+
+#[derive(Resource)]
+struct DeterminismAudit {
+    interval: u64,
+    tick: u64,
+    log: BufWriter<File>,
+    reference: Option<HashMap<u64, AuditRecord>>, // Earlier run's records by tick
+    diverged: bool,                               // Only the first divergence is dumped
+}
+
+// One line of the audit log
+struct AuditRecord {
+    tick: u64,
+    map: u64,
+    units: u64,
+    regions: Vec<u64>,
+}
+
+impl AuditRecord {
+    /// `<tick> <map hash> <unit hash> <region hashes, comma separated>`
+    fn line(&self) -> String {
+        let regions: Vec<String> = self.regions.iter().map(|hash| format!("{:016x}", hash)).collect();
+        format!("{} {:016x} {:016x} {}", self.tick, self.map, self.units, regions.join(","))
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let tick = words.next()?.parse().ok()?;
+        let map = u64::from_str_radix(words.next()?, 16).ok()?;
+        let units = u64::from_str_radix(words.next()?, 16).ok()?;
+        let regions = words
+            .next()?
+            .split(',')
+            .map(|hash| u64::from_str_radix(hash, 16).ok())
+            .collect::<Option<_>>()?;
+        Some(Self { tick, map, units, regions })
+    }
+}
+
+fn audit_world_state(
+    mut audit: ResMut<DeterminismAudit>,
+    mineral_map: Res<MineralMap>,
+    equipment_state: Res<EquipmentTreeState>,
+    stockpile: Res<Stockpile>,
+) {
+    audit.tick += 1;
+    if !audit.tick.is_multiple_of(audit.interval) {
+        return;
+    }
+
+    let regions = mineral_map.region_hashes(REGION_CELLS);
+    let record = AuditRecord {
+        tick: audit.tick,
+        map: fold(regions.iter().copied()),
+        units: unit_hash(&equipment_state, &stockpile),
+        regions,
+    };
+    if let Err(err) = writeln!(audit.log, "{}", record.line()).and_then(|_| audit.log.flush()) {
+        warn!("Could not write the audit log: {}", err);
+    }
+
+    if audit.diverged {
+        return;
+    }
+    let Some(expected) = audit.reference.as_ref().and_then(|reference| reference.get(&record.tick)) else {
+        return;
+    };
+    if expected.map == record.map && expected.units == record.units {
+        return;
+    }
+
+    let mut dump = format!("First divergence at tick {}\n", record.tick);
+    let region = record.regions.iter().zip(&expected.regions).position(|(ours, theirs)| ours != theirs);
+    match region {
+        Some(index) => {
+            let across = mineral_map.width.div_ceil(REGION_CELLS);
+            let (x0, y0) = ((index % across) * REGION_CELLS, (index / across) * REGION_CELLS);
+            warn!(
+                "Determinism audit: map diverged at tick {} in cells ({}, {}) to ({}, {})",
+                record.tick,
+                x0,
+                y0,
+                x0 + REGION_CELLS - 1,
+                y0 + REGION_CELLS - 1
+            );
+            dump.push_str(&format!("Region ({}, {}) size {}, cells in this run:\n", x0, y0, REGION_CELLS));
+            for y in y0..(y0 + REGION_CELLS).min(mineral_map.height) {
+                for x in x0..(x0 + REGION_CELLS).min(mineral_map.width) {
+                    if let Some(cell) = mineral_map.get(x, y) {
+                        dump.push_str(&format!(
+                            "{} {} {} {:.4} mined={} sampled={}\n",
+                            x,
+                            y,
+                            cell.mineral_type.name(),
+                            cell.density,
+                            cell.mined,
+                            cell.sampled
+                        ));
+                    }
+                }
+            }
+        }
+        None => warn!("Determinism audit: unit state diverged at tick {}", record.tick),
+    }
+    dump.push_str("Units in this run:\n");
+    for id in equipment_state.all_ids() {
+        if let Some(node) = equipment_state.find_node(id) {
+            dump.push_str(&format!("{} {} {:?} active={}\n", id, node.name, node.position, node.active));
+        }
+    }
+    if let Err(err) = std::fs::write(DIVERGENCE_DUMP, dump) {
+        warn!("Could not write {}: {}", DIVERGENCE_DUMP, err);
+    }
+    audit.diverged = true;
+}
+
+/// Hash of every unit's position and state, and of the stockpile
+fn unit_hash(equipment_state: &EquipmentTreeState, stockpile: &Stockpile) -> u64 {
+    let mut words = Vec::new();
+    for id in equipment_state.all_ids() {
+        let Some(node) = equipment_state.find_node(id) else {
+            continue;
+        };
+        let position = node.position.unwrap_or(Vec2::NAN);
+        words.extend([id as u64, u64::from(position.x.to_bits()), u64::from(position.y.to_bits()), node.active as u64]);
+    }
+    for mineral in MineralType::LEGEND {
+        let amount = stockpile.amounts.get(&mineral).copied().unwrap_or_default();
+        words.push(u64::from(amount.to_bits()));
+    }
+    fold(words.into_iter())
+}
+
+/// FNV-1a over 64-bit words
+fn fold(words: impl Iterator<Item = u64>) -> u64 {
+    words.fold(0xCBF2_9CE4_8422_2325, |hash, word| (hash ^ word).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{place_unpositioned_equipment, PlacementMode, SpawnSettings};
+    use regolith_voxel::world::{MapSymmetry, WorldGenPreset};
+
+    /// Run a headless game for some ticks and return its audit log
+    fn audit_log(seed: u32, run: &str) -> String {
+        let log_path = std::env::temp_dir().join(format!("regolith-audit-test-{}-{}.log", std::process::id(), run));
+        let mineral_map = MineralMap::generate_with_preset(seed, MapSymmetry::None, WorldGenPreset::Standard, 256);
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(SpawnSettings::seeded(mineral_map.seed))
+            .insert_resource(mineral_map)
+            .init_resource::<EquipmentTreeState>()
+            .init_resource::<PlacementMode>()
+            .init_resource::<Stockpile>()
+            .add_systems(Update, place_unpositioned_equipment)
+            .add_plugins(AuditPlugin {
+                interval: 2,
+                log_path: log_path.to_string_lossy().into_owned(),
+                reference_path: None,
+            });
+        for _ in 0..10 {
+            app.update();
+        }
+        drop(app);
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        let _ = std::fs::remove_file(&log_path);
+        log
+    }
+
+    #[test]
+    fn same_seed_writes_identical_audit_logs() {
+        let first = audit_log(42, "first");
+        let second = audit_log(42, "second");
+        assert_eq!(first.lines().count(), 5);
+        assert_eq!(first, second);
+    }
+}
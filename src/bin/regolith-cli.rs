@@ -0,0 +1,233 @@
+// Command-line companion for world generation and map inspection.
+//
+//   regolith-cli stats  --seed 42 [--symmetry mirror-x] [--preset asteroid] [--size 1024]
+//   regolith-cli stats  --daily
+//   regolith-cli render --seed 42 [--symmetry quad] map.png
+//   regolith-cli stats  --image lunar_dem.png
+//   regolith-cli render --generator checkerboard board.png
+
+use rand::{thread_rng, Rng};
+use regolith_voxel::generator::GeneratorRegistry;
+use regolith_voxel::world::{
+    current_day, daily_seed, format_day, MapSymmetry, MineralMap, WorldGenParams, WorldGenPreset, WorldReport,
+    DEFAULT_MAP_SIZE, MAP_SIZES,
+};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage:
+  regolith-cli stats  [--seed N | --daily] [--symmetry MODE] [--preset NAME] [--size CELLS] [--generator NAME]
+  regolith-cli render [--seed N | --daily] [--symmetry MODE] [--preset NAME] [--size CELLS] [--generator NAME] <output.png>
+  regolith-cli stats  --image <map.png>
+  regolith-cli render --image <map.png> <output.png>
+
+Symmetry modes: none, mirror-x, mirror-y, rotate, quad
+Presets: standard, asteroid, lunar, mars
+Sizes: 256, 512 (default), 1024, 2048, 4096
+Generators: noise (default), flat, checkerboard
+--daily uses today's daily challenge seed
+--image loads a grayscale heightmap or a rendered map instead of generating one";
+
+// Options shared by the generating commands
+struct GenerateOptions {
+    seed: Option<u32>,
+    symmetry: MapSymmetry,
+    preset: WorldGenPreset,
+    size: usize,
+    generator: String,
+    image: Option<String>,
+    positional: Vec<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<GenerateOptions, String> {
+    let mut options = GenerateOptions {
+        seed: None,
+        symmetry: MapSymmetry::None,
+        preset: WorldGenPreset::Standard,
+        size: DEFAULT_MAP_SIZE,
+        generator: "noise".to_string(),
+        image: None,
+        positional: Vec::new(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a value")?;
+                options.seed = Some(value.parse().map_err(|_| format!("Invalid seed '{}'", value))?);
+            }
+            "--daily" => {
+                let day = current_day();
+                println!("Daily challenge {}", format_day(day));
+                options.seed = Some(daily_seed(day));
+            }
+            "--symmetry" => {
+                let value = args.next().ok_or("--symmetry needs a value")?;
+                options.symmetry =
+                    MapSymmetry::from_name(value).ok_or(format!("Unknown symmetry '{}'", value))?;
+            }
+            "--preset" => {
+                let value = args.next().ok_or("--preset needs a value")?;
+                options.preset =
+                    WorldGenPreset::from_name(value).ok_or(format!("Unknown preset '{}'", value))?;
+            }
+            "--size" => {
+                let value = args.next().ok_or("--size needs a value")?;
+                options.size = value
+                    .parse()
+                    .ok()
+                    .filter(|size| MAP_SIZES.contains(size))
+                    .ok_or(format!("Unsupported size '{}', expected one of {:?}", value, MAP_SIZES))?;
+            }
+            "--generator" => {
+                let value = args.next().ok_or("--generator needs a value")?;
+                let generators = GeneratorRegistry::default();
+                if generators.get(value).is_none() {
+                    return Err(format!("Unknown generator '{}', expected one of: {}", value, generators.names().join(", ")));
+                }
+                options.generator = value.clone();
+            }
+            "--image" => {
+                options.image = Some(args.next().ok_or("--image needs a path")?.clone());
+            }
+            _ => options.positional.push(arg.clone()),
+        }
+    }
+    Ok(options)
+}
+
+fn generate(options: &GenerateOptions) -> Result<MineralMap, String> {
+    if let Some(path) = &options.image {
+        let bytes = std::fs::read(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+        return MineralMap::from_png(&bytes).map_err(|err| format!("Could not load {}: {}", path, err));
+    }
+    let params = WorldGenParams {
+        seed: options.seed.unwrap_or_else(|| thread_rng().gen()),
+        symmetry: options.symmetry,
+        size: options.size,
+        ..options.preset.params()
+    };
+    let mut map = GeneratorRegistry::default().generate(&options.generator, &params);
+    map.preset = options.preset;
+    Ok(map)
+}
+
+fn print_stats(map: &MineralMap) {
+    let total = map.data.len();
+    println!("Seed:     {}", map.seed);
+    println!("Size:     {}x{}", map.width, map.height);
+    println!("Symmetry: {}", map.symmetry.name());
+    println!("Preset:   {}", map.preset.name());
+    let (coldest, hottest) = map
+        .temperature
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(low, high), &t| (low.min(t), high.max(t)));
+    let mean = map.temperature.iter().sum::<f32>() / map.temperature.len().max(1) as f32;
+    println!("Temp:     {:.0}-{:.0} K (mean {:.0} K)", coldest, hottest, mean);
+    println!();
+    println!("{:<10} {:>8} {:>7} {:>12}", "Mineral", "Cells", "Share", "Avg density");
+    for (mineral, count, density) in map.mineral_stats() {
+        println!(
+            "{:<10} {:>8} {:>6.1}% {:>12.3}",
+            mineral.name(),
+            count,
+            count as f32 / total as f32 * 100.0,
+            density / count as f32
+        );
+    }
+    let report = WorldReport::new(map);
+    println!();
+    println!("Value:    {:.0}", report.total_value);
+    println!("Void:     {:.1}%", report.void_fraction * 100.0);
+    println!("{:<10} {:>10} {:>13}", "Mineral", "Value", "Largest vein");
+    for mineral in &report.minerals {
+        println!("{:<10} {:>10.0} {:>13}", mineral.mineral.name(), mineral.value, mineral.largest_vein);
+    }
+    println!();
+    println!("{:<16} {:>8} {:>7}", "Biome", "Cells", "Share");
+    for (biome, count) in map.biome_stats() {
+        println!(
+            "{:<16} {:>8} {:>6.1}%",
+            biome.name(),
+            count,
+            count as f32 / total as f32 * 100.0
+        );
+    }
+    println!();
+    println!("Craters:");
+    for (center, radius) in &map.craters {
+        println!("  radius {:<3} at ({}, {})", radius, center.x, center.y);
+    }
+    println!();
+    println!("Faults:");
+    for fault in &map.faults {
+        println!(
+            "  throw {:<3} from ({}, {}) to ({}, {})",
+            fault.throw, fault.start.x, fault.start.y, fault.end.x, fault.end.y
+        );
+    }
+    println!();
+    println!("Structures:");
+    for (name, center) in &map.structures {
+        println!("  {:<18} at ({}, {})", name, center.x, center.y);
+    }
+    println!();
+    println!("{:<16} {:>8} {:>7}", "Stratum", "Cells", "Share");
+    for (stratum, count) in map.strata_stats() {
+        println!(
+            "{:<16} {:>8} {:>6.1}%",
+            stratum.name(),
+            count,
+            count as f32 / total as f32 * 100.0
+        );
+    }
+}
+
+fn render(map: &MineralMap, path: &str) -> Result<(), String> {
+    let image = map
+        .to_image()
+        .try_into_dynamic()
+        .map_err(|err| format!("Could not convert the map texture: {:?}", err))?;
+    image
+        .save(path)
+        .map_err(|err| format!("Could not write {}: {}", path, err))?;
+    println!("Wrote {} ({}x{}, seed {})", path, map.width, map.height, map.seed);
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(USAGE.to_string());
+    };
+
+    match command.as_str() {
+        "stats" => {
+            let options = parse_options(rest)?;
+            print_stats(&generate(&options)?);
+            Ok(())
+        }
+        "render" => {
+            let options = parse_options(rest)?;
+            let [path] = options.positional.as_slice() else {
+                return Err(USAGE.to_string());
+            };
+            render(&generate(&options)?, path)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command '{}'\n\n{}", command, USAGE)),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,344 @@
+// Reinforcement learning environment for the mining problem (feature "rl").
+//
+// A Gymnasium-style step/observe/act API over a generated mineral map, for
+// training agents without the game window:
+//   let mut env = MiningEnv::new(EnvConfig::default());
+//   let mut observation = env.reset(42);
+//   loop {
+//       let step = env.step(Action::from_index(policy(&observation)).unwrap());
+//       if step.terminated || step.truncated { break; }
+//       observation = step.observation;
+//   }
+// The fleet is a sampler and two miners that follow the game's digging
+// rules from `mining`, region depletion included. Minerals stay hidden from
+// the observation until sampled or dug.
+
+use crate::mining::{dig_cell, mining_footprint, MinerKind, RegionDepletion};
+use crate::world::{MapSymmetry, MineralMap, MineralType, WorldGenPreset};
+use bevy::prelude::*;
+
+const SAMPLE_RADIUS: i32 = 3; // Cells a sampler surveys around itself
+
+// Units the agent commands, in observation order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EnvUnit {
+    Sampler,
+    SurfaceMiner,
+    DeepMiner,
+}
+
+impl EnvUnit {
+    pub const ALL: [EnvUnit; 3] = [EnvUnit::Sampler, EnvUnit::SurfaceMiner, EnvUnit::DeepMiner];
+
+    pub fn name(&self) -> &str {
+        match self {
+            EnvUnit::Sampler => "Sampler",
+            EnvUnit::SurfaceMiner => "Surface Miner",
+            EnvUnit::DeepMiner => "Deep Miner",
+        }
+    }
+
+    /// The digging rules this unit follows, if it is a miner
+    pub fn miner_kind(&self) -> Option<MinerKind> {
+        match self {
+            EnvUnit::SurfaceMiner => Some(MinerKind::Surface),
+            EnvUnit::DeepMiner => Some(MinerKind::Deep),
+            EnvUnit::Sampler => None,
+        }
+    }
+}
+
+// What a unit is told to do for one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Wait,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Work, // Sample for the sampler, dig for the miners
+}
+
+impl Command {
+    pub const ALL: [Command; 6] = [
+        Command::Wait,
+        Command::MoveUp,
+        Command::MoveDown,
+        Command::MoveLeft,
+        Command::MoveRight,
+        Command::Work,
+    ];
+
+    fn offset(&self) -> IVec2 {
+        match self {
+            Command::MoveUp => IVec2::NEG_Y,
+            Command::MoveDown => IVec2::Y,
+            Command::MoveLeft => IVec2::NEG_X,
+            Command::MoveRight => IVec2::X,
+            Command::Wait | Command::Work => IVec2::ZERO,
+        }
+    }
+}
+
+// One discrete action: a command to one unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Action {
+    pub unit: EnvUnit,
+    pub command: Command,
+}
+
+impl Action {
+    /// Size of the discrete action space
+    pub const COUNT: usize = EnvUnit::ALL.len() * Command::ALL.len();
+
+    /// Action for an index in `0..Action::COUNT`, unit-major
+    pub fn from_index(index: usize) -> Option<Self> {
+        let unit = *EnvUnit::ALL.get(index / Command::ALL.len())?;
+        let command = Command::ALL[index % Command::ALL.len()];
+        Some(Self { unit, command })
+    }
+
+    pub fn index(&self) -> usize {
+        let unit = EnvUnit::ALL.iter().position(|&unit| unit == self.unit).unwrap_or(0);
+        let command = Command::ALL.iter().position(|&command| command == self.command).unwrap_or(0);
+        unit * Command::ALL.len() + command
+    }
+}
+
+// Reward weights; each step's reward is the sum of the terms that apply
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardConfig {
+    pub mineral_values: [f32; 9], // Per unit of density mined, in `MineralType::LEGEND` order
+    pub sample_reward: f32,       // Per newly sampled cell holding a mineral
+    pub step_cost: f32,           // Subtracted every step
+    pub invalid_penalty: f32,     // Subtracted when a command does nothing (walls, barren digs)
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            mineral_values: MineralType::LEGEND.map(|mineral| mineral.value()),
+            sample_reward: 0.05,
+            step_cost: 0.01,
+            invalid_penalty: 0.1,
+        }
+    }
+}
+
+// Environment settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvConfig {
+    pub preset: WorldGenPreset,
+    pub size: usize,          // Cells on a side of the generated map
+    pub patch_radius: usize,  // Observed cells on each side of a unit
+    pub max_steps: usize,     // Episode is truncated after this many steps
+    pub quota: Option<f32>,   // Episode ends once this much mineral is mined
+    pub reward: RewardConfig,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            preset: WorldGenPreset::Standard,
+            size: 256,
+            patch_radius: 5,
+            max_steps: 2000,
+            quota: None,
+            reward: RewardConfig::default(),
+        }
+    }
+}
+
+// What the agent sees after each step
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// Square patch around each unit, row-major, `(2 * patch_radius + 1)^2`
+    /// codes: 0 unknown, 1 empty or dug, 2 + `MineralType::LEGEND` index,
+    /// 255 off the map
+    pub patches: Vec<Vec<u8>>,
+    pub positions: Vec<UVec2>, // Cell of each unit
+    pub inventory: [f32; 9],   // Mined amounts in `MineralType::LEGEND` order
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepResult {
+    pub observation: Observation,
+    pub reward: f32,
+    pub terminated: bool, // Quota reached
+    pub truncated: bool,  // Out of steps
+}
+
+pub struct MiningEnv {
+    pub config: EnvConfig,
+    pub map: MineralMap,
+    positions: [UVec2; 3],
+    inventory: [f32; 9],
+    depletion: RegionDepletion,
+    steps: usize,
+}
+
+impl MiningEnv {
+    pub fn new(config: EnvConfig) -> Self {
+        let mut env = Self {
+            map: MineralMap::generate_with_preset(0, MapSymmetry::None, config.preset, config.size),
+            config,
+            positions: [UVec2::ZERO; 3],
+            inventory: [0.0; 9],
+            depletion: RegionDepletion::default(),
+            steps: 0,
+        };
+        env.reset(0);
+        env
+    }
+
+    /// Start a new episode on the map generated from a seed, with the
+    /// fleet at the top middle of the map
+    pub fn reset(&mut self, seed: u32) -> Observation {
+        if self.map.seed != seed {
+            self.map = MineralMap::generate_with_preset(seed, MapSymmetry::None, self.config.preset, self.config.size);
+        } else {
+            for cell in &mut self.map.data {
+                cell.sampled = false;
+                cell.mined = false;
+            }
+        }
+        let start = UVec2::new(self.map.width as u32 / 2, 0);
+        self.positions = [start; 3];
+        self.inventory = [0.0; 9];
+        self.depletion = RegionDepletion::default();
+        self.steps = 0;
+        self.observe()
+    }
+
+    /// Apply one action and advance the episode
+    pub fn step(&mut self, action: Action) -> StepResult {
+        let reward_config = self.config.reward.clone();
+        let mut reward = -reward_config.step_cost;
+        let unit_index = EnvUnit::ALL.iter().position(|&unit| unit == action.unit).unwrap_or(0);
+        let position = self.positions[unit_index];
+
+        let acted = match action.command {
+            Command::Wait => true,
+            Command::Work if action.unit == EnvUnit::Sampler => {
+                let found = self.sample(position);
+                reward += found as f32 * reward_config.sample_reward;
+                found > 0
+            }
+            Command::Work => {
+                let mined = self.dig(action.unit, position);
+                reward += mined
+                    .iter()
+                    .zip(reward_config.mineral_values)
+                    .map(|(amount, value)| amount * value)
+                    .sum::<f32>();
+                mined.iter().any(|&amount| amount > 0.0)
+            }
+            command => {
+                let next = position.as_ivec2() + command.offset();
+                let on_map = next.x >= 0
+                    && next.y >= 0
+                    && (next.x as usize) < self.map.width
+                    && (next.y as usize) < self.map.height;
+                if on_map {
+                    self.positions[unit_index] = next.as_uvec2();
+                }
+                on_map
+            }
+        };
+        if !acted {
+            reward -= reward_config.invalid_penalty;
+        }
+
+        self.steps += 1;
+        let total: f32 = self.inventory.iter().sum();
+        StepResult {
+            observation: self.observe(),
+            reward,
+            terminated: self.config.quota.is_some_and(|quota| total >= quota),
+            truncated: self.steps >= self.config.max_steps,
+        }
+    }
+
+    /// Mark the cells around a position sampled, returning how many newly
+    /// sampled cells hold a mineral
+    fn sample(&mut self, center: UVec2) -> usize {
+        let mut found = 0;
+        for cell in self.sample_area(center, SAMPLE_RADIUS) {
+            if let Some(cell) = self.map.get_mut(cell.x as usize, cell.y as usize) {
+                if !cell.sampled && cell.mineral_type != MineralType::Empty {
+                    found += 1;
+                }
+                cell.sampled = true;
+            }
+        }
+        found
+    }
+
+    /// Dig out the cells a miner reaches, returning the amounts mined in
+    /// `MineralType::LEGEND` order
+    fn dig(&mut self, unit: EnvUnit, center: UVec2) -> [f32; 9] {
+        let mut mined = [0.0; 9];
+        let Some(miner) = unit.miner_kind() else {
+            return mined;
+        };
+        for position in mining_footprint(&self.map, miner, center) {
+            let Some((mineral, amount)) = dig_cell(&mut self.map, &mut self.depletion, position) else {
+                continue;
+            };
+            if let Some(index) = MineralType::LEGEND.iter().position(|&legend| legend == mineral) {
+                mined[index] += amount;
+            }
+        }
+        for (total, amount) in self.inventory.iter_mut().zip(mined) {
+            *total += amount;
+        }
+        mined
+    }
+
+    /// Cells within a radius of a center cell
+    fn sample_area(&self, center: UVec2, radius: i32) -> Vec<UVec2> {
+        let mut cells = Vec::new();
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let cell = center.as_ivec2() + IVec2::new(dx, dy);
+                if dx * dx + dy * dy <= radius * radius && cell.x >= 0 && cell.y >= 0 {
+                    cells.push(cell.as_uvec2());
+                }
+            }
+        }
+        cells
+    }
+
+    /// The current observation
+    pub fn observe(&self) -> Observation {
+        let radius = self.config.patch_radius as i32;
+        let patches = self
+            .positions
+            .iter()
+            .map(|center| {
+                let mut patch = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (x, y) = (center.x as i32 + dx, center.y as i32 + dy);
+                        let cell = (x >= 0 && y >= 0).then(|| self.map.get(x as usize, y as usize)).flatten();
+                        patch.push(match cell {
+                            None => 255,
+                            Some(cell) if cell.mined => 1,
+                            Some(cell) if !cell.sampled => 0,
+                            Some(cell) => MineralType::LEGEND
+                                .iter()
+                                .position(|&mineral| mineral == cell.mineral_type)
+                                .map_or(1, |index| index as u8 + 2),
+                        });
+                    }
+                }
+                patch
+            })
+            .collect();
+        Observation {
+            patches,
+            positions: self.positions.to_vec(),
+            inventory: self.inventory,
+        }
+    }
+}
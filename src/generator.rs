@@ -0,0 +1,157 @@
+// Pluggable world generators. The noise generator builds the game's maps;
+// others are registered by name and picked with `--generator <name>`, so a
+// test, benchmark or imported map can be swapped in without touching the
+// game itself.
+
+use crate::structures::StructureRegistry;
+use crate::world::{arg_value, Biome, MineralCell, MineralMap, MineralType, Stratum, WorldGenParams};
+use bevy::prelude::*;
+
+const CHECKER_CELLS: usize = 16; // Side of a checkerboard square in cells
+
+// Builds a mineral map from generation parameters
+pub trait WorldGenerator: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn generate(&self, params: &WorldGenParams) -> MineralMap;
+}
+
+// The default generator: noise terrain, biomes, strata and veins, then
+// faults, the temperature field, craters and structures
+pub struct NoiseGenerator;
+
+impl WorldGenerator for NoiseGenerator {
+    fn name(&self) -> &str {
+        "noise"
+    }
+
+    fn generate(&self, params: &WorldGenParams) -> MineralMap {
+        let mut map = MineralMap::generate_with_params(params);
+        map.shear_faults(map.scaled_count(params.fault_count));
+        map.temperature = map.temperature_field(true);
+        map.place_thermal_deposits();
+        map.carve_craters(map.scaled_count(params.crater_count));
+        StructureRegistry::default().place(&mut map);
+        map
+    }
+}
+
+// Level strata of evenly spread minerals, for tests that need a predictable map
+pub struct FlatGenerator;
+
+impl WorldGenerator for FlatGenerator {
+    fn name(&self) -> &str {
+        "flat"
+    }
+
+    fn generate(&self, params: &WorldGenParams) -> MineralMap {
+        let size = params.size;
+        let data = (0..size * size)
+            .map(|index| {
+                let stratum = Stratum::at_depth((index / size) as f64 / size as f64, [0.0, 0.0]);
+                let biome = Biome::RegolithPlains;
+                MineralCell {
+                    mineral_type: MineralType::from_noise_value(0.0, stratum, biome),
+                    biome,
+                    stratum,
+                    density: 0.5,
+                    ..default()
+                }
+            })
+            .collect();
+        MineralMap::from_cells(size, size, params.seed, data)
+    }
+}
+
+// Squares alternating between empty ground and full-density minerals,
+// cycling through every minable mineral; a worst case for the map texture
+// and mining dispatch in benchmarks
+pub struct CheckerboardGenerator;
+
+impl WorldGenerator for CheckerboardGenerator {
+    fn name(&self) -> &str {
+        "checkerboard"
+    }
+
+    fn generate(&self, params: &WorldGenParams) -> MineralMap {
+        let size = params.size;
+        let minerals: Vec<MineralType> = MineralType::LEGEND.into_iter().filter(|m| m.is_minable()).collect();
+        let squares = size.div_ceil(CHECKER_CELLS);
+        let data = (0..size * size)
+            .map(|index| {
+                let (x, y) = (index % size / CHECKER_CELLS, index / size / CHECKER_CELLS);
+                let (mineral_type, density) = if (x + y) % 2 == 0 {
+                    (MineralType::Empty, 0.0)
+                } else {
+                    (minerals[(y * squares + x) / 2 % minerals.len()], 1.0)
+                };
+                MineralCell {
+                    mineral_type,
+                    stratum: Stratum::at_depth((index / size) as f64 / size as f64, [0.0, 0.0]),
+                    density,
+                    ..default()
+                }
+            })
+            .collect();
+        MineralMap::from_cells(size, size, params.seed, data)
+    }
+}
+
+// The generators available to the game by name
+#[derive(Resource)]
+pub struct GeneratorRegistry {
+    generators: Vec<Box<dyn WorldGenerator>>,
+}
+
+impl Default for GeneratorRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(NoiseGenerator);
+        registry.register(FlatGenerator);
+        registry.register(CheckerboardGenerator);
+        registry
+    }
+}
+
+impl GeneratorRegistry {
+    pub fn empty() -> Self {
+        Self { generators: Vec::new() }
+    }
+
+    pub fn register(&mut self, generator: impl WorldGenerator + 'static) {
+        self.generators.push(Box::new(generator));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.generators.iter().map(|generator| generator.name()).collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn WorldGenerator> {
+        self.generators
+            .iter()
+            .find(|generator| generator.name() == name)
+            .map(|generator| generator.as_ref())
+    }
+
+    /// Name of the generator picked with `--generator <name>`, "noise" by default
+    pub fn name_from_args(&self) -> String {
+        let Some(value) = arg_value("--generator") else {
+            return NoiseGenerator.name().to_string();
+        };
+
+        if self.get(&value).is_none() {
+            eprintln!("Unknown generator '{}', expected one of: {}", value, self.names().join(", "));
+            return NoiseGenerator.name().to_string();
+        }
+        value
+    }
+
+    /// Generate a map with the named generator, or the noise generator if
+    /// no generator has that name
+    pub fn generate(&self, name: &str, params: &WorldGenParams) -> MineralMap {
+        match self.get(name) {
+            Some(generator) => generator.generate(params),
+            None => NoiseGenerator.generate(params),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Simulation core shared by the game and the `regolith-cli` tool.
+
+pub mod ambience;
+#[cfg(feature = "rl")]
+pub mod env;
+pub mod generator;
+pub mod mining;
+pub mod structures;
+pub mod world;
@@ -1,17 +1,91 @@
+use bevy::audio::{AddAudioSource, Pitch, SpatialScale};
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
-use bevy::window::PrimaryWindow;
+use bevy::window::{MonitorSelection, PresentMode, PrimaryWindow, WindowFocused, WindowMode};
+use bevy::winit::{UpdateMode, WinitSettings};
 use bevy_egui::{egui, EguiContexts, EguiPlugin};
 use egui_arbor::{ActionIcon, DropPosition, IconType, Outliner, OutlinerActions, OutlinerNode, tree_ops::TreeOperations};
-use noise::{NoiseFn, Perlin, Fbm};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use regolith_voxel::ambience::{AmbientLayer, AmbientLevels, AmbientSound};
+use regolith_voxel::generator::GeneratorRegistry;
+use regolith_voxel::mining::{dig_cell, mining_footprint, MinerKind, RegionDepletion};
+use regolith_voxel::world::{
+    current_day, daily_seed, format_day, map_size_from_args, seed_from_args, Biome, MapSymmetry, MineralMap, MineralType, Stratum,
+    WorldGenParams, WorldGenPreset, WorldReport, MAP_SCALE, MAP_SIZES,
+};
 use std::collections::HashSet;
-
-const MAP_WIDTH: usize = 512;
-const MAP_HEIGHT: usize = 512;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod audit;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "telemetry")]
+mod telemetry;
+
+// Waypoint editing and transport routing
+const DOUBLE_CLICK_SECONDS: f32 = 0.35;
+const WAYPOINT_PICK_RADIUS: f32 = 12.0;
+const TRANSPORT_SPEED: f32 = 150.0;
+
+// Mining dispatch
+const MINER_SPEED: f32 = 100.0;
+const MINE_SECONDS: f32 = 0.5;
+const ZONE_LOAD_PENALTY: f32 = 150.0; // Extra cost per miner already in a zone, in world units
+const FAULT_SURVEY_RADIUS: f32 = 6.0; // Cells from a fault trace at which a sampler detects it
+
+// Laser drills
+const LASER_RANGE: usize = 80; // Longest channel a beam cuts, in cells
+const LASER_CELLS_PER_SECOND: f32 = 12.0;
+const LASER_BLOCKING_DENSITY: f32 = 0.9; // Solids at least this dense stop the beam
+const LASER_HEAT_PER_SECOND: f32 = 0.12; // Heat gained while firing; a drill overheats at 1.0
+const LASER_COOL_PER_SECOND: f32 = 0.2; // Heat lost while idle
+
+// Drainage preview
+const DRAINAGE_MAX_PARTICLES: usize = 4000; // Liquid cells simulated from a source region
+const DRAINAGE_DRAWN_PATHS: usize = 40; // Streamlines drawn, spread over the particles
+
+// Formation moves
+const FORMATION_SPACING: f32 = 40.0; // Minimum distance between destinations
+
+// Sprite tint for disabled units
+const DISABLED_TINT: Color = Color::srgba(0.5, 0.5, 0.5, 0.6);
+
+// Spatial audio works in world units scaled down from map pixels
+const AUDIO_SPATIAL_SCALE: f32 = 1.0 / 200.0;
+const AUDIO_EAR_GAP: f32 = 200.0;
+const AMBIENT_UPDATE_SECONDS: f32 = 1.0; // How often the soundscape re-reads the view
+const AMBIENT_SAMPLES: usize = 64; // Cells sampled along each side of the view
 
 fn main() {
-    App::new()
+    install_panic_hook();
+
+    // --daily plays today's shared challenge map instead of a random one
+    let daily_challenge = std::env::args()
+        .any(|arg| arg == "--daily")
+        .then(DailyChallenge::today);
+    // --generator swaps the noise terrain for another registered generator
+    let generators = GeneratorRegistry::default();
+    let generator = generators.name_from_args();
+    let mineral_map = match &daily_challenge {
+        Some(challenge) => MineralMap::generate_with_seed(challenge.seed, MapSymmetry::None),
+        None => generate_map(
+            &generators,
+            &generator,
+            seed_from_args().unwrap_or_else(|| thread_rng().gen()),
+            MapSymmetry::from_args(),
+            WorldGenPreset::from_args(),
+            map_size_from_args(),
+        ),
+    };
+
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "Regolith Voxel - Mining Game".to_string(),
@@ -23,174 +97,220 @@ fn main() {
         .add_plugins(EguiPlugin {
             enable_multipass_for_primary_context: false,
         })
-        .init_resource::<MineralMap>()
+        .add_plugins(FrameTimeDiagnosticsPlugin::default())
+        .add_audio_source::<AmbientSound>()
+        .insert_resource(WorldSeed::new(&mineral_map, generator))
+        .insert_resource(WorldReport::new(&mineral_map))
+        .insert_resource(SpawnSettings::seeded(mineral_map.seed))
+        .insert_resource(mineral_map)
+        .insert_resource(generators)
         .init_resource::<EquipmentTreeState>()
         .init_resource::<EquipmentTreeActions>()
         .init_resource::<SelectedEquipment>()
-        .add_systems(Startup, (setup, load_equipment_sprites))
+        .init_resource::<BatchRenameDialog>()
+        .init_resource::<PlacementMode>()
+        .init_resource::<BatchSpawn>()
+        .init_resource::<EventLog>()
+        .init_resource::<UiPanels>()
+        .init_resource::<BackgroundSettings>()
+        .init_resource::<FocusPause>()
+        .init_resource::<AccessibilitySettings>()
+        .init_resource::<LayoutSettings>()
+        .init_resource::<AlertSettings>()
+        .init_resource::<TrailSettings>()
+        .init_resource::<AmbientSettings>()
+        .init_resource::<Ambience>()
+        .init_resource::<GraphicsSettings>()
+        .init_resource::<MovementTrails>()
+        .init_resource::<OffscreenAlerts>()
+        .init_resource::<TreeDragToMap>()
+        .init_resource::<Waypoints>()
+        .init_resource::<MiningZones>()
+        .init_resource::<MiningDispatcher>()
+        .init_resource::<LaserDrills>()
+        .init_resource::<DrainagePreview>()
+        .init_resource::<Stockpile>()
+        .init_resource::<RegionDepletion>()
+        .init_resource::<CommandPalette>()
+        .init_resource::<NavigationHistory>()
+        .init_resource::<MapCursor>()
+        .init_resource::<SpectatorMode>()
+        .init_resource::<MoveOrders>()
+        .init_resource::<PlanningMode>()
+        .init_resource::<SharedRoutes>()
+        .init_resource::<CellHistory>()
+        .add_event::<GameCommand>()
+        .insert_resource(PendingCrashReport(take_pending_crash_report()))
+        .add_systems(Startup, (setup, load_equipment_sprites, start_ambience))
+        // UI panels and windows
+        .add_systems(Update, (
+            status_bar_system.before(ui_system),
+            ui_system.run_if(not_spectating),
+            debug_panel_system,
+            settings_window_system,
+            apply_ui_theme,
+            focus_pause_banner,
+            draw_offscreen_indicators,
+            inspector_system.run_if(not_spectating),
+            mining_zones_window.run_if(not_spectating),
+            world_report_window,
+            crash_report_dialog,
+            update_crash_context,
+            command_palette_system.before(apply_game_commands).run_if(not_spectating),
+            apply_game_commands.after(ui_system),
+            regenerate_world.after(ui_system),
+            toggle_temperature_overlay.after(navigation_hotkeys),
+            navigation_hotkeys.before(apply_game_commands),
+            record_selection_history.after(click_select_equipment),
+            gamepad_radial_menu.before(apply_game_commands).run_if(not_spectating),
+            spectator_system,
+        ))
+        // Window focus and alerts
+        .add_systems(Update, (
+            pause_on_focus_loss,
+            apply_background_throttle,
+            apply_graphics_settings,
+            detect_offscreen_events,
+            ambient_soundscape_system,
+        ))
+        // Camera and equipment on the map
         .add_systems(Update, (
-            ui_system,
             camera_control_system,
+            map_cursor_system
+                .before(click_select_equipment)
+                .before(issue_move_orders)
+                .run_if(not_spectating),
+            place_unpositioned_equipment.before(spawn_equipment_sprites),
             spawn_equipment_sprites,
-            click_select_equipment,
-            move_selected_equipment,
+            place_pending_equipment
+                .before(click_select_equipment)
+                .before(issue_move_orders)
+                .run_if(not_spectating),
+            place_batch_spawn
+                .before(click_select_equipment)
+                .before(issue_move_orders)
+                .run_if(not_spectating),
+            drop_tree_drag_on_map.after(ui_system).run_if(not_spectating),
+            update_home_base_marker,
+            click_select_equipment.run_if(not_spectating),
+            move_selected_equipment.run_if(not_spectating),
             update_equipment_positions,
             update_selection_outlines,
+            dim_disabled_equipment,
+            draw_tag_borders,
+            update_unit_activity.run_if(overlay_refresh_due),
+            record_movement_trails.run_if(overlay_refresh_due),
+            draw_movement_trails.after(record_movement_trails),
         ))
-        .run();
-}
-
-// Mineral types with distinct colors
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum MineralType {
-    Empty,      // Black/dark gray
-    Iron,       // Rusty orange
-    Copper,     // Copper color
-    Gold,       // Gold/yellow
-    Silver,     // Light gray/silver
-    Uranium,    // Green
-    Diamond,    // Cyan/blue
-    Coal,       // Dark gray
-}
-
-impl MineralType {
-    fn color(&self) -> Color {
-        match self {
-            MineralType::Empty => Color::srgb(0.1, 0.1, 0.15),
-            MineralType::Iron => Color::srgb(0.8, 0.4, 0.2),
-            MineralType::Copper => Color::srgb(0.72, 0.45, 0.2),
-            MineralType::Gold => Color::srgb(1.0, 0.84, 0.0),
-            MineralType::Silver => Color::srgb(0.75, 0.75, 0.75),
-            MineralType::Uranium => Color::srgb(0.2, 0.8, 0.2),
-            MineralType::Diamond => Color::srgb(0.4, 0.8, 1.0),
-            MineralType::Coal => Color::srgb(0.2, 0.2, 0.2),
-        }
-    }
-
-    fn from_noise_value(value: f64, depth: f64) -> Self {
-        // Depth affects mineral distribution (deeper = rarer minerals)
-        let depth_factor = depth / MAP_HEIGHT as f64;
+        // Waypoints and routes
+        .add_systems(Update, (
+            edit_waypoints.before(click_select_equipment).run_if(not_spectating),
+            create_waypoint_on_double_click
+                .after(edit_waypoints)
+                .after(place_pending_equipment)
+                .run_if(not_spectating),
+            sync_waypoint_markers,
+            follow_routes,
+            follow_shared_routes,
+            shared_routes_window.run_if(not_spectating),
+            draw_selected_route,
+        ))
+        // Mining zones and job dispatch
+        .add_systems(Update, (
+            designate_mining_zones
+                .before(click_select_equipment)
+                .before(issue_move_orders)
+                .run_if(not_spectating),
+            dispatch_mining_jobs.run_if(on_timer(Duration::from_millis(250))),
+            work_mining_jobs,
+            draw_mining_overlay,
+            survey_faults,
+            drainage_preview_system
+                .before(click_select_equipment)
+                .before(issue_move_orders)
+                .run_if(not_spectating),
+            aim_laser_drill.run_if(not_spectating),
+            work_laser_drills,
+            preview_manual_mining.run_if(not_spectating),
+            inspect_cells.before(click_select_equipment).before(issue_move_orders),
+        ))
+        // Group move orders
+        .add_systems(Update, (
+            issue_move_orders.after(edit_waypoints).run_if(not_spectating),
+            follow_move_orders,
+            show_move_orders,
+            draw_planned_orders,
+            planning_window.run_if(not_spectating),
+        ))
+        .add_systems(Last, limit_frame_rate);
 
-        match value {
-            v if v < -0.4 => MineralType::Empty,
-            v if v < -0.2 && depth_factor > 0.6 => MineralType::Uranium,
-            v if v < 0.0 => MineralType::Coal,
-            v if v < 0.2 => MineralType::Iron,
-            v if v < 0.4 => MineralType::Copper,
-            v if v < 0.6 && depth_factor > 0.5 => MineralType::Silver,
-            v if v < 0.8 && depth_factor > 0.7 => MineralType::Gold,
-            v if v < 1.0 && depth_factor > 0.8 => MineralType::Diamond,
-            _ => MineralType::Empty,
-        }
+    if let Some(challenge) = daily_challenge {
+        app.insert_resource(challenge)
+            .add_systems(Startup, setup_daily_challenge)
+            .add_systems(Update, daily_challenge_system);
     }
-}
-
-// Data for each cell/pixel in the map
-#[derive(Debug, Clone)]
-struct MineralCell {
-    mineral_type: MineralType,
-    density: f32,      // 0.0 to 1.0, how much mineral is present
-    sampled: bool,     // Has this cell been sampled?
-    mined: bool,       // Has this cell been mined?
-}
 
-impl Default for MineralCell {
-    fn default() -> Self {
-        Self {
-            mineral_type: MineralType::Empty,
-            density: 0.0,
-            sampled: false,
-            mined: false,
-        }
+    // --audit <ticks> hashes the world state for determinism checks
+    if let Some(audit) = audit::AuditPlugin::from_args() {
+        app.add_plugins(audit);
     }
-}
-
-// The main mineral map resource
-#[derive(Resource)]
-struct MineralMap {
-    width: usize,
-    height: usize,
-    data: Vec<MineralCell>,
-}
+    #[cfg(feature = "telemetry")]
+    app.add_plugins(telemetry::TelemetryPlugin);
+    #[cfg(feature = "remote")]
+    app.add_plugins(remote::RemotePlugin);
 
-impl Default for MineralMap {
-    fn default() -> Self {
-        Self::generate()
-    }
+    app.run();
 }
 
-impl MineralMap {
-    fn generate() -> Self {
-        let mut rng = thread_rng();
-        let seed: u32 = rng.gen();
-
-        // Create noise generators
-        let perlin = Perlin::new(seed);
-        let fbm = Fbm::<Perlin>::new(seed);
-
-        let mut data = Vec::with_capacity(MAP_WIDTH * MAP_HEIGHT);
-
-        for y in 0..MAP_HEIGHT {
-            for x in 0..MAP_WIDTH {
-                // Use multiple octaves of noise for varied terrain
-                let scale = 0.02;
-                let noise_value = fbm.get([x as f64 * scale, y as f64 * scale]);
-
-                // Add some fine detail
-                let detail = perlin.get([x as f64 * 0.1, y as f64 * 0.1]) * 0.2;
-                let combined = noise_value + detail;
-
-                let mineral_type = MineralType::from_noise_value(combined, y as f64);
-                let density = ((combined + 1.0) / 2.0) as f32; // Normalize to 0-1
-
-                data.push(MineralCell {
-                    mineral_type,
-                    density,
-                    sampled: false,
-                    mined: false,
-                });
-            }
-        }
-
-        Self {
-            width: MAP_WIDTH,
-            height: MAP_HEIGHT,
-            data,
-        }
-    }
-
-    fn get(&self, x: usize, y: usize) -> Option<&MineralCell> {
-        if x < self.width && y < self.height {
-            Some(&self.data[y * self.width + x])
-        } else {
-            None
-        }
-    }
-
-    fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut MineralCell> {
-        if x < self.width && y < self.height {
-            Some(&mut self.data[y * self.width + x])
-        } else {
-            None
-        }
-    }
+/// Generate a map with the named generator from a preset's parameters
+fn generate_map(
+    generators: &GeneratorRegistry,
+    generator: &str,
+    seed: u32,
+    symmetry: MapSymmetry,
+    preset: WorldGenPreset,
+    size: usize,
+) -> MineralMap {
+    let params = WorldGenParams {
+        seed,
+        symmetry,
+        size,
+        ..preset.params()
+    };
+    let mut map = generators.generate(generator, &params);
+    map.preset = preset;
+    map
 }
 
 // Component to mark the mineral map sprite
 #[derive(Component)]
 struct MineralMapRenderer;
 
+// Marker component for the temperature overlay drawn over the map
+#[derive(Component)]
+struct TemperatureOverlayRenderer;
+
 // Mining equipment types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum EquipmentType {
     Sampler,
     SurfaceMining,
     DeepMining,
     Refining,
     Transport,
+    LaserDrill,
 }
 
 impl EquipmentType {
+    const ALL: [EquipmentType; 6] = [
+        EquipmentType::Sampler,
+        EquipmentType::SurfaceMining,
+        EquipmentType::DeepMining,
+        EquipmentType::Refining,
+        EquipmentType::Transport,
+        EquipmentType::LaserDrill,
+    ];
+
     fn name(&self) -> &str {
         match self {
             EquipmentType::Sampler => "Sampler",
@@ -198,6 +318,7 @@ impl EquipmentType {
             EquipmentType::DeepMining => "Deep Mining",
             EquipmentType::Refining => "Refining",
             EquipmentType::Transport => "Transport",
+            EquipmentType::LaserDrill => "Laser Drill",
         }
     }
 
@@ -208,6 +329,66 @@ impl EquipmentType {
             EquipmentType::DeepMining => "Extracts minerals from deep deposits",
             EquipmentType::Refining => "Processes raw minerals into refined materials",
             EquipmentType::Transport => "Moves resources between locations",
+            EquipmentType::LaserDrill => "Cuts a narrow straight channel toward a target",
+        }
+    }
+
+    /// Travel speed in world units per second
+    fn move_speed(&self) -> f32 {
+        match self {
+            EquipmentType::Sampler => 120.0,
+            EquipmentType::SurfaceMining | EquipmentType::DeepMining | EquipmentType::LaserDrill => MINER_SPEED,
+            EquipmentType::Refining => 60.0,
+            EquipmentType::Transport => TRANSPORT_SPEED,
+        }
+    }
+
+    fn is_miner(&self) -> bool {
+        self.miner_kind().is_some()
+    }
+
+    /// The digging rules this equipment follows, if it is a miner
+    fn miner_kind(&self) -> Option<MinerKind> {
+        match self {
+            EquipmentType::SurfaceMining => Some(MinerKind::Surface),
+            EquipmentType::DeepMining => Some(MinerKind::Deep),
+            _ => None,
+        }
+    }
+
+    /// RGBA color of the generated map sprite
+    fn sprite_color(&self) -> [u8; 4] {
+        match self {
+            EquipmentType::Sampler => [100, 200, 255, 255],       // Light blue
+            EquipmentType::SurfaceMining => [255, 200, 100, 255], // Orange
+            EquipmentType::DeepMining => [200, 100, 255, 255],    // Purple
+            EquipmentType::Refining => [255, 100, 100, 255],      // Red
+            EquipmentType::Transport => [100, 255, 100, 255],     // Green
+            EquipmentType::LaserDrill => [255, 90, 200, 255],     // Magenta
+        }
+    }
+
+    /// Prefix used when naming newly spawned units
+    fn unit_name(&self) -> &str {
+        match self {
+            EquipmentType::Sampler => "Sampler",
+            EquipmentType::SurfaceMining => "Surface Miner",
+            EquipmentType::DeepMining => "Deep Miner",
+            EquipmentType::Refining => "Refinery",
+            EquipmentType::Transport => "Transport",
+            EquipmentType::LaserDrill => "Laser Drill",
+        }
+    }
+
+    /// Name of the default container this equipment type is grouped into
+    fn container_name(&self) -> &str {
+        match self {
+            EquipmentType::Sampler => "Samplers",
+            EquipmentType::SurfaceMining => "Surface Mining",
+            EquipmentType::DeepMining => "Deep Mining",
+            EquipmentType::Refining => "Refining",
+            EquipmentType::Transport => "Transport",
+            EquipmentType::LaserDrill => "Laser Drills",
         }
     }
 
@@ -218,6 +399,8 @@ impl EquipmentType {
             EquipmentType::DeepMining => "sprites/deep_mining.png",
             EquipmentType::Refining => "sprites/refining.png",
             EquipmentType::Transport => "sprites/transport.png",
+            // Laser drills share the deep miner's sprite until they get their own
+            EquipmentType::LaserDrill => "sprites/deep_mining.png",
         }
     }
 }
@@ -229,16 +412,125 @@ struct EquipmentTreeNode {
     name: String,
     node_type: NodeType,
     position: Option<Vec2>,
-    active: bool,
+    active: bool, // Operational state; disabled units are paused
+    route: Option<Route>,
+    patrol: Option<PatrolAssignment>,
+    tag: Option<ColorTag>, // Units without a tag use their nearest tagged container's
+    activity: UnitActivity, // Refreshed every frame for the tree's activity icons
     children: Vec<EquipmentTreeNode>,
 }
 
+// What a unit is doing right now, shown as its icon in the tree
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnitActivity {
+    Idle,
+    Disabled,
+    Moving { frame: usize },
+    Digging { progress: f32 }, // 0..1 through the current dig
+    Stalled, // Has orders but hasn't moved for a while
+}
+
+impl UnitActivity {
+    const SPINNER: [&str; 4] = ["◐", "◓", "◑", "◒"];
+    const LEVELS: [&str; 8] = ["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
+
+    fn icon(&self) -> Option<&str> {
+        match self {
+            UnitActivity::Idle => None,
+            UnitActivity::Disabled => Some("⏸"),
+            UnitActivity::Moving { frame } => Some(Self::SPINNER[frame % Self::SPINNER.len()]),
+            UnitActivity::Digging { progress } => {
+                let level = (progress * Self::LEVELS.len() as f32) as usize;
+                Some(Self::LEVELS[level.min(Self::LEVELS.len() - 1)])
+            }
+            UnitActivity::Stalled => Some("⚠"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum NodeType {
     Container,
     Equipment(EquipmentType),
 }
 
+// Color tags for grouping units visually on the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorTag {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Cyan,
+    Blue,
+    Purple,
+    Pink,
+}
+
+impl ColorTag {
+    const ALL: [ColorTag; 8] = [
+        ColorTag::Red,
+        ColorTag::Orange,
+        ColorTag::Yellow,
+        ColorTag::Green,
+        ColorTag::Cyan,
+        ColorTag::Blue,
+        ColorTag::Purple,
+        ColorTag::Pink,
+    ];
+
+    fn name(&self) -> &str {
+        match self {
+            ColorTag::Red => "Red",
+            ColorTag::Orange => "Orange",
+            ColorTag::Yellow => "Yellow",
+            ColorTag::Green => "Green",
+            ColorTag::Cyan => "Cyan",
+            ColorTag::Blue => "Blue",
+            ColorTag::Purple => "Purple",
+            ColorTag::Pink => "Pink",
+        }
+    }
+
+    fn rgb(&self) -> [u8; 3] {
+        match self {
+            ColorTag::Red => [230, 60, 60],
+            ColorTag::Orange => [240, 140, 40],
+            ColorTag::Yellow => [240, 220, 50],
+            ColorTag::Green => [70, 200, 90],
+            ColorTag::Cyan => [60, 210, 220],
+            ColorTag::Blue => [60, 110, 240],
+            ColorTag::Purple => [160, 90, 230],
+            ColorTag::Pink => [240, 110, 190],
+        }
+    }
+
+    fn color(&self) -> Color {
+        let [r, g, b] = self.rgb();
+        Color::srgb_u8(r, g, b)
+    }
+
+    fn color32(&self) -> egui::Color32 {
+        let [r, g, b] = self.rgb();
+        egui::Color32::from_rgb(r, g, b)
+    }
+}
+
+/// Small color swatch button for picking a tag; `None` shows a "no tag" button
+fn tag_swatch(ui: &mut egui::Ui, tag: Option<ColorTag>, selected: bool) -> egui::Response {
+    let button = match tag {
+        Some(tag) => egui::Button::new("").fill(tag.color32()),
+        None => egui::Button::new("∅"),
+    };
+    let stroke = if selected {
+        egui::Stroke::new(2.0, egui::Color32::WHITE)
+    } else {
+        egui::Stroke::NONE
+    };
+    ui.add(button.stroke(stroke).min_size(egui::vec2(16.0, 16.0)))
+        .on_hover_text(tag.as_ref().map_or("No tag", ColorTag::name))
+}
+
 impl EquipmentTreeNode {
     fn container(id: usize, name: impl Into<String>) -> Self {
         Self {
@@ -247,6 +539,10 @@ impl EquipmentTreeNode {
             node_type: NodeType::Container,
             position: None,
             active: false,
+            route: None,
+            patrol: None,
+            tag: None,
+            activity: UnitActivity::Idle,
             children: Vec::new(),
         }
     }
@@ -257,7 +553,11 @@ impl EquipmentTreeNode {
             name: name.into(),
             node_type: NodeType::Equipment(equipment_type),
             position: None,
-            active: false,
+            active: true,
+            route: None,
+            patrol: None,
+            tag: None,
+            activity: UnitActivity::Idle,
             children: Vec::new(),
         }
     }
@@ -318,6 +618,186 @@ impl EquipmentTreeNode {
 
         None
     }
+
+    /// Recursively find the parent of the node with the given ID
+    fn find_parent(&self, id: usize) -> Option<&EquipmentTreeNode> {
+        if self.children.iter().any(|child| child.id == id) {
+            return Some(self);
+        }
+
+        self.children.iter().find_map(|child| child.find_parent(id))
+    }
+
+    /// Collect this node's ID and its descendants' IDs in display order
+    fn collect_ids(&self, ids: &mut Vec<usize>) {
+        ids.push(self.id);
+        for child in &self.children {
+            child.collect_ids(ids);
+        }
+    }
+
+    /// Copy of this subtree keeping only units tagged `tag` (directly or through
+    /// a container) and the containers leading to them
+    fn filter_by_tag(&self, tag: ColorTag, inherited: Option<ColorTag>) -> Option<EquipmentTreeNode> {
+        let effective = self.tag.or(inherited);
+        let children: Vec<_> = self
+            .children
+            .iter()
+            .filter_map(|child| child.filter_by_tag(tag, effective))
+            .collect();
+        if effective != Some(tag) && children.is_empty() {
+            return None;
+        }
+        Some(EquipmentTreeNode {
+            children,
+            ..self.clone()
+        })
+    }
+
+    /// Sort rank from what this node or its busiest descendant is doing:
+    /// working units first, then idle, then disabled
+    fn activity_rank(&self) -> u8 {
+        let own = if self.is_container() {
+            u8::MAX
+        } else {
+            match self.activity {
+                UnitActivity::Idle => 1,
+                UnitActivity::Disabled => 2,
+                _ => 0,
+            }
+        };
+        self.children
+            .iter()
+            .map(|child| child.activity_rank())
+            .fold(own, u8::min)
+    }
+
+    /// Distance from a point to this node, or to its closest positioned descendant
+    fn distance_to(&self, point: Vec2) -> Option<f32> {
+        let own = self.position.map(|pos| pos.distance(point));
+        self.children
+            .iter()
+            .filter_map(|child| child.distance_to(point))
+            .chain(own)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    /// Recursively sort this node's children
+    fn sort_children(&mut self, mode: TreeSortMode, origin: Option<Vec2>) {
+        sort_nodes(&mut self.children, mode, origin);
+        for child in &mut self.children {
+            child.sort_children(mode, origin);
+        }
+    }
+}
+
+// How a unit treats the end of its route
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RouteMode {
+    OneShot,
+    Patrol,
+}
+
+// Ordered list of waypoints a transport drives through
+#[derive(Debug, Clone)]
+struct Route {
+    waypoints: Vec<usize>,
+    mode: RouteMode,
+    next: usize, // Index of the waypoint currently being driven to
+}
+
+impl Route {
+    fn new() -> Self {
+        Self {
+            waypoints: Vec::new(),
+            mode: RouteMode::OneShot,
+            next: 0,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.next >= self.waypoints.len()
+    }
+
+    /// Move on to the next waypoint, looping back to the first on a patrol
+    fn advance(&mut self) {
+        self.next += 1;
+        if self.mode == RouteMode::Patrol && self.is_complete() {
+            self.next = 0;
+        }
+    }
+
+    /// Remove the waypoint at an index, keeping `next` on the same stop.
+    /// A patrol that was heading to the removed last stop wraps to the first.
+    fn remove_at(&mut self, index: usize) {
+        self.waypoints.remove(index);
+        if index < self.next {
+            self.next -= 1;
+        }
+        if self.mode == RouteMode::Patrol && !self.waypoints.is_empty() {
+            self.next %= self.waypoints.len();
+        }
+    }
+}
+
+// A stop on a shared route and how long units wait there
+#[derive(Debug, Clone)]
+struct RouteStop {
+    waypoint: usize,
+    dwell: f32, // Seconds
+}
+
+// A named patrol route that any number of units can follow
+#[derive(Debug, Clone)]
+struct SharedRoute {
+    id: usize,
+    name: String,
+    stops: Vec<RouteStop>,
+}
+
+// A unit's progress along a shared route
+#[derive(Debug, Clone)]
+struct PatrolAssignment {
+    route_id: usize,
+    next: usize,                 // Index of the stop currently being driven to
+    dwell_remaining: Option<f32>, // Set while waiting at a stop
+}
+
+// Orderings available from the outliner's Sort menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TreeSortMode {
+    Alphabetical,
+    ByType,
+    ByDistance,
+    ByActivity,
+}
+
+/// Sort a list of sibling nodes in place (stable, so equal nodes keep their order)
+fn sort_nodes(nodes: &mut [EquipmentTreeNode], mode: TreeSortMode, origin: Option<Vec2>) {
+    match mode {
+        TreeSortMode::Alphabetical => {
+            nodes.sort_by_key(|node| node.name.to_lowercase());
+        }
+        TreeSortMode::ByType => {
+            // Containers first, then equipment grouped by type
+            nodes.sort_by_key(|node| node.equipment_type());
+        }
+        TreeSortMode::ByDistance => {
+            let Some(origin) = origin else {
+                return;
+            };
+            // Nodes without a position go last
+            nodes.sort_by(|a, b| match (a.distance_to(origin), b.distance_to(origin)) {
+                (Some(a), Some(b)) => a.total_cmp(&b),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            });
+        }
+        TreeSortMode::ByActivity => {
+            nodes.sort_by_key(|node| node.activity_rank());
+        }
+    }
 }
 
 // Implement OutlinerNode for the tree
@@ -347,16 +827,28 @@ impl OutlinerNode for EquipmentTreeNode {
     fn icon(&self) -> Option<IconType> {
         if self.is_container() {
             Some(IconType::Collection)
+        } else if let Some(icon) = self.activity.icon() {
+            Some(IconType::Custom(icon.to_string()))
         } else {
             Some(IconType::Entity)
         }
     }
 
     fn action_icons(&self) -> Vec<ActionIcon> {
-        vec![ActionIcon::Visibility, ActionIcon::Selection]
+        let mut icons = vec![ActionIcon::Visibility, ActionIcon::Selection];
+        if !self.is_container() {
+            icons.push(ActionIcon::Custom {
+                icon: POWER_ICON.to_string(),
+                tooltip: Some("Enable / disable this unit".to_string()),
+            });
+        }
+        icons
     }
 }
 
+// Tree action icon toggling a unit's operational state
+const POWER_ICON: &str = "⏻";
+
 // Implement TreeOperations for drag-drop functionality
 impl TreeOperations for EquipmentTreeNode {}
 
@@ -365,6 +857,38 @@ impl TreeOperations for EquipmentTreeNode {}
 struct EquipmentTreeState {
     nodes: Vec<EquipmentTreeNode>,
     next_id: usize,
+    undo_moves: Vec<TreeMove>,
+}
+
+// Position of a node among its siblings
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TreeLocation {
+    parent: Option<usize>, // None for the root level
+    index: usize,
+}
+
+// One drag-drop move, with where each moved node was before it moved
+#[derive(Debug, Clone)]
+struct TreeMove {
+    origins: Vec<(usize, TreeLocation)>,
+}
+
+// Why a drag-drop move was rejected
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TreeMoveError {
+    MissingNode(usize),
+    NotAContainer(usize),
+    IntoOwnDescendant(usize),
+}
+
+impl TreeMoveError {
+    fn message(&self) -> String {
+        match self {
+            TreeMoveError::MissingNode(id) => format!("Node #{} no longer exists", id),
+            TreeMoveError::NotAContainer(id) => format!("Node #{} is not a container", id),
+            TreeMoveError::IntoOwnDescendant(id) => format!("Can't move node #{} into itself", id),
+        }
+    }
 }
 
 impl Default for EquipmentTreeState {
@@ -416,16 +940,24 @@ impl Default for EquipmentTreeState {
                 next_id += 1;
                 container
             },
+            {
+                let container = EquipmentTreeNode::container(next_id, "Laser Drills");
+                next_id += 1;
+                container
+            },
         ];
 
         Self {
             nodes,
             next_id,
+            undo_moves: Vec::new(),
         }
     }
 }
 
 impl EquipmentTreeState {
+    const MAX_UNDO: usize = 50;
+
     fn add_container(&mut self, name: String) {
         let container = EquipmentTreeNode::container(self.next_id, name);
         self.next_id += 1;
@@ -442,44 +974,504 @@ impl EquipmentTreeState {
         id
     }
 
-    fn find_node(&self, id: usize) -> Option<&EquipmentTreeNode> {
-        for node in &self.nodes {
-            if let Some(found) = node.find_node(id) {
-                return Some(found);
-            }
+    /// Add a new container holding units of one type at the given positions,
+    /// named from a naming template. Returns the container's ID.
+    fn add_group(
+        &mut self,
+        name: String,
+        equipment_type: EquipmentType,
+        positions: &[Vec2],
+        name_template: &str,
+    ) -> usize {
+        let mut container = EquipmentTreeNode::container(self.next_id, name);
+        self.next_id += 1;
+
+        for (i, &position) in positions.iter().enumerate() {
+            let context = NameTemplateContext {
+                type_name: equipment_type.unit_name(),
+                container: &container.name,
+                index: i + 1,
+                n: i + 1,
+                id: self.next_id,
+            };
+            let mut unit = EquipmentTreeNode::equipment(
+                self.next_id,
+                apply_name_template(name_template, &context),
+                equipment_type,
+            );
+            unit.position = Some(position);
+            container.children.push(unit);
+            self.next_id += 1;
         }
-        None
+
+        let id = container.id;
+        self.nodes.push(container);
+        id
     }
 
-    fn find_node_mut(&mut self, id: usize) -> Option<&mut EquipmentTreeNode> {
+    /// Sort the whole tree, recursing into containers
+    fn sort(&mut self, mode: TreeSortMode, origin: Option<Vec2>) {
+        sort_nodes(&mut self.nodes, mode, origin);
         for node in &mut self.nodes {
-            if let Some(found) = node.find_node_mut(id) {
-                return Some(found);
-            }
+            node.sort_children(mode, origin);
         }
-        None
     }
-}
-
-// Actions handler for the outliner
-#[derive(Resource, Default)]
-struct EquipmentTreeActions {
-    selected: HashSet<usize>,
-    visible: HashSet<usize>,
-}
 
-impl EquipmentTreeActions {
-    fn new() -> Self {
-        Self {
-            selected: HashSet::new(),
-            visible: HashSet::new(),
+    /// Move loose root-level equipment into the container matching its type,
+    /// creating the container if it doesn't exist yet
+    fn auto_group_by_type(&mut self) {
+        let (loose, mut kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .partition(|node| !node.is_container());
+
+        for node in loose {
+            let Some(equipment_type) = node.equipment_type() else {
+                continue;
+            };
+
+            let container_name = equipment_type.container_name();
+            let container = match kept
+                .iter()
+                .position(|n| n.is_container() && n.name == container_name)
+            {
+                Some(idx) => &mut kept[idx],
+                None => {
+                    kept.push(EquipmentTreeNode::container(self.next_id, container_name));
+                    self.next_id += 1;
+                    kept.last_mut().unwrap()
+                }
+            };
+            container.children.push(node);
         }
-    }
-}
 
-impl OutlinerActions<EquipmentTreeNode> for EquipmentTreeActions {
-    fn on_rename(&mut self, _id: &usize, _new_name: String) {
-        // Renaming is handled in the ui_system
+        self.nodes = kept;
+    }
+
+    fn rename_node(&mut self, id: usize, new_name: String) -> bool {
+        self.nodes
+            .iter_mut()
+            .any(|root| root.rename_node(id, new_name.clone()))
+    }
+
+    /// Where a node sits: its parent container (None at the root level) and index
+    fn location(&self, id: usize) -> Option<TreeLocation> {
+        let parent = self.find_parent(id).map(|parent| parent.id);
+        let index = self.siblings(parent)?.iter().position(|n| n.id == id)?;
+        Some(TreeLocation { parent, index })
+    }
+
+    fn siblings(&self, parent: Option<usize>) -> Option<&Vec<EquipmentTreeNode>> {
+        match parent {
+            None => Some(&self.nodes),
+            Some(id) => self.find_node(id).map(|node| &node.children),
+        }
+    }
+
+    fn siblings_mut(&mut self, parent: Option<usize>) -> Option<&mut Vec<EquipmentTreeNode>> {
+        match parent {
+            None => Some(&mut self.nodes),
+            Some(id) => self.find_node_mut(id).map(|node| &mut node.children),
+        }
+    }
+
+    /// Detach a node (and its subtree) from wherever it is in the tree
+    fn take_node(&mut self, id: usize) -> Option<EquipmentTreeNode> {
+        let location = self.location(id)?;
+        Some(self.siblings_mut(location.parent)?.remove(location.index))
+    }
+
+    /// Insert a node at a location, clamping the index to the sibling count.
+    /// Falls back to the end of the root level if the parent no longer exists.
+    fn insert_at(&mut self, location: TreeLocation, node: EquipmentTreeNode) {
+        let siblings = match self.siblings_mut(location.parent) {
+            Some(siblings) => siblings,
+            None => &mut self.nodes,
+        };
+        let index = location.index.min(siblings.len());
+        siblings.insert(index, node);
+    }
+
+    /// True if `id` is somewhere below `ancestor` in the tree
+    fn is_descendant(&self, id: usize, ancestor: usize) -> bool {
+        self.find_node(ancestor)
+            .is_some_and(|node| node.id != id && node.find_node(id).is_some())
+    }
+
+    /// Check that dropping `ids` relative to `target_id` is a valid move
+    fn validate_move(&self, ids: &[usize], target_id: usize, position: DropPosition) -> Result<(), TreeMoveError> {
+        let target = self.find_node(target_id).ok_or(TreeMoveError::MissingNode(target_id))?;
+        if matches!(position, DropPosition::Inside) && !target.is_container() {
+            return Err(TreeMoveError::NotAContainer(target_id));
+        }
+        for &id in ids {
+            if self.find_node(id).is_none() {
+                return Err(TreeMoveError::MissingNode(id));
+            }
+            if id == target_id || self.is_descendant(target_id, id) {
+                return Err(TreeMoveError::IntoOwnDescendant(id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Move nodes relative to a drop target as a single edit. The whole move is
+    /// validated up front, so either every node moves or nothing changes.
+    /// Successful moves are recorded on the undo stack.
+    fn move_nodes(&mut self, ids: &[usize], target_id: usize, position: DropPosition) -> Result<(), TreeMoveError> {
+        // Nodes under another dragged node move along with it
+        let mut moving: Vec<usize> = Vec::new();
+        for &id in ids {
+            if !moving.contains(&id) && !ids.iter().any(|&other| self.is_descendant(id, other)) {
+                moving.push(id);
+            }
+        }
+        if moving.is_empty() {
+            return Ok(());
+        }
+        self.validate_move(&moving, target_id, position)?;
+
+        let mut origins = Vec::with_capacity(moving.len());
+        let mut anchor = target_id;
+        for id in moving {
+            let origin = self.location(id).ok_or(TreeMoveError::MissingNode(id))?;
+            let node = self.take_node(id).ok_or(TreeMoveError::MissingNode(id))?;
+            origins.push((id, origin));
+
+            // Validation guarantees the target is still in the tree
+            let target = self.location(anchor).ok_or(TreeMoveError::MissingNode(anchor))?;
+            let destination = match position {
+                DropPosition::Before => target,
+                DropPosition::After => TreeLocation { index: target.index + 1, ..target },
+                DropPosition::Inside => TreeLocation {
+                    parent: Some(target_id),
+                    index: usize::MAX,
+                },
+            };
+            self.insert_at(destination, node);
+
+            // Keep the dragged nodes in order when dropping after the target
+            if matches!(position, DropPosition::After) {
+                anchor = id;
+            }
+        }
+
+        self.undo_moves.push(TreeMove { origins });
+        if self.undo_moves.len() > Self::MAX_UNDO {
+            self.undo_moves.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Undo the most recent move, putting each node back where it came from.
+    /// Returns false if there is nothing to undo.
+    fn undo_move(&mut self) -> bool {
+        let Some(last) = self.undo_moves.pop() else {
+            return false;
+        };
+        for (id, origin) in last.origins.into_iter().rev() {
+            if let Some(node) = self.take_node(id) {
+                self.insert_at(origin, node);
+            }
+        }
+        true
+    }
+
+    fn find_node(&self, id: usize) -> Option<&EquipmentTreeNode> {
+        for node in &self.nodes {
+            if let Some(found) = node.find_node(id) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn find_node_mut(&mut self, id: usize) -> Option<&mut EquipmentTreeNode> {
+        for node in &mut self.nodes {
+            if let Some(found) = node.find_node_mut(id) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    fn find_parent(&self, id: usize) -> Option<&EquipmentTreeNode> {
+        self.nodes.iter().find_map(|node| node.find_parent(id))
+    }
+
+    /// Remove a deleted waypoint from every route that uses it
+    fn remove_waypoint_from_routes(&mut self, waypoint_id: usize) {
+        for id in self.all_ids() {
+            let Some(route) = self.find_node_mut(id).and_then(|node| node.route.as_mut()) else {
+                continue;
+            };
+            if let Some(idx) = route.waypoints.iter().position(|&w| w == waypoint_id) {
+                route.remove_at(idx);
+            }
+        }
+    }
+
+    /// IDs of units patrolling a shared route, in ID order
+    fn patrol_units(&self, route_id: usize) -> Vec<usize> {
+        let mut ids: Vec<usize> = self
+            .all_ids()
+            .into_iter()
+            .filter(|&id| {
+                self.find_node(id)
+                    .and_then(|node| node.patrol.as_ref())
+                    .is_some_and(|patrol| patrol.route_id == route_id)
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Put units on a shared route, spreading everyone on it evenly across its stops
+    fn assign_patrol(&mut self, ids: &[usize], route: &SharedRoute) {
+        for &id in ids {
+            if let Some(node) = self.find_node_mut(id) {
+                // A shared route replaces the unit's own route
+                node.route = None;
+                node.patrol = Some(PatrolAssignment {
+                    route_id: route.id,
+                    next: 0,
+                    dwell_remaining: None,
+                });
+            }
+        }
+
+        let units = self.patrol_units(route.id);
+        let stop_count = route.stops.len().max(1);
+        for (i, id) in units.iter().enumerate() {
+            if let Some(patrol) = self.find_node_mut(*id).and_then(|node| node.patrol.as_mut()) {
+                patrol.next = i * stop_count / units.len();
+                patrol.dwell_remaining = None;
+            }
+        }
+    }
+
+    /// Take every unit off a shared route
+    fn clear_patrol(&mut self, route_id: usize) {
+        for id in self.patrol_units(route_id) {
+            if let Some(node) = self.find_node_mut(id) {
+                node.patrol = None;
+            }
+        }
+    }
+
+    /// A node's own tag, or else the tag of its nearest tagged container
+    fn effective_tag(&self, id: usize) -> Option<ColorTag> {
+        let mut node = self.find_node(id)?;
+        loop {
+            if node.tag.is_some() {
+                return node.tag;
+            }
+            node = self.find_parent(node.id)?;
+        }
+    }
+
+    /// True if the unit exists and is enabled
+    fn is_enabled(&self, id: usize) -> bool {
+        self.find_node(id).is_some_and(|node| node.active)
+    }
+
+    /// Count equipment nodes as (working, idle, disabled) by their activity.
+    /// Stalled units still have orders, so they count as working.
+    fn equipment_counts(&self) -> (usize, usize, usize) {
+        self.all_ids()
+            .into_iter()
+            .filter_map(|id| self.find_node(id))
+            .filter(|node| !node.is_container())
+            .fold((0, 0, 0), |(working, idle, disabled), node| match node.activity {
+                UnitActivity::Idle => (working, idle + 1, disabled),
+                UnitActivity::Disabled => (working, idle, disabled + 1),
+                _ => (working + 1, idle, disabled),
+            })
+    }
+
+    /// All node IDs in display order (depth-first)
+    fn all_ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        for node in &self.nodes {
+            node.collect_ids(&mut ids);
+        }
+        ids
+    }
+
+    /// Compute new names for the given nodes from a naming template.
+    /// `index` in the template counts from 1 in the order the IDs are given.
+    fn preview_batch_rename(&self, ids: &[usize], template: &str) -> Vec<(usize, String)> {
+        ids.iter()
+            .enumerate()
+            .filter_map(|(i, &id)| {
+                let node = self.find_node(id)?;
+                let parent = self.find_parent(id);
+                let siblings = parent.map_or(&self.nodes[..], |p| &p.children[..]);
+                let n = siblings.iter().position(|s| s.id == id).unwrap_or(0) + 1;
+                let equipment_type = node.equipment_type();
+
+                let context = NameTemplateContext {
+                    type_name: equipment_type.as_ref().map_or("Container", |t| t.name()),
+                    container: parent.map_or("", |p| p.name.as_str()),
+                    index: i + 1,
+                    n,
+                    id,
+                };
+                Some((id, apply_name_template(template, &context)))
+            })
+            .collect()
+    }
+
+    /// Check proposed names for empties and duplicates, both within the batch
+    /// and against nodes outside of it
+    fn validate_batch_rename(&self, renames: &[(usize, String)]) -> Vec<String> {
+        let mut errors = Vec::new();
+        let renamed_ids: HashSet<usize> = renames.iter().map(|(id, _)| *id).collect();
+        let mut seen = HashSet::new();
+
+        for (_, name) in renames {
+            if name.trim().is_empty() {
+                errors.push("Names cannot be empty".to_string());
+            } else if !seen.insert(name.as_str()) {
+                errors.push(format!("\"{}\" would be used more than once", name));
+            }
+        }
+
+        for id in self.all_ids() {
+            if renamed_ids.contains(&id) {
+                continue;
+            }
+            if let Some(node) = self.find_node(id) {
+                if seen.contains(node.name.as_str()) {
+                    errors.push(format!("\"{}\" is already used by another node", node.name));
+                }
+            }
+        }
+
+        errors.dedup();
+        errors
+    }
+}
+
+// Values available to naming template placeholders
+struct NameTemplateContext<'a> {
+    type_name: &'a str,
+    container: &'a str,
+    index: usize,
+    n: usize,
+    id: usize,
+}
+
+/// Expand a naming template such as `{type}-{index:03}` or `{container} {n}`.
+///
+/// Supported placeholders are `{type}`, `{container}`, `{index}` (position in
+/// the batch), `{n}` (position within the parent) and `{id}`. Numeric
+/// placeholders accept a zero-padded width like `{index:03}`. Unknown
+/// placeholders are kept verbatim.
+fn apply_name_template(template: &str, context: &NameTemplateContext) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+
+        let placeholder = &rest[start + 1..start + len];
+        let (key, width) = match placeholder.split_once(':') {
+            Some((key, spec)) => (key, spec.parse::<usize>().ok()),
+            None => (placeholder, None),
+        };
+
+        let number = match key {
+            "index" => Some(context.index),
+            "n" => Some(context.n),
+            "id" => Some(context.id),
+            _ => None,
+        };
+
+        match (key, number) {
+            (_, Some(number)) => {
+                result.push_str(&format!("{:0width$}", number, width = width.unwrap_or(0)));
+            }
+            ("type", None) => result.push_str(context.type_name),
+            ("container", None) => result.push_str(context.container),
+            _ => result.push_str(&rest[start..start + len + 1]),
+        }
+
+        rest = &rest[start + len + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+// Resource holding the current world seed, so a map can be shared and
+// regenerated, and the state of the New World dialog
+#[derive(Resource)]
+struct WorldSeed {
+    seed: u32,
+    symmetry: MapSymmetry,
+    preset: WorldGenPreset,
+    size: usize, // Cells on a side
+    generator: String, // Name of the world generator new maps are built with
+    dialog_open: bool,
+    input: String,
+    image_path: String, // PNG to load with "Load Map Image…"
+}
+
+impl WorldSeed {
+    fn new(mineral_map: &MineralMap, generator: String) -> Self {
+        Self {
+            seed: mineral_map.seed,
+            symmetry: mineral_map.symmetry,
+            preset: mineral_map.preset,
+            size: mineral_map.width,
+            generator,
+            dialog_open: false,
+            input: mineral_map.seed.to_string(),
+            image_path: String::new(),
+        }
+    }
+}
+
+// State for the batch rename dialog
+#[derive(Resource)]
+struct BatchRenameDialog {
+    open: bool,
+    template: String,
+}
+
+impl Default for BatchRenameDialog {
+    fn default() -> Self {
+        Self {
+            open: false,
+            template: "{type}-{index:03}".to_string(),
+        }
+    }
+}
+
+// Actions handler for the outliner
+#[derive(Resource, Default)]
+struct EquipmentTreeActions {
+    selected: HashSet<usize>,
+    visible: HashSet<usize>,
+    power_toggles: Vec<usize>, // Units toggled from the tree, applied by ui_system
+}
+
+impl EquipmentTreeActions {
+    fn new() -> Self {
+        Self {
+            selected: HashSet::new(),
+            visible: HashSet::new(),
+            power_toggles: Vec::new(),
+        }
+    }
+}
+
+impl OutlinerActions<EquipmentTreeNode> for EquipmentTreeActions {
+    fn on_rename(&mut self, _id: &usize, _new_name: String) {
+        // Renaming is handled in the ui_system
     }
 
     fn on_move(&mut self, _id: &usize, _target: &usize, _position: DropPosition) {
@@ -521,7 +1513,11 @@ impl OutlinerActions<EquipmentTreeNode> for EquipmentTreeActions {
         self.on_select(id, !is_selected);
     }
 
-    fn on_custom_action(&mut self, _id: &usize, _icon: &str) {}
+    fn on_custom_action(&mut self, id: &usize, icon: &str) {
+        if icon == POWER_ICON {
+            self.power_toggles.push(*id);
+        }
+    }
 }
 
 // Resource to store equipment sprites
@@ -548,102 +1544,1233 @@ struct SelectedEquipment {
     selected_id: Option<usize>,
 }
 
-fn setup(
-    mut commands: Commands,
-    mut images: ResMut<Assets<Image>>,
-    mineral_map: Res<MineralMap>,
-) {
-    // Setup 2D camera
-    commands.spawn(Camera2d);
+// A single entry in the event log
+#[derive(Debug, Clone)]
+struct LogEntry {
+    time: f32,
+    message: String,
+    position: Option<Vec2>,
+}
 
-    // Create the image from mineral data
-    let mut image_data = Vec::with_capacity(MAP_WIDTH * MAP_HEIGHT * 4);
-
-    for cell in &mineral_map.data {
-        let color = cell.mineral_type.color();
-        // Adjust brightness by density
-        let brightness = 0.5 + cell.density * 0.5;
-        image_data.push((color.to_srgba().red * brightness * 255.0) as u8);
-        image_data.push((color.to_srgba().green * brightness * 255.0) as u8);
-        image_data.push((color.to_srgba().blue * brightness * 255.0) as u8);
-        image_data.push(255);
-    }
-
-    let image = Image::new(
-        Extent3d {
-            width: MAP_WIDTH as u32,
-            height: MAP_HEIGHT as u32,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        image_data,
-        TextureFormat::Rgba8UnormSrgb,
-        Default::default(),
-    );
+// Resource holding recent game events, newest last
+#[derive(Resource, Default)]
+struct EventLog {
+    entries: Vec<LogEntry>,
+    total_pushed: usize, // Count of all entries ever pushed, including trimmed ones
+}
 
-    let image_handle = images.add(image);
+impl EventLog {
+    const MAX_ENTRIES: usize = 200;
 
-    // Spawn the mineral map sprite
-    commands.spawn((
-        Sprite::from_image(image_handle),
-        Transform::from_scale(Vec3::splat(2.0)), // Scale up for visibility
-        MineralMapRenderer,
-    ));
-}
+    fn push(&mut self, time: f32, message: impl Into<String>, position: Option<Vec2>) {
+        self.total_pushed += 1;
+        self.entries.push(LogEntry {
+            time,
+            message: message.into(),
+            position,
+        });
+        if self.entries.len() > Self::MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
 
-// Camera controls: WASD to pan, Q/E to zoom
-fn camera_control_system(
-    time: Res<Time>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut query: Query<&mut Transform, With<Camera>>,
-) {
-    let Ok(mut camera_transform) = query.single_mut() else {
-        return;
-    };
+    fn latest(&self) -> Option<&LogEntry> {
+        self.entries.last()
+    }
 
-    let pan_speed = 300.0 * time.delta_secs();
-    let zoom_speed = 2.0 * time.delta_secs();
+    /// Entries pushed since `seen` entries had been pushed in total
+    fn since(&self, seen: usize) -> &[LogEntry] {
+        let new = self.total_pushed.saturating_sub(seen).min(self.entries.len());
+        &self.entries[self.entries.len() - new..]
+    }
 
-    // Pan with WASD
-    if keyboard.pressed(KeyCode::KeyW) {
-        camera_transform.translation.y += pan_speed;
+    /// Drop all but the most recent entries and release unused capacity
+    fn compact(&mut self, keep: usize) {
+        let excess = self.entries.len().saturating_sub(keep);
+        self.entries.drain(..excess);
+        self.entries.shrink_to_fit();
     }
-    if keyboard.pressed(KeyCode::KeyS) {
-        camera_transform.translation.y -= pan_speed;
+}
+
+// Resource controlling which optional UI panels are visible
+#[derive(Resource)]
+struct UiPanels {
+    equipment: bool,
+    event_log: bool,
+    debug: bool,
+    settings: bool,
+    mining_zones: bool,
+    routes: bool,
+    world_report: bool,
+}
+
+// Optional UI panels that commands can toggle
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UiPanel {
+    Equipment,
+    EventLog,
+    Debug,
+    Settings,
+    MiningZones,
+    Routes,
+    WorldReport,
+}
+
+impl UiPanel {
+    const ALL: [UiPanel; 7] = [
+        UiPanel::Equipment,
+        UiPanel::EventLog,
+        UiPanel::Debug,
+        UiPanel::Settings,
+        UiPanel::MiningZones,
+        UiPanel::Routes,
+        UiPanel::WorldReport,
+    ];
+
+    fn name(&self) -> &str {
+        match self {
+            UiPanel::Equipment => "Equipment Tree",
+            UiPanel::EventLog => "Event Log",
+            UiPanel::Debug => "Debug",
+            UiPanel::Settings => "Settings",
+            UiPanel::MiningZones => "Mining Zones",
+            UiPanel::Routes => "Routes",
+            UiPanel::WorldReport => "World Report",
+        }
     }
-    if keyboard.pressed(KeyCode::KeyA) {
-        camera_transform.translation.x -= pan_speed;
+
+    /// Icon for the compact layout's panel strip
+    fn icon(&self) -> &str {
+        match self {
+            UiPanel::Equipment => "🌲",
+            UiPanel::EventLog => "📜",
+            UiPanel::Debug => "🐞",
+            UiPanel::Settings => "⚙",
+            UiPanel::MiningZones => "⛏",
+            UiPanel::Routes => "🗺",
+            UiPanel::WorldReport => "📊",
+        }
     }
-    if keyboard.pressed(KeyCode::KeyD) {
-        camera_transform.translation.x += pan_speed;
+}
+
+impl UiPanels {
+    fn toggle(&mut self, panel: UiPanel) {
+        let open = match panel {
+            UiPanel::Equipment => &mut self.equipment,
+            UiPanel::EventLog => &mut self.event_log,
+            UiPanel::Debug => &mut self.debug,
+            UiPanel::Settings => &mut self.settings,
+            UiPanel::MiningZones => &mut self.mining_zones,
+            UiPanel::Routes => &mut self.routes,
+            UiPanel::WorldReport => &mut self.world_report,
+        };
+        *open = !*open;
     }
+}
 
-    // Zoom with Q/E
-    if keyboard.pressed(KeyCode::KeyQ) {
-        camera_transform.scale *= 1.0 + zoom_speed;
+impl Default for UiPanels {
+    fn default() -> Self {
+        Self {
+            equipment: true,
+            event_log: false,
+            debug: false,
+            settings: false,
+            mining_zones: false,
+            routes: false,
+            world_report: false,
+        }
     }
-    if keyboard.pressed(KeyCode::KeyE) {
-        camera_transform.scale *= 1.0 - zoom_speed;
-        // Prevent zooming in too far
-        camera_transform.scale.x = camera_transform.scale.x.max(0.1);
-        camera_transform.scale.y = camera_transform.scale.y.max(0.1);
+}
+
+// Resource with options for running in the background
+#[derive(Resource)]
+struct BackgroundSettings {
+    pause_on_focus_loss: bool,
+    throttle_in_background: bool,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        Self {
+            pause_on_focus_loss: true,
+            throttle_in_background: true,
+        }
     }
 }
 
-// Load equipment sprites - generate them programmatically
-fn load_equipment_sprites(
-    mut commands: Commands,
-    mut images: ResMut<Assets<Image>>,
-) {
-    let mut sprites = std::collections::HashMap::new();
+// Graphics quality presets; Custom is any hand-edited combination
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    Custom,
+}
 
-    // Helper to create a colored square sprite
-    fn create_colored_sprite(images: &mut ResMut<Assets<Image>>, color: [u8; 4]) -> Handle<Image> {
-        let size = 32;
-        let mut pixel_data = Vec::new();
-        for y in 0..size {
-            for x in 0..size {
-                // Create a border effect
+// How the window is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DisplayMode {
+    Windowed,
+    Borderless,
+}
+
+// Resource with display and graphics quality options
+#[derive(Resource)]
+struct GraphicsSettings {
+    preset: GraphicsPreset,
+    vsync: bool,
+    frame_cap: Option<u32>, // Frames per second, None for uncapped
+    overlay_rate: Option<f32>, // Overlay refreshes per second, None for every frame
+    display_mode: DisplayMode,
+    resolution: (u32, u32),
+}
+
+impl GraphicsSettings {
+    const RESOLUTIONS: [(u32, u32); 4] = [(1280, 720), (1600, 900), (1920, 1080), (2560, 1440)];
+
+    /// Apply a preset's quality options, leaving the window mode and size alone
+    fn apply_preset(&mut self, preset: GraphicsPreset, trail_settings: &mut TrailSettings) {
+        let (frame_cap, overlay_rate, trails) = match preset {
+            GraphicsPreset::Low => (Some(30), Some(5.0), false),
+            GraphicsPreset::Medium => (Some(60), Some(15.0), true),
+            GraphicsPreset::High => (None, None, true),
+            GraphicsPreset::Custom => return,
+        };
+        self.preset = preset;
+        self.vsync = true;
+        self.frame_cap = frame_cap;
+        self.overlay_rate = overlay_rate;
+        trail_settings.enabled = trails;
+    }
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            preset: GraphicsPreset::High,
+            vsync: true,
+            frame_cap: None,
+            overlay_rate: None,
+            display_mode: DisplayMode::Windowed,
+            resolution: (1280, 720),
+        }
+    }
+}
+
+// Resource with accessibility options for the egui UI
+#[derive(Resource, Default)]
+struct AccessibilitySettings {
+    high_contrast: bool,
+}
+
+// Resource with UI layout options for small screens and controllers
+#[derive(Resource)]
+struct LayoutSettings {
+    compact: bool,
+    ui_scale: f32,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            compact: false,
+            ui_scale: 1.0,
+        }
+    }
+}
+
+// Resource with options for off-screen event alerts
+#[derive(Resource)]
+struct AlertSettings {
+    audio_cues: bool,
+    edge_indicators: bool,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            audio_cues: true,
+            edge_indicators: true,
+        }
+    }
+}
+
+// Resource with options for the movement trails drawn behind units
+#[derive(Resource)]
+struct TrailSettings {
+    enabled: bool,
+    duration: f32, // Seconds of game time a trail covers
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            duration: 8.0,
+        }
+    }
+}
+
+// Resource with recent positions of each unit, oldest first, as (time, position)
+#[derive(Resource, Default)]
+struct MovementTrails {
+    trails: std::collections::HashMap<usize, std::collections::VecDeque<(f32, Vec2)>>,
+}
+
+impl MovementTrails {
+    const MIN_STEP: f32 = 2.0; // Minimum distance between recorded points
+}
+
+// An event location outside the view, shown as an arrow at the screen edge
+struct OffscreenAlert {
+    position: Vec2,
+    expires_at: f32,
+}
+
+// Resource with options for the ambient soundscape
+#[derive(Resource)]
+struct AmbientSettings {
+    enabled: bool,
+    volume: f32,
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            volume: 0.6,
+        }
+    }
+}
+
+// Resource with the soundscape's layer levels, shared with the audio thread
+#[derive(Resource)]
+struct Ambience {
+    levels: AmbientLevels,
+    sound: Handle<AmbientSound>,
+    next_update: f32,
+}
+
+impl FromWorld for Ambience {
+    fn from_world(world: &mut World) -> Self {
+        let levels = AmbientLevels::default();
+        let sound = world.resource_mut::<Assets<AmbientSound>>().add(AmbientSound {
+            levels: levels.clone(),
+        });
+
+        Self {
+            levels,
+            sound,
+            next_update: 0.0,
+        }
+    }
+}
+
+// Resource with active off-screen alerts and the audio cue played for them
+#[derive(Resource)]
+struct OffscreenAlerts {
+    alerts: Vec<OffscreenAlert>,
+    seen_events: usize,
+    cue: Handle<Pitch>,
+}
+
+impl FromWorld for OffscreenAlerts {
+    fn from_world(world: &mut World) -> Self {
+        let cue = world
+            .resource_mut::<Assets<Pitch>>()
+            .add(Pitch::new(660.0, Duration::from_millis(180)));
+
+        Self {
+            alerts: Vec::new(),
+            seen_events: 0,
+            cue,
+        }
+    }
+}
+
+// Resource tracking whether the game was paused because the window lost focus
+#[derive(Resource, Default)]
+struct FocusPause {
+    active: bool,
+}
+
+// Where newly created equipment appears on the map
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum SpawnPolicy {
+    #[default]
+    Random,
+    CameraCenter,
+    HomeBase,
+    Placement,
+}
+
+// Resource holding the spawn policy and the Home Base location
+#[derive(Resource)]
+struct SpawnSettings {
+    policy: SpawnPolicy,
+    home_base: Option<Vec2>,
+    rng: StdRng, // Seeded from the map seed so a seeded run places units the same way
+}
+
+impl Default for SpawnSettings {
+    fn default() -> Self {
+        Self::seeded(0)
+    }
+}
+
+impl SpawnSettings {
+    fn seeded(seed: u32) -> Self {
+        Self {
+            policy: SpawnPolicy::default(),
+            home_base: None,
+            rng: StdRng::seed_from_u64(u64::from(seed)),
+        }
+    }
+
+    /// Spawn position for a policy, jittered slightly so units don't stack.
+    /// Returns None for a random position (or when the anchor isn't available).
+    fn spawn_position(&mut self, policy: SpawnPolicy, camera_center: Option<Vec2>) -> Option<Vec2> {
+        let anchor = match policy {
+            SpawnPolicy::CameraCenter => camera_center,
+            SpawnPolicy::HomeBase => self.home_base,
+            SpawnPolicy::Random | SpawnPolicy::Placement => None,
+        }?;
+
+        Some(anchor + Vec2::new(self.rng.gen_range(-32.0..32.0), self.rng.gen_range(-32.0..32.0)))
+    }
+
+    /// Random position on the map for equipment spawned without one
+    fn random_position(&mut self, mineral_map: &MineralMap) -> Vec2 {
+        let half = Vec2::new(mineral_map.width as f32, mineral_map.height as f32) * MAP_SCALE / 2.0;
+        Vec2::new(self.rng.gen_range(-half.x..half.x), self.rng.gen_range(-half.y..half.y))
+    }
+}
+
+// A player action that can be triggered from menus, hotkeys or the command palette
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+enum GameCommand {
+    Spawn(EquipmentType, Option<SpawnPolicy>), // None uses the configured policy
+    TogglePanel(UiPanel),
+    ToggleMiningOverlay,
+    ToggleTemperatureOverlay,
+    ToggleZoneDesignation,
+    ToggleHighContrast,
+    JumpToHomeBase,
+    JumpToSelected,
+    JumpToLatestEvent,
+    TogglePause,
+    SetSpeed(f32),
+    CenterOn(Vec2),
+    NavigateBack,
+    TogglePlanning,
+    NewWorld(u32, MapSymmetry, WorldGenPreset, usize), // Regenerate the map from a seed at a size
+    LoadMapImage, // Replace the map with the image at the New World dialog's path
+    ToggleSpectator,
+}
+
+impl GameCommand {
+    const SPEEDS: [f32; 4] = [0.5, 1.0, 2.0, 4.0];
+
+    /// Every command offered in the command palette
+    fn palette_entries() -> Vec<GameCommand> {
+        let mut commands: Vec<GameCommand> = EquipmentType::ALL
+            .into_iter()
+            .map(|equipment_type| GameCommand::Spawn(equipment_type, None))
+            .collect();
+        commands.extend(UiPanel::ALL.into_iter().map(GameCommand::TogglePanel));
+        commands.extend([
+            GameCommand::ToggleMiningOverlay,
+            GameCommand::ToggleTemperatureOverlay,
+            GameCommand::ToggleZoneDesignation,
+            GameCommand::ToggleHighContrast,
+            GameCommand::JumpToHomeBase,
+            GameCommand::JumpToSelected,
+            GameCommand::JumpToLatestEvent,
+            GameCommand::NavigateBack,
+            GameCommand::TogglePause,
+            GameCommand::TogglePlanning,
+            GameCommand::ToggleSpectator,
+        ]);
+        commands.extend(Self::SPEEDS.into_iter().map(GameCommand::SetSpeed));
+        commands
+    }
+
+    fn label(&self) -> String {
+        match self {
+            GameCommand::Spawn(equipment_type, _) => format!("Spawn {}", equipment_type.name()),
+            GameCommand::TogglePanel(panel) => format!("Toggle {} Panel", panel.name()),
+            GameCommand::ToggleMiningOverlay => "Toggle Miner Assignment Overlay".to_string(),
+            GameCommand::ToggleTemperatureOverlay => "Toggle Temperature Overlay".to_string(),
+            GameCommand::ToggleZoneDesignation => "Designate Mining Zone".to_string(),
+            GameCommand::ToggleHighContrast => "Toggle High Contrast".to_string(),
+            GameCommand::JumpToHomeBase => "Camera: Jump to Home Base".to_string(),
+            GameCommand::JumpToSelected => "Camera: Jump to Selected Unit".to_string(),
+            GameCommand::JumpToLatestEvent => "Camera: Jump to Latest Event".to_string(),
+            GameCommand::TogglePause => "Pause / Resume".to_string(),
+            GameCommand::SetSpeed(speed) => format!("Game Speed: {}x", speed),
+            GameCommand::CenterOn(position) => format!("Camera: Center on ({:.0}, {:.0})", position.x, position.y),
+            GameCommand::NavigateBack => "Go Back (Selection / Camera)".to_string(),
+            GameCommand::TogglePlanning => "Planning Mode: Start / Discard Plan".to_string(),
+            GameCommand::NewWorld(seed, _, _, _) => format!("New World: Seed {}", seed),
+            GameCommand::LoadMapImage => "Load Map Image".to_string(),
+            GameCommand::ToggleSpectator => "Spectator Mode".to_string(),
+        }
+    }
+}
+
+// A selection and camera position to return to
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NavigationEntry {
+    selection: Option<usize>,
+    camera: Vec2,
+}
+
+// Resource for keyboard-only play: F6 steps focus from a cell cursor on the
+// map, which the arrow keys move and Enter acts on, through each open panel
+// window, then to all panels together
+#[derive(Resource, Default)]
+struct MapCursor {
+    active: bool,
+    cell: UVec2,
+    position: Vec2,                 // World position of the cell's center
+    held: f32,                      // Seconds an arrow key has been held, for key repeat
+    window: Option<egui::LayerId>, // Panel window given focus with F6
+}
+
+impl MapCursor {
+    const STEP: i32 = 2; // Cells per arrow press
+    const FAST_STEP: i32 = 16; // Cells per arrow press with Shift
+    const REPEAT_DELAY: f32 = 0.3;
+    const REPEAT_INTERVAL: f32 = 0.05;
+
+    /// Enter pressed while the cursor has focus
+    fn confirmed(&self, keyboard: &ButtonInput<KeyCode>) -> bool {
+        self.active && keyboard.just_pressed(KeyCode::Enter)
+    }
+}
+
+// Resource for spectator mode: the simulation keeps running but everything
+// except the camera and overlays is locked, with an optional auto-camera
+// that tours the busiest units
+#[derive(Resource)]
+struct SpectatorMode {
+    active: bool,
+    auto_camera: bool,
+    target: Option<usize>, // Unit the auto-camera is following
+    next_cut: f32,         // Real time at which the auto-camera moves on
+}
+
+impl Default for SpectatorMode {
+    fn default() -> Self {
+        Self {
+            active: false,
+            auto_camera: true,
+            target: None,
+            next_cut: 0.0,
+        }
+    }
+}
+
+impl SpectatorMode {
+    const DWELL: f32 = 8.0; // Seconds the auto-camera stays on a unit
+}
+
+/// Run condition for systems that take player input, off in spectator mode
+fn not_spectating(spectator: Res<SpectatorMode>) -> bool {
+    !spectator.active
+}
+
+// Resource holding previous selections and camera positions, newest last
+#[derive(Resource, Default)]
+struct NavigationHistory {
+    entries: Vec<NavigationEntry>,
+    current_selection: Option<usize>, // Selection last seen, to detect changes
+}
+
+impl NavigationHistory {
+    const MAX_ENTRIES: usize = 50;
+
+    fn push(&mut self, entry: NavigationEntry) {
+        if self.entries.last() == Some(&entry) {
+            return;
+        }
+        self.entries.push(entry);
+        if self.entries.len() > Self::MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+}
+
+// Resource holding the command palette's open state and search text
+#[derive(Resource, Default)]
+struct CommandPalette {
+    open: bool,
+    query: String,
+    highlighted: usize,
+}
+
+/// Fuzzy match score of a query against a label: every query character must
+/// appear in order. Consecutive matches and word starts score higher.
+fn fuzzy_score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let offset = label[position..].iter().position(|&c| c == query_char)?;
+        let index = position + offset;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == index) {
+            score += 5;
+        }
+        if index == 0 || !label[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(index);
+        position = index + 1;
+    }
+    Some(score)
+}
+
+// Resource tracking equipment waiting to be placed with a click
+#[derive(Resource, Default)]
+struct PlacementMode {
+    pending: Option<usize>,
+}
+
+// How batch-spawned units are laid out over the dragged area
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BatchShape {
+    Line,
+    Grid,
+}
+
+impl BatchShape {
+    fn name(&self) -> &str {
+        match self {
+            BatchShape::Line => "Row",
+            BatchShape::Grid => "Grid",
+        }
+    }
+}
+
+// Resource for the batch spawn tool: drag a line or rectangle on the map to
+// spawn evenly spaced units into a new container
+#[derive(Resource)]
+struct BatchSpawn {
+    placing: bool,
+    equipment_type: EquipmentType,
+    count: usize,
+    shape: BatchShape,
+    name_template: String,
+    drag_start: Option<Vec2>,
+}
+
+impl Default for BatchSpawn {
+    fn default() -> Self {
+        Self {
+            placing: false,
+            equipment_type: EquipmentType::SurfaceMining,
+            count: 5,
+            shape: BatchShape::Line,
+            name_template: "{type} {id}".to_string(),
+            drag_start: None,
+        }
+    }
+}
+
+/// Evenly spaced positions for `count` units between two dragged points.
+/// Lines include both end points; grids fill the rectangle with rows and
+/// columns matching its aspect ratio.
+fn batch_positions(start: Vec2, end: Vec2, count: usize, shape: BatchShape) -> Vec<Vec2> {
+    if count == 0 {
+        return Vec::new();
+    }
+    // Evenly spaced fractions across 0..=1, or the middle for a single slot
+    let spread = |i: usize, slots: usize| {
+        if slots > 1 { i as f32 / (slots - 1) as f32 } else { 0.5 }
+    };
+
+    match shape {
+        BatchShape::Line => (0..count).map(|i| start.lerp(end, spread(i, count))).collect(),
+        BatchShape::Grid => {
+            let size = (end - start).abs().max(Vec2::splat(1.0));
+            let columns = ((count as f32 * size.x / size.y).sqrt().round() as usize).clamp(1, count);
+            let rows = count.div_ceil(columns);
+            let min = start.min(end);
+            let max = start.max(end);
+
+            // Fill rows from the top, like compact formations
+            (0..count)
+                .map(|i| {
+                    let x = spread(i % columns, columns);
+                    let y = spread(i / columns, rows);
+                    Vec2::new(min.x + (max.x - min.x) * x, max.y - (max.y - min.y) * y)
+                })
+                .collect()
+        }
+    }
+}
+
+// A map marker that transports can be routed through
+#[derive(Debug, Clone)]
+struct Waypoint {
+    id: usize,
+    position: Vec2,
+}
+
+// Resource holding all waypoints placed on the map
+#[derive(Resource, Default)]
+struct Waypoints {
+    points: Vec<Waypoint>,
+    next_id: usize,
+}
+
+impl Waypoints {
+    fn add(&mut self, position: Vec2) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.points.push(Waypoint { id, position });
+        id
+    }
+
+    fn remove(&mut self, id: usize) {
+        self.points.retain(|waypoint| waypoint.id != id);
+    }
+
+    fn position(&self, id: usize) -> Option<Vec2> {
+        self.points.iter().find(|w| w.id == id).map(|w| w.position)
+    }
+
+    /// Closest waypoint within `radius` of a world position
+    fn nearest(&self, position: Vec2, radius: f32) -> Option<usize> {
+        self.points
+            .iter()
+            .map(|w| (w.id, w.position.distance(position)))
+            .filter(|(_, distance)| *distance < radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+}
+
+// Component to mark waypoint marker sprites
+#[derive(Component)]
+struct WaypointMarker {
+    waypoint_id: usize,
+}
+
+// A rectangle of map cells designated for mining (inclusive bounds)
+#[derive(Debug, Clone)]
+struct MiningZone {
+    id: usize,
+    min: UVec2,
+    max: UVec2,
+}
+
+impl MiningZone {
+    fn cells(&self) -> impl Iterator<Item = UVec2> + '_ {
+        (self.min.y..=self.max.y)
+            .flat_map(move |y| (self.min.x..=self.max.x).map(move |x| UVec2::new(x, y)))
+    }
+}
+
+// Resource holding mining zones and the zone designation tool state
+#[derive(Resource, Default)]
+struct MiningZones {
+    zones: Vec<MiningZone>,
+    next_id: usize,
+    designating: bool,
+    drag_start: Option<Vec2>,
+}
+
+// Resource for the drainage preview tool: a source region dragged on the
+// map and where liquid released from it would flow and settle
+#[derive(Resource, Default)]
+struct DrainagePreview {
+    active: bool,
+    drag_start: Option<Vec2>,
+    source: Option<(UVec2, UVec2)>, // Min and max cell of the source region
+    flow: Option<DrainageFlow>,
+}
+
+// Result of a drainage simulation; the world itself is never touched
+struct DrainageFlow {
+    paths: Vec<Vec<UVec2>>, // Streamlines of a sample of the particles
+    settled: Vec<UVec2>,    // Where each particle came to rest
+    escaped: usize,         // Particles that settled outside the source region
+}
+
+/// Simulate liquid filling the open cells of a source region draining
+/// through empty and dug-out cells: each cell of liquid falls, slides
+/// diagonally or runs along its row toward the nearest drop, and settles
+/// when there is none, so later cells pool on top of earlier ones.
+fn simulate_drainage(map: &MineralMap, min: UVec2, max: UVec2) -> DrainageFlow {
+    let _span = info_span!("drainage_preview").entered();
+    let in_source = |cell: UVec2| cell.cmpge(min).all() && cell.cmple(max).all();
+    let is_open = |cell: UVec2| {
+        map.get(cell.x as usize, cell.y as usize)
+            .is_some_and(|c| c.mined || c.mineral_type == MineralType::Empty || in_source(cell))
+    };
+
+    // The liquid starts in the source's open cells, lowest first so it drains in order
+    let mut particles: Vec<UVec2> = (min.y..=max.y)
+        .rev()
+        .flat_map(|y| (min.x..=max.x).map(move |x| UVec2::new(x, y)))
+        .filter(|&cell| map.get(cell.x as usize, cell.y as usize).is_some_and(|c| c.mined || c.mineral_type == MineralType::Empty))
+        .collect();
+    let stride = particles.len().div_ceil(DRAINAGE_MAX_PARTICLES).max(1);
+    particles = particles.into_iter().step_by(stride).collect();
+    let path_stride = particles.len().div_ceil(DRAINAGE_DRAWN_PATHS).max(1);
+
+    let mut settled: HashSet<UVec2> = HashSet::new();
+    let mut flow = DrainageFlow {
+        paths: Vec::new(),
+        settled: Vec::new(),
+        escaped: 0,
+    };
+    let max_steps = 2 * (map.width + map.height);
+    for (index, &start) in particles.iter().enumerate() {
+        let free = |cell: UVec2| is_open(cell) && !settled.contains(&cell);
+        let mut cell = start;
+        let mut direction: i32 = if index % 2 == 0 { 1 } else { -1 };
+        let mut path = vec![cell];
+        for _ in 0..max_steps {
+            let below = cell + UVec2::Y;
+            if free(below) {
+                cell = below;
+                path.push(cell);
+                continue;
+            }
+            // Slide diagonally, trying the current direction first
+            let diagonal = [direction, -direction].into_iter().find_map(|dx| {
+                let x = cell.x.checked_add_signed(dx)?;
+                free(UVec2::new(x, cell.y + 1)).then_some((dx, UVec2::new(x, cell.y + 1)))
+            });
+            if let Some((dx, next)) = diagonal {
+                direction = dx;
+                cell = next;
+                path.push(cell);
+                continue;
+            }
+            // Run along the row to the nearest cell with a drop below it
+            let drop_toward = |dx: i32| {
+                let mut side = cell;
+                loop {
+                    side = UVec2::new(side.x.checked_add_signed(dx)?, side.y);
+                    if !free(side) {
+                        return None;
+                    }
+                    if free(side + UVec2::Y) {
+                        return Some(side);
+                    }
+                }
+            };
+            let drop = [direction, -direction]
+                .into_iter()
+                .filter_map(|dx| drop_toward(dx).map(|side| (dx, side)))
+                .min_by_key(|(_, side)| side.x.abs_diff(cell.x));
+            let Some((dx, side)) = drop else {
+                break;
+            };
+            direction = dx;
+            while cell != side {
+                cell.x = cell.x.wrapping_add_signed(dx);
+                path.push(cell);
+            }
+        }
+        settled.insert(cell);
+        flow.settled.push(cell);
+        if !in_source(cell) {
+            flow.escaped += 1;
+        }
+        if index % path_stride == 0 && path.len() > 1 {
+            flow.paths.push(path);
+        }
+    }
+    flow
+}
+
+// A cell a miner has been sent to dig
+#[derive(Debug, Clone)]
+struct MiningJob {
+    cell: UVec2,
+    zone_id: Option<usize>, // None for manual orders
+    progress: f32, // Seconds spent digging
+}
+
+// Resource assigning zone cells to miners, keyed by equipment ID.
+// Each cell is claimed by at most one miner. Jobs are changed through
+// assign, unassign, retain_jobs and clear, which keep `claimed` in step.
+#[derive(Resource, Default)]
+struct MiningDispatcher {
+    assignments: std::collections::HashMap<usize, MiningJob>,
+    claimed: HashSet<UVec2>, // Cells of all assigned jobs
+    manual: HashSet<usize>,  // Miners under manual control, skipped by the dispatcher
+    show_overlay: bool,
+}
+
+impl MiningDispatcher {
+    fn is_claimed(&self, cell: UVec2) -> bool {
+        self.claimed.contains(&cell)
+    }
+
+    /// Give a miner a job, replacing any job it had
+    fn assign(&mut self, id: usize, job: MiningJob) {
+        self.claimed.insert(job.cell);
+        if let Some(old) = self.assignments.insert(id, job) {
+            self.release(old.cell);
+        }
+    }
+
+    fn unassign(&mut self, id: usize) {
+        if let Some(job) = self.assignments.remove(&id) {
+            self.release(job.cell);
+        }
+    }
+
+    fn retain_jobs(&mut self, keep: impl FnMut(&usize, &mut MiningJob) -> bool) {
+        let before = self.assignments.len();
+        self.assignments.retain(keep);
+        if self.assignments.len() != before {
+            self.claimed = self.assignments.values().map(|job| job.cell).collect();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.assignments.clear();
+        self.claimed.clear();
+    }
+
+    // Manual orders can send a second miner to a claimed cell, which then
+    // stays claimed until both are done with it
+    fn release(&mut self, cell: UVec2) {
+        if !self.assignments.values().any(|job| job.cell == cell) {
+            self.claimed.remove(&cell);
+        }
+    }
+
+    fn zone_load(&self, zone_id: usize) -> usize {
+        self.assignments.values().filter(|job| job.zone_id == Some(zone_id)).count()
+    }
+}
+
+// Units moving together to their formation slots
+#[derive(Debug, Clone)]
+struct FormationMove {
+    targets: std::collections::HashMap<usize, Vec2>, // Units still en route
+    unit_count: usize,
+    speed: f32, // Slowest member's speed, shared by the group
+    initial_distance: f32,
+    remaining_distance: f32,
+}
+
+// Resource holding active group move orders
+#[derive(Resource, Default)]
+struct MoveOrders {
+    groups: Vec<FormationMove>,
+}
+
+impl MoveOrders {
+    fn is_moving(&self, id: usize) -> bool {
+        self.groups.iter().any(|group| group.targets.contains_key(&id))
+    }
+
+    /// Drop any earlier order for a unit
+    fn cancel(&mut self, id: usize) {
+        for group in &mut self.groups {
+            group.targets.remove(&id);
+        }
+        self.groups.retain(|group| !group.targets.is_empty());
+    }
+}
+
+// Resource for planning mode: while planning, move and dig orders are queued
+// and drawn as ghosts instead of running, until the plan is executed
+#[derive(Resource, Default)]
+struct PlanningMode {
+    active: bool,
+    moves: Vec<FormationMove>,
+    digs: Vec<(usize, UVec2)>, // (unit, target cell)
+}
+
+impl PlanningMode {
+    fn order_count(&self) -> usize {
+        self.moves.len() + self.digs.len()
+    }
+
+    /// Queue a move, replacing any move or dig already planned for its units
+    fn queue_move(&mut self, formation: FormationMove) {
+        for group in &mut self.moves {
+            group.targets.retain(|id, _| !formation.targets.contains_key(id));
+        }
+        self.moves.retain(|group| !group.targets.is_empty());
+        self.digs.retain(|(id, _)| !formation.targets.contains_key(id));
+        self.moves.push(formation);
+    }
+
+    /// Queue a dig, replacing any move or dig already planned for the unit
+    fn queue_dig(&mut self, unit: usize, cell: UVec2) {
+        for group in &mut self.moves {
+            group.targets.remove(&unit);
+        }
+        self.moves.retain(|group| !group.targets.is_empty());
+        self.digs.retain(|(id, _)| *id != unit);
+        self.digs.push((unit, cell));
+    }
+
+    fn discard(&mut self) {
+        self.active = false;
+        self.moves.clear();
+        self.digs.clear();
+    }
+}
+
+/// Destinations for units at the given positions moving to `destination`.
+/// Formations keep each unit's offset from the group center; compact moves
+/// pack the units into a grid around the destination instead.
+fn formation_targets(positions: &[Vec2], destination: Vec2, compact: bool) -> Vec<Vec2> {
+    if positions.is_empty() {
+        return Vec::new();
+    }
+    if !compact {
+        let center = positions.iter().copied().sum::<Vec2>() / positions.len() as f32;
+        return positions.iter().map(|&p| destination + (p - center)).collect();
+    }
+
+    let columns = (positions.len() as f32).sqrt().ceil() as usize;
+    let rows = positions.len().div_ceil(columns);
+
+    // Fill slots top-left first with units ordered the same way, to limit crossing paths
+    let mut order: Vec<usize> = (0..positions.len()).collect();
+    order.sort_by(|&a, &b| {
+        positions[b].y.total_cmp(&positions[a].y).then(positions[a].x.total_cmp(&positions[b].x))
+    });
+
+    let mut targets = vec![Vec2::ZERO; positions.len()];
+    for (slot, &unit) in order.iter().enumerate() {
+        let column = (slot % columns) as f32 - (columns - 1) as f32 / 2.0;
+        let row = (slot / columns) as f32 - (rows - 1) as f32 / 2.0;
+        targets[unit] = destination + Vec2::new(column, -row) * FORMATION_SPACING;
+    }
+    targets
+}
+
+/// Nudge destinations that land too close to an earlier destination or a
+/// stationary unit onto the nearest free spot on rings around them
+fn resolve_destination_overlaps(targets: &mut [Vec2], obstacles: &[Vec2]) {
+    let min_distance = FORMATION_SPACING * 0.75;
+    for i in 0..targets.len() {
+        let is_free = |candidate: Vec2, targets: &[Vec2]| {
+            targets[..i].iter().chain(obstacles).all(|&other| other.distance(candidate) >= min_distance)
+        };
+        if is_free(targets[i], targets) {
+            continue;
+        }
+
+        'rings: for ring in 1..=8 {
+            let radius = ring as f32 * min_distance;
+            let steps = 6 * ring;
+            for step in 0..steps {
+                let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+                let candidate = targets[i] + Vec2::from_angle(angle) * radius;
+                if is_free(candidate, targets) {
+                    targets[i] = candidate;
+                    break 'rings;
+                }
+            }
+        }
+    }
+}
+
+// Resource holding named routes shared between units
+#[derive(Resource, Default)]
+struct SharedRoutes {
+    routes: Vec<SharedRoute>,
+    next_id: usize,
+}
+
+impl SharedRoutes {
+    fn add(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.routes.push(SharedRoute {
+            id,
+            name: format!("Route {}", id + 1),
+            stops: Vec::new(),
+        });
+        id
+    }
+
+    fn get(&self, id: usize) -> Option<&SharedRoute> {
+        self.routes.iter().find(|route| route.id == id)
+    }
+
+    fn remove_waypoint(&mut self, waypoint_id: usize) {
+        for route in &mut self.routes {
+            route.stops.retain(|stop| stop.waypoint != waypoint_id);
+        }
+    }
+}
+
+// Something that happened to a map cell
+#[derive(Debug, Clone, Copy)]
+enum CellEventKind {
+    Targeted { unit: usize }, // Assigned as a mining job
+    Mined { unit: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CellEvent {
+    cell: UVec2,
+    time: f32,
+    kind: CellEventKind,
+}
+
+// Resource logging cell events, so a cell's history can be rebuilt on demand
+// without storing it per cell, plus the inspect tool state
+#[derive(Resource, Default)]
+struct CellHistory {
+    events: std::collections::VecDeque<CellEvent>,
+    inspecting: bool,
+    inspected: Option<UVec2>,
+}
+
+impl CellHistory {
+    const MAX_EVENTS: usize = 50_000;
+
+    fn record(&mut self, cell: UVec2, time: f32, kind: CellEventKind) {
+        self.events.push_back(CellEvent { cell, time, kind });
+        if self.events.len() > Self::MAX_EVENTS {
+            self.events.pop_front();
+        }
+    }
+
+    fn for_cell(&self, cell: UVec2) -> impl Iterator<Item = &CellEvent> {
+        self.events.iter().filter(move |event| event.cell == cell)
+    }
+
+    /// Drop all but the most recent events and release unused capacity
+    fn compact(&mut self, keep: usize) {
+        let excess = self.events.len().saturating_sub(keep);
+        self.events.drain(..excess);
+        self.events.shrink_to_fit();
+    }
+}
+
+// Resource tallying mined material by mineral type
+#[derive(Resource, Default)]
+struct Stockpile {
+    amounts: std::collections::HashMap<MineralType, f32>,
+}
+
+impl Stockpile {
+    /// The largest stocks, highest first
+    fn top(&self, count: usize) -> Vec<(MineralType, f32)> {
+        let mut amounts: Vec<_> = self.amounts.iter().map(|(&m, &a)| (m, a)).collect();
+        amounts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        amounts.truncate(count);
+        amounts
+    }
+}
+
+// Resource holding the outliner nodes being dragged, kept through the release
+// frame so a drop outside the panel can be handled on the map
+#[derive(Resource, Default)]
+struct TreeDragToMap {
+    nodes: Vec<usize>,
+}
+
+// Component to mark the Home Base sprite
+#[derive(Component)]
+struct HomeBaseMarker;
+
+fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mineral_map: Res<MineralMap>,
+) {
+    // Setup 2D camera
+    commands.spawn((Camera2d, SpatialListener::new(AUDIO_EAR_GAP)));
+
+    // Create the image from mineral data
+    let image_handle = images.add(mineral_map.to_image());
+
+    // Spawn the mineral map sprite
+    commands.spawn((
+        Sprite::from_image(image_handle),
+        Transform::from_scale(Vec3::splat(MAP_SCALE)), // Scale up for visibility
+        MineralMapRenderer,
+    ));
+
+    // Temperature overlay, hidden until toggled
+    commands.spawn((
+        Sprite::from_image(images.add(mineral_map.temperature_image())),
+        Transform::from_xyz(0.0, 0.0, 0.5).with_scale(Vec3::splat(MAP_SCALE)),
+        Visibility::Hidden,
+        TemperatureOverlayRenderer,
+    ));
+}
+
+// Camera controls: WASD to pan, Q/E to zoom
+// Uses real time so the camera still moves while the game is paused
+fn camera_control_system(
+    time: Res<Time<Real>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut query: Query<&mut Transform, With<Camera>>,
+    mut contexts: EguiContexts,
+) {
+    let Ok(mut camera_transform) = query.single_mut() else {
+        return;
+    };
+    // Don't pan while typing into a text field
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let pan_speed = 300.0 * time.delta_secs();
+    let zoom_speed = 2.0 * time.delta_secs();
+
+    // Pan with WASD
+    if keyboard.pressed(KeyCode::KeyW) {
+        camera_transform.translation.y += pan_speed;
+    }
+    if keyboard.pressed(KeyCode::KeyS) {
+        camera_transform.translation.y -= pan_speed;
+    }
+    if keyboard.pressed(KeyCode::KeyA) {
+        camera_transform.translation.x -= pan_speed;
+    }
+    if keyboard.pressed(KeyCode::KeyD) {
+        camera_transform.translation.x += pan_speed;
+    }
+
+    // Zoom with Q/E
+    if keyboard.pressed(KeyCode::KeyQ) {
+        camera_transform.scale *= 1.0 + zoom_speed;
+    }
+    if keyboard.pressed(KeyCode::KeyE) {
+        camera_transform.scale *= 1.0 - zoom_speed;
+        // Prevent zooming in too far
+        camera_transform.scale.x = camera_transform.scale.x.max(0.1);
+        camera_transform.scale.y = camera_transform.scale.y.max(0.1);
+    }
+}
+
+// Load equipment sprites - generate them programmatically
+fn load_equipment_sprites(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let mut sprites = std::collections::HashMap::new();
+
+    // Helper to create a colored square sprite
+    fn create_colored_sprite(images: &mut ResMut<Assets<Image>>, color: [u8; 4]) -> Handle<Image> {
+        let size = 32;
+        let mut pixel_data = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                // Create a border effect
                 if x < 2 || x >= size - 2 || y < 2 || y >= size - 2 {
                     // Border - slightly darker
                     pixel_data.extend_from_slice(&[
@@ -653,276 +2780,4357 @@ fn load_equipment_sprites(
                         color[3],
                     ]);
                 } else {
-                    // Inner color
-                    pixel_data.extend_from_slice(&color);
+                    // Inner color
+                    pixel_data.extend_from_slice(&color);
+                }
+            }
+        }
+
+        let image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixel_data,
+            TextureFormat::Rgba8UnormSrgb,
+            Default::default(),
+        );
+
+        images.add(image)
+    }
+
+    // Create colored sprites for each equipment type
+    for equipment_type in EquipmentType::ALL {
+        sprites.insert(
+            equipment_type,
+            create_colored_sprite(&mut images, equipment_type.sprite_color()),
+        );
+    }
+
+    commands.insert_resource(EquipmentSprites { sprites });
+}
+
+// System to spawn sprite entities for equipment that doesn't have one yet
+fn spawn_equipment_sprites(
+    mut commands: Commands,
+    equipment_state: Res<EquipmentTreeState>,
+    equipment_sprites: Res<EquipmentSprites>,
+    existing_sprites: Query<&EquipmentSprite>,
+    placement: Res<PlacementMode>,
+    mut event_log: ResMut<EventLog>,
+    time: Res<Time>,
+) {
+    // Get all existing equipment IDs that already have sprites
+    let existing_ids: std::collections::HashSet<usize> = existing_sprites
+        .iter()
+        .map(|sprite| sprite.equipment_id)
+        .collect();
+
+    // Helper function to recursively spawn sprites
+    fn spawn_for_node(
+        node: &EquipmentTreeNode,
+        existing_ids: &std::collections::HashSet<usize>,
+        equipment_sprites: &EquipmentSprites,
+        pending: Option<usize>,
+        commands: &mut Commands,
+        event_log: &mut EventLog,
+        now: f32,
+    ) {
+        // If this is an equipment node (not a container)
+        if let Some(equipment_type) = node.equipment_type() {
+            // Equipment waiting for a placement click gets its sprite once placed
+            if !existing_ids.contains(&node.id) && pending != Some(node.id) {
+                // Equipment needs a sprite
+                if let Some(sprite_handle) = equipment_sprites.sprites.get(&equipment_type) {
+                    let position = node.position.unwrap_or_default();
+
+                    commands.spawn((
+                        Sprite::from_image(sprite_handle.clone()),
+                        Transform::from_translation(position.extend(1.0)),
+                        EquipmentSprite {
+                            equipment_id: node.id,
+                        },
+                    ));
+                    event_log.push(now, format!("{} deployed", node.name), Some(position));
+                }
+            }
+        }
+
+        // Recursively spawn for children
+        for child in &node.children {
+            spawn_for_node(child, existing_ids, equipment_sprites, pending, commands, event_log, now);
+        }
+    }
+
+    // Spawn sprites for all equipment nodes in the tree
+    for node in &equipment_state.nodes {
+        spawn_for_node(
+            node,
+            &existing_ids,
+            &equipment_sprites,
+            placement.pending,
+            &mut commands,
+            &mut event_log,
+            time.elapsed_secs(),
+        );
+    }
+}
+
+// System to give equipment spawned without a position (and not waiting for
+// a placement click) a random spot on the map before its sprite appears
+fn place_unpositioned_equipment(
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut spawn_settings: ResMut<SpawnSettings>,
+    placement: Res<PlacementMode>,
+    mineral_map: Res<MineralMap>,
+) {
+    let unplaced: Vec<usize> = equipment_state
+        .all_ids()
+        .into_iter()
+        .filter(|&id| placement.pending != Some(id))
+        .filter(|&id| {
+            equipment_state
+                .find_node(id)
+                .is_some_and(|node| node.equipment_type().is_some() && node.position.is_none())
+        })
+        .collect();
+
+    for id in unplaced {
+        let position = spawn_settings.random_position(&mineral_map);
+        if let Some(node) = equipment_state.find_node_mut(id) {
+            node.position = Some(position);
+        }
+    }
+}
+
+// System to update equipment positions in the state when sprites move
+fn update_equipment_positions(
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    sprite_query: Query<(&Transform, &EquipmentSprite), Changed<Transform>>,
+) {
+    for (transform, equipment_sprite) in &sprite_query {
+        // Find the equipment node and update its position
+        if let Some(node) = equipment_state.find_node_mut(equipment_sprite.equipment_id) {
+            node.position = Some(transform.translation.truncate());
+        }
+    }
+}
+
+// System to draw a colored border around units with a color tag
+fn draw_tag_borders(
+    equipment_state: Res<EquipmentTreeState>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+    mut gizmos: Gizmos,
+) {
+    for (transform, equipment_sprite) in &sprite_query {
+        if let Some(tag) = equipment_state.effective_tag(equipment_sprite.equipment_id) {
+            gizmos.rect_2d(
+                Isometry2d::from_translation(transform.translation.truncate()),
+                Vec2::splat(36.0),
+                tag.color(),
+            );
+        }
+    }
+}
+
+// System to record where units have been recently, for their trails
+fn record_movement_trails(
+    time: Res<Time>,
+    trail_settings: Res<TrailSettings>,
+    mut trails: ResMut<MovementTrails>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+) {
+    if !trail_settings.enabled {
+        trails.trails.clear();
+        return;
+    }
+    let now = time.elapsed_secs();
+    let cutoff = now - trail_settings.duration;
+
+    for (transform, equipment_sprite) in &sprite_query {
+        let position = transform.translation.truncate();
+        let trail = trails.trails.entry(equipment_sprite.equipment_id).or_default();
+        if trail.back().is_none_or(|(_, last)| last.distance(position) >= MovementTrails::MIN_STEP) {
+            trail.push_back((now, position));
+        }
+        while trail.front().is_some_and(|(recorded, _)| *recorded < cutoff) {
+            trail.pop_front();
+        }
+    }
+    // Forget units that no longer exist or have stood still for the whole trail
+    trails
+        .trails
+        .retain(|_, trail| trail.back().is_some_and(|(recorded, _)| *recorded >= cutoff));
+}
+
+// System to draw each unit's trail, fading out with age
+fn draw_movement_trails(
+    time: Res<Time>,
+    trail_settings: Res<TrailSettings>,
+    trails: Res<MovementTrails>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+    mut gizmos: Gizmos,
+) {
+    if !trail_settings.enabled {
+        return;
+    }
+    let now = time.elapsed_secs();
+
+    for (transform, equipment_sprite) in &sprite_query {
+        let Some(trail) = trails.trails.get(&equipment_sprite.equipment_id) else {
+            continue;
+        };
+        // Join the trail up to the unit's current position
+        let current = (now, transform.translation.truncate());
+        let points = trail.iter().chain(std::iter::once(&current)).map(|&(recorded, position)| {
+            let age = ((now - recorded) / trail_settings.duration).clamp(0.0, 1.0);
+            (position, Color::srgba(0.6, 0.9, 1.0, 0.6 * (1.0 - age)))
+        });
+        gizmos.linestrip_gradient_2d(points);
+    }
+}
+
+// System to refresh each unit's activity for the tree icons: digging progress,
+// a spinner while moving, and a warning when a unit with orders stops moving
+fn update_unit_activity(
+    time: Res<Time>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    dispatcher: Res<MiningDispatcher>,
+    move_orders: Res<MoveOrders>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+    mut last_moved: Local<std::collections::HashMap<usize, (Vec2, f32)>>,
+) {
+    const STALL_SECONDS: f32 = 3.0;
+    const SPINNER_FPS: f32 = 8.0;
+    let now = time.elapsed_secs();
+    let frame = (now * SPINNER_FPS) as usize;
+
+    for (transform, equipment_sprite) in &sprite_query {
+        let id = equipment_sprite.equipment_id;
+        let position = transform.translation.truncate();
+        let (last_position, moved_at) = last_moved.entry(id).or_insert((position, now));
+        if last_position.distance(position) > 0.5 {
+            *last_position = position;
+            *moved_at = now;
+        }
+        let still_for = now - *moved_at;
+
+        let Some(node) = equipment_state.find_node(id) else {
+            continue;
+        };
+        let job = dispatcher.assignments.get(&id);
+        let dwelling = node.patrol.as_ref().is_some_and(|patrol| patrol.dwell_remaining.is_some());
+        let has_orders = move_orders.is_moving(id)
+            || node.route.as_ref().is_some_and(|route| !route.is_complete())
+            || (node.patrol.is_some() && !dwelling)
+            || job.is_some();
+
+        let activity = if !node.active {
+            UnitActivity::Disabled
+        } else if let Some(job) = job.filter(|job| job.progress > 0.0) {
+            UnitActivity::Digging {
+                progress: job.progress / MINE_SECONDS,
+            }
+        } else if has_orders && still_for > STALL_SECONDS {
+            UnitActivity::Stalled
+        } else if has_orders {
+            UnitActivity::Moving { frame }
+        } else {
+            UnitActivity::Idle
+        };
+        // Only touch the tree when something changed, to keep change detection useful
+        if node.activity != activity {
+            if let Some(node) = equipment_state.find_node_mut(id) {
+                node.activity = activity;
+            }
+        }
+    }
+
+    // Forget units whose sprites have been despawned
+    if last_moved.len() > sprite_query.iter().len() {
+        let live: HashSet<usize> = sprite_query.iter().map(|(_, sprite)| sprite.equipment_id).collect();
+        last_moved.retain(|id, _| live.contains(id));
+    }
+}
+
+// System to dim the sprites of disabled units
+fn dim_disabled_equipment(
+    equipment_state: Res<EquipmentTreeState>,
+    mut sprite_query: Query<(&mut Sprite, &EquipmentSprite)>,
+) {
+    for (mut sprite, equipment_sprite) in &mut sprite_query {
+        let color = if equipment_state.is_enabled(equipment_sprite.equipment_id) {
+            Color::WHITE
+        } else {
+            DISABLED_TINT
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
+
+/// World position under the cursor, if the cursor is inside the primary window
+fn cursor_world_position(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor_position = windows.single().ok()?.cursor_position()?;
+    let (camera, camera_transform) = camera_query.single().ok()?;
+    camera.viewport_to_world_2d(camera_transform, cursor_position).ok()
+}
+
+// The player's pointer on the map: the mouse, or while the keyboard cursor
+// has focus, its cell, with Enter standing in for the left button and
+// Delete for the right
+#[derive(SystemParam)]
+struct MapPointer<'w, 's> {
+    mouse_button: ResMut<'w, ButtonInput<MouseButton>>,
+    keyboard: ResMut<'w, ButtonInput<KeyCode>>,
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera_query: Query<'w, 's, (&'static Camera, &'static GlobalTransform)>,
+    map_cursor: Res<'w, MapCursor>,
+    contexts: EguiContexts<'w, 's>,
+}
+
+impl MapPointer<'_, '_> {
+    fn key(button: MouseButton) -> Option<KeyCode> {
+        match button {
+            MouseButton::Left => Some(KeyCode::Enter),
+            MouseButton::Right => Some(KeyCode::Delete),
+            _ => None,
+        }
+    }
+
+    /// World position pointed at
+    fn position(&self) -> Option<Vec2> {
+        if self.map_cursor.active {
+            return Some(self.map_cursor.position);
+        }
+        cursor_world_position(&self.windows, &self.camera_query)
+    }
+
+    fn just_pressed(&self, button: MouseButton) -> bool {
+        if self.map_cursor.active {
+            return Self::key(button).is_some_and(|key| self.keyboard.just_pressed(key));
+        }
+        self.mouse_button.just_pressed(button)
+    }
+
+    fn pressed(&self, button: MouseButton) -> bool {
+        if self.map_cursor.active {
+            return Self::key(button).is_some_and(|key| self.keyboard.pressed(key));
+        }
+        self.mouse_button.pressed(button)
+    }
+
+    /// Whether the mouse is over a panel rather than the map; never true
+    /// for the keyboard cursor
+    fn over_ui(&mut self) -> bool {
+        !self.map_cursor.active && self.contexts.ctx_mut().is_pointer_over_area()
+    }
+
+    /// Whether a hotkey was pressed while no panel is taking keyboard input
+    fn hotkey(&mut self, key: KeyCode) -> bool {
+        self.keyboard.just_pressed(key) && !self.contexts.ctx_mut().wants_keyboard_input()
+    }
+
+    /// Mark a press handled so later systems don't also act on it
+    fn consume(&mut self, button: MouseButton) {
+        self.mouse_button.clear_just_pressed(button);
+        if let Some(key) = Self::key(button).filter(|_| self.map_cursor.active) {
+            self.keyboard.clear_just_pressed(key);
+        }
+    }
+}
+
+// System to place pending equipment where the map is clicked
+fn place_pending_equipment(
+    mut pointer: MapPointer,
+    mut placement: ResMut<PlacementMode>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+) {
+    let Some(pending_id) = placement.pending else {
+        return;
+    };
+
+    // Cancelling leaves the unit to spawn at a random position
+    if pointer.keyboard.just_pressed(KeyCode::Escape) {
+        placement.pending = None;
+        return;
+    }
+
+    if !pointer.just_pressed(MouseButton::Left) || pointer.over_ui() {
+        return;
+    }
+
+    let Some(world_position) = pointer.position() else {
+        return;
+    };
+
+    if let Some(node) = equipment_state.find_node_mut(pending_id) {
+        node.position = Some(world_position);
+    }
+    placement.pending = None;
+
+    // Consume the click so it doesn't also change the selection
+    pointer.consume(MouseButton::Left);
+}
+
+// System to drag out a line or rectangle for the batch spawn tool, previewing
+// where each unit will go
+fn place_batch_spawn(
+    mut pointer: MapPointer,
+    mut batch_spawn: ResMut<BatchSpawn>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut gizmos: Gizmos,
+) {
+    if !batch_spawn.placing {
+        return;
+    }
+    if pointer.keyboard.just_pressed(KeyCode::Escape) {
+        batch_spawn.placing = false;
+        batch_spawn.drag_start = None;
+        return;
+    }
+
+    let Some(world_position) = pointer.position() else {
+        return;
+    };
+
+    if pointer.just_pressed(MouseButton::Left) && !pointer.over_ui() {
+        batch_spawn.drag_start = Some(world_position);
+        pointer.consume(MouseButton::Left);
+    }
+
+    let Some(start) = batch_spawn.drag_start else {
+        return;
+    };
+    let positions = batch_positions(start, world_position, batch_spawn.count, batch_spawn.shape);
+
+    if pointer.pressed(MouseButton::Left) {
+        let color = Color::srgb(0.3, 0.9, 1.0);
+        match batch_spawn.shape {
+            BatchShape::Line => gizmos.line_2d(start, world_position, color),
+            BatchShape::Grid => {
+                let center = (start + world_position) / 2.0;
+                gizmos.rect_2d(Isometry2d::from_translation(center), (world_position - start).abs(), color);
+            }
+        }
+        for position in &positions {
+            gizmos.circle_2d(Isometry2d::from_translation(*position), 8.0, color);
+        }
+        return;
+    }
+
+    // Released: spawn the units into a new container
+    let equipment_type = batch_spawn.equipment_type;
+    let name = format!(
+        "{} {} {}",
+        equipment_type.unit_name(),
+        batch_spawn.shape.name(),
+        equipment_state.next_id
+    );
+    equipment_state.add_group(name, equipment_type, &positions, &batch_spawn.name_template);
+    batch_spawn.placing = false;
+    batch_spawn.drag_start = None;
+}
+
+// System to drop equipment dragged out of the outliner onto the map, with a
+// ghost preview under the cursor while dragging
+fn drop_tree_drag_on_map(
+    mut pointer: MapPointer,
+    mut tree_drag: ResMut<TreeDragToMap>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    equipment_actions: Res<EquipmentTreeActions>,
+    mut placement: ResMut<PlacementMode>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+) {
+    // P drops the outliner selection at the keyboard cursor
+    let keyboard_drop = pointer.map_cursor.active && pointer.keyboard.just_pressed(KeyCode::KeyP);
+    if keyboard_drop {
+        tree_drag.nodes = equipment_actions.selected.iter().copied().collect();
+        tree_drag.nodes.sort_unstable();
+    }
+    if tree_drag.nodes.is_empty() {
+        return;
+    }
+    if pointer.over_ui() {
+        return;
+    }
+
+    // Only equipment can be placed; containers stay in the tree
+    let dragged: Vec<(usize, String, EquipmentType)> = tree_drag
+        .nodes
+        .iter()
+        .filter_map(|&id| equipment_state.find_node(id))
+        .filter_map(|node| Some((node.id, node.name.clone(), node.equipment_type()?)))
+        .collect();
+
+    if !keyboard_drop && !pointer.mouse_button.just_released(MouseButton::Left) {
+        // Ghost preview at the cursor
+        let ctx = pointer.contexts.ctx_mut();
+        if let Some(pointer) = ctx.pointer_latest_pos() {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("tree_drag_ghost"),
+            ));
+            for (i, (_, name, equipment_type)) in dragged.iter().enumerate() {
+                let [r, g, b, _] = equipment_type.sprite_color();
+                let center = pointer + egui::vec2(i as f32 * 36.0, 0.0);
+                painter.rect_filled(
+                    egui::Rect::from_center_size(center, egui::vec2(32.0, 32.0)),
+                    2.0,
+                    egui::Color32::from_rgba_unmultiplied(r, g, b, 120),
+                );
+                if i == 0 {
+                    painter.text(
+                        center + egui::vec2(0.0, 22.0),
+                        egui::Align2::CENTER_TOP,
+                        name,
+                        egui::FontId::proportional(12.0),
+                        egui::Color32::WHITE,
+                    );
+                }
+            }
+        }
+        return;
+    }
+
+    let Some(world_position) = pointer.position() else {
+        tree_drag.nodes.clear();
+        return;
+    };
+
+    // Multiple units are laid out in a row starting at the drop point
+    for (i, (id, _, _)) in dragged.iter().enumerate() {
+        let position = world_position + Vec2::new(i as f32 * 40.0, 0.0);
+
+        if let Some(node) = equipment_state.find_node_mut(*id) {
+            node.position = Some(position);
+        }
+        if placement.pending == Some(*id) {
+            placement.pending = None;
+        }
+
+        // Move the unit's sprite if it has one. Units without a sprite yet,
+        // such as one awaiting placement, get it from spawn_equipment_sprites
+        // at the position set above
+        for (mut transform, equipment_sprite) in &mut sprite_query {
+            if equipment_sprite.equipment_id == *id {
+                transform.translation = position.extend(transform.translation.z);
+            }
+        }
+    }
+
+    tree_drag.nodes.clear();
+}
+
+// System to keep the Home Base sprite in sync with the spawn settings
+fn update_home_base_marker(
+    mut commands: Commands,
+    spawn_settings: Res<SpawnSettings>,
+    mut marker_query: Query<(Entity, &mut Transform), With<HomeBaseMarker>>,
+) {
+    if !spawn_settings.is_changed() {
+        return;
+    }
+
+    match (spawn_settings.home_base, marker_query.single_mut()) {
+        (Some(position), Ok((_, mut transform))) => {
+            transform.translation = position.extend(0.5);
+        }
+        (Some(position), Err(_)) => {
+            commands.spawn((
+                Sprite::from_color(Color::srgba(1.0, 1.0, 1.0, 0.35), Vec2::splat(96.0)),
+                Transform::from_translation(position.extend(0.5)),
+                HomeBaseMarker,
+            ));
+        }
+        (None, Ok((entity, _))) => {
+            commands.entity(entity).despawn();
+        }
+        (None, Err(_)) => {}
+    }
+}
+
+// System to create a waypoint where the map is double-clicked, or with
+// Insert at the keyboard cursor
+fn create_waypoint_on_double_click(
+    mut pointer: MapPointer,
+    mut waypoints: ResMut<Waypoints>,
+    mut event_log: ResMut<EventLog>,
+    time: Res<Time<Real>>,
+    mut last_click: Local<Option<(f32, Vec2)>>,
+) {
+    let inserted = pointer.map_cursor.active && pointer.keyboard.just_pressed(KeyCode::Insert);
+    if !inserted && (pointer.map_cursor.active || !pointer.just_pressed(MouseButton::Left) || pointer.over_ui()) {
+        return;
+    }
+    let Some(world_position) = pointer.position() else {
+        return;
+    };
+
+    let now = time.elapsed_secs();
+    let is_double_click = inserted
+        || last_click.is_some_and(|(last_time, last_position)| {
+            now - last_time < DOUBLE_CLICK_SECONDS
+                && last_position.distance(world_position) < WAYPOINT_PICK_RADIUS
+        });
+
+    if is_double_click {
+        let id = waypoints.add(world_position);
+        event_log.push(now, format!("Waypoint {} placed", id), Some(world_position));
+        *last_click = None;
+    } else {
+        *last_click = Some((now, world_position));
+    }
+}
+
+// System to drag waypoint markers, delete them with right-click, and add them
+// to the selected transport's route with Shift+click. On the keyboard cursor
+// Enter (held to drag) and Delete do the same.
+fn edit_waypoints(
+    mut pointer: MapPointer,
+    mut waypoints: ResMut<Waypoints>,
+    selected: Res<SelectedEquipment>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut dragging: Local<Option<usize>>,
+    mut shared_routes: ResMut<SharedRoutes>,
+) {
+    let Some(world_position) = pointer.position() else {
+        return;
+    };
+
+    // Continue an ongoing drag
+    if let Some(id) = *dragging {
+        if pointer.pressed(MouseButton::Left) {
+            if let Some(waypoint) = waypoints.points.iter_mut().find(|w| w.id == id) {
+                waypoint.position = world_position;
+            }
+        } else {
+            *dragging = None;
+        }
+        return;
+    }
+
+    if pointer.over_ui() {
+        return;
+    }
+    let Some(hit) = waypoints.nearest(world_position, WAYPOINT_PICK_RADIUS) else {
+        return;
+    };
+
+    if pointer.just_pressed(MouseButton::Right) {
+        waypoints.remove(hit);
+        equipment_state.remove_waypoint_from_routes(hit);
+        shared_routes.remove_waypoint(hit);
+        pointer.consume(MouseButton::Right);
+        return;
+    }
+
+    if !pointer.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    let shift = pointer.keyboard.pressed(KeyCode::ShiftLeft) || pointer.keyboard.pressed(KeyCode::ShiftRight);
+    let selected_transport = selected
+        .selected_id
+        .and_then(|id| equipment_state.find_node_mut(id))
+        .filter(|node| node.equipment_type() == Some(EquipmentType::Transport));
+
+    match (shift, selected_transport) {
+        (true, Some(node)) => {
+            node.route.get_or_insert_with(Route::new).waypoints.push(hit);
+        }
+        _ => *dragging = Some(hit),
+    }
+
+    // Consume the click so it doesn't change the selection
+    pointer.consume(MouseButton::Left);
+}
+
+// System to keep waypoint marker sprites in sync with the Waypoints resource
+fn sync_waypoint_markers(
+    mut commands: Commands,
+    waypoints: Res<Waypoints>,
+    mut marker_query: Query<(Entity, &WaypointMarker, &mut Transform)>,
+) {
+    if !waypoints.is_changed() {
+        return;
+    }
+
+    let mut existing = HashSet::new();
+    for (entity, marker, mut transform) in &mut marker_query {
+        match waypoints.position(marker.waypoint_id) {
+            Some(position) => {
+                transform.translation = position.extend(0.8);
+                existing.insert(marker.waypoint_id);
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for waypoint in waypoints.points.iter().filter(|w| !existing.contains(&w.id)) {
+        commands.spawn((
+            Sprite::from_color(Color::srgb(0.2, 0.9, 1.0), Vec2::splat(12.0)),
+            Transform::from_translation(waypoint.position.extend(0.8))
+                .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_4)),
+            WaypointMarker {
+                waypoint_id: waypoint.id,
+            },
+        ));
+    }
+}
+
+// System to drive transports along their routes
+fn follow_routes(
+    time: Res<Time>,
+    waypoints: Res<Waypoints>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+    move_orders: Res<MoveOrders>,
+) {
+    let step = TRANSPORT_SPEED * time.delta_secs();
+
+    for (mut transform, equipment_sprite) in &mut sprite_query {
+        // Direct move orders take priority over the route
+        if move_orders.is_moving(equipment_sprite.equipment_id) {
+            continue;
+        }
+        let id = equipment_sprite.equipment_id;
+        let Some(route) = equipment_state
+            .find_node(id)
+            .filter(|node| node.active)
+            .and_then(|node| node.route.as_ref())
+        else {
+            continue;
+        };
+
+        if route.is_complete() {
+            continue;
+        }
+        let Some(target) = waypoints.position(route.waypoints[route.next]) else {
+            continue;
+        };
+
+        let current = transform.translation.truncate();
+        let to_target = target - current;
+        if to_target.length() <= step {
+            transform.translation = target.extend(transform.translation.z);
+            // The tree is only marked changed when a waypoint is reached
+            if let Some(route) = equipment_state.find_node_mut(id).and_then(|node| node.route.as_mut()) {
+                route.advance();
+            }
+        } else {
+            let next = current + to_target.normalize() * step;
+            transform.translation = next.extend(transform.translation.z);
+        }
+    }
+}
+
+// System to move units around their shared routes, waiting at each stop
+fn follow_shared_routes(
+    time: Res<Time>,
+    waypoints: Res<Waypoints>,
+    shared_routes: Res<SharedRoutes>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+    move_orders: Res<MoveOrders>,
+) {
+    let delta = time.delta_secs();
+
+    for (mut transform, equipment_sprite) in &mut sprite_query {
+        if move_orders.is_moving(equipment_sprite.equipment_id) {
+            continue;
+        }
+        let Some(node) = equipment_state
+            .find_node_mut(equipment_sprite.equipment_id)
+            .filter(|node| node.active)
+        else {
+            continue;
+        };
+        let Some(speed) = node.equipment_type().map(|equipment_type| equipment_type.move_speed()) else {
+            continue;
+        };
+        let Some(patrol) = node.patrol.as_mut() else {
+            continue;
+        };
+        let Some(route) = shared_routes.get(patrol.route_id).filter(|route| !route.stops.is_empty()) else {
+            continue;
+        };
+
+        // Stops may have been removed since the unit set off
+        patrol.next %= route.stops.len();
+
+        if let Some(remaining) = patrol.dwell_remaining.as_mut() {
+            *remaining -= delta;
+            if *remaining <= 0.0 {
+                patrol.dwell_remaining = None;
+                patrol.next = (patrol.next + 1) % route.stops.len();
+            }
+            continue;
+        }
+
+        let stop = &route.stops[patrol.next];
+        let Some(target) = waypoints.position(stop.waypoint) else {
+            continue;
+        };
+        let current = transform.translation.truncate();
+        let to_target = target - current;
+        let step = speed * delta;
+        if to_target.length() <= step {
+            transform.translation = target.extend(transform.translation.z);
+            patrol.dwell_remaining = Some(stop.dwell);
+        } else {
+            let next = current + to_target.normalize() * step;
+            transform.translation = next.extend(transform.translation.z);
+        }
+    }
+}
+
+// Window for creating shared routes and assigning units to them
+fn shared_routes_window(
+    mut contexts: EguiContexts,
+    mut ui_panels: ResMut<UiPanels>,
+    mut shared_routes: ResMut<SharedRoutes>,
+    waypoints: Res<Waypoints>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    equipment_actions: Res<EquipmentTreeActions>,
+) {
+    // Samplers and transports selected in the outliner, including those inside selected containers
+    let mut selected_ids = Vec::new();
+    for &id in &equipment_actions.selected {
+        if let Some(node) = equipment_state.find_node(id) {
+            node.collect_ids(&mut selected_ids);
+        }
+    }
+    selected_ids.retain(|&id| {
+        matches!(
+            equipment_state.find_node(id).and_then(|node| node.equipment_type()),
+            Some(EquipmentType::Sampler | EquipmentType::Transport)
+        )
+    });
+    selected_ids.sort();
+    selected_ids.dedup();
+
+    let mut assign = None;
+    let mut unassign = None;
+    let mut delete = None;
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Routes")
+        .open(&mut ui_panels.routes)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            if ui.button("+ New Route").clicked() {
+                shared_routes.add();
+            }
+            ui.separator();
+
+            for route in &mut shared_routes.routes {
+                let unit_count = equipment_state.patrol_units(route.id).len();
+                egui::CollapsingHeader::new(format!("{} ({} units)", route.name, unit_count))
+                    .id_salt(("shared_route", route.id))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.text_edit_singleline(&mut route.name);
+
+                        let mut remove_stop = None;
+                        for (i, stop) in route.stops.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{}. Waypoint {}", i + 1, stop.waypoint));
+                                ui.add(
+                                    egui::DragValue::new(&mut stop.dwell)
+                                        .range(0.0..=120.0)
+                                        .speed(0.5)
+                                        .suffix(" s"),
+                                )
+                                .on_hover_text("Time spent at this stop");
+                                if ui.small_button("✖").on_hover_text("Remove stop").clicked() {
+                                    remove_stop = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = remove_stop {
+                            route.stops.remove(i);
+                        }
+
+                        egui::ComboBox::from_id_salt(("add_stop", route.id))
+                            .selected_text("Add stop…")
+                            .show_ui(ui, |ui| {
+                                for waypoint in &waypoints.points {
+                                    if ui.selectable_label(false, format!("Waypoint {}", waypoint.id)).clicked() {
+                                        route.stops.push(RouteStop {
+                                            waypoint: waypoint.id,
+                                            dwell: 2.0,
+                                        });
+                                    }
+                                }
+                                if waypoints.points.is_empty() {
+                                    ui.label("Double-click the map to place waypoints");
+                                }
+                            });
+
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(!selected_ids.is_empty(), egui::Button::new("Assign Selected"))
+                                .on_hover_text("Samplers and transports selected in the equipment tree")
+                                .clicked()
+                            {
+                                assign = Some(route.id);
+                            }
+                            if ui.add_enabled(unit_count > 0, egui::Button::new("Unassign All")).clicked() {
+                                unassign = Some(route.id);
+                            }
+                            if ui.button("Delete").clicked() {
+                                delete = Some(route.id);
+                            }
+                        });
+                    });
+            }
+
+            if shared_routes.routes.is_empty() {
+                ui.label("No routes yet");
+            }
+        });
+
+    if let Some(route) = assign.and_then(|id| shared_routes.get(id)) {
+        equipment_state.assign_patrol(&selected_ids, route);
+    }
+    if let Some(id) = unassign {
+        equipment_state.clear_patrol(id);
+    }
+    if let Some(id) = delete {
+        equipment_state.clear_patrol(id);
+        shared_routes.routes.retain(|route| route.id != id);
+    }
+}
+
+// System to draw the selected unit's route as a polyline
+fn draw_selected_route(
+    mut gizmos: Gizmos,
+    selected: Res<SelectedEquipment>,
+    equipment_state: Res<EquipmentTreeState>,
+    waypoints: Res<Waypoints>,
+    shared_routes: Res<SharedRoutes>,
+) {
+    let Some(node) = selected.selected_id.and_then(|id| equipment_state.find_node(id)) else {
+        return;
+    };
+
+    // Shared routes are drawn as a closed loop through their stops
+    if let Some(route) = node.patrol.as_ref().and_then(|patrol| shared_routes.get(patrol.route_id)) {
+        let mut points: Vec<Vec2> = route
+            .stops
+            .iter()
+            .filter_map(|stop| waypoints.position(stop.waypoint))
+            .collect();
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+        gizmos.linestrip_2d(points, Color::srgba(1.0, 0.6, 1.0, 0.8));
+        return;
+    }
+
+    let (Some(route), Some(start)) = (&node.route, node.position) else {
+        return;
+    };
+
+    let mut points: Vec<Vec2> = route
+        .waypoints
+        .iter()
+        .filter_map(|&id| waypoints.position(id))
+        .collect();
+    if route.mode == RouteMode::Patrol {
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+    }
+
+    gizmos.linestrip_2d(
+        std::iter::once(start).chain(points),
+        Color::srgba(0.2, 0.9, 1.0, 0.8),
+    );
+}
+
+// Inspector window for the selected unit
+fn inspector_system(
+    mut contexts: EguiContexts,
+    selected: Res<SelectedEquipment>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut dispatcher: ResMut<MiningDispatcher>,
+    shared_routes: Res<SharedRoutes>,
+) {
+    let Some(selected_id) = selected.selected_id else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+    // Edits are made without change detection, which is set below only
+    // when the player actually changed something
+    let Some(node) = equipment_state.bypass_change_detection().find_node_mut(selected_id) else {
+        return;
+    };
+    let Some(equipment_type) = node.equipment_type() else {
+        return;
+    };
+
+    let mut edited = false;
+    let mut patrol_change = None;
+    egui::Window::new("Inspector")
+        .default_pos(egui::pos2(260.0, 80.0))
+        .default_width(240.0)
+        .show(ctx, |ui| {
+            ui.heading(&node.name);
+            ui.label(equipment_type.name());
+            ui.small(equipment_type.description());
+            edited |= ui
+                .checkbox(&mut node.active, "Enabled")
+                .on_hover_text("Disabled units stop mining, patrolling and following orders")
+                .changed();
+            ui.horizontal(|ui| {
+                ui.label("Tag");
+                for tag in std::iter::once(None).chain(ColorTag::ALL.map(Some)) {
+                    if tag_swatch(ui, tag, node.tag == tag).clicked() {
+                        node.tag = tag;
+                        edited = true;
+                    }
+                }
+            });
+            if let Some(position) = node.position {
+                ui.label(format!("Position: ({:.0}, {:.0})", position.x, position.y));
+            }
+
+            if equipment_type.is_miner() {
+                ui.separator();
+                let mut manual = dispatcher.manual.contains(&selected_id);
+                if ui
+                    .checkbox(&mut manual, "Manual control")
+                    .on_hover_text("Take this miner off zone dispatch and order digs with M")
+                    .changed()
+                {
+                    if manual {
+                        dispatcher.manual.insert(selected_id);
+                        dispatcher.unassign(selected_id);
+                    } else {
+                        dispatcher.manual.remove(&selected_id);
+                    }
+                }
+                match dispatcher.assignments.get(&selected_id) {
+                    Some(job) => ui.label(format!("Digging cell ({}, {})", job.cell.x, job.cell.y)),
+                    None => ui.label("Idle"),
+                };
+            }
+
+            if matches!(equipment_type, EquipmentType::Sampler | EquipmentType::Transport) {
+                ui.separator();
+                let current = node.patrol.as_ref().map(|patrol| patrol.route_id);
+                let current_name = current
+                    .and_then(|id| shared_routes.get(id))
+                    .map_or("None", |route| route.name.as_str());
+                egui::ComboBox::from_label("Shared route")
+                    .selected_text(current_name)
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(current.is_none(), "None").clicked() {
+                            patrol_change = Some(None);
+                        }
+                        for route in &shared_routes.routes {
+                            if ui.selectable_label(current == Some(route.id), &route.name).clicked() {
+                                patrol_change = Some(Some(route.id));
+                            }
+                        }
+                    });
+            }
+
+            // A unit on a shared route doesn't use its own route
+            if equipment_type != EquipmentType::Transport || node.patrol.is_some() {
+                return;
+            }
+
+            ui.separator();
+            ui.label("Route");
+            let route = node.route.get_or_insert_with(Route::new);
+            ui.horizontal(|ui| {
+                edited |= ui.radio_value(&mut route.mode, RouteMode::OneShot, "One-shot").changed();
+                edited |= ui.radio_value(&mut route.mode, RouteMode::Patrol, "Patrol").changed();
+            });
+
+            let mut remove = None;
+            for (i, waypoint_id) in route.waypoints.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let marker = if i == route.next { "▶" } else { " " };
+                    ui.label(format!("{} {}. Waypoint {}", marker, i + 1, waypoint_id));
+                    if ui.small_button("✖").on_hover_text("Remove from route").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = remove {
+                route.remove_at(i);
+                edited = true;
+            }
+
+            if route.waypoints.is_empty() {
+                ui.small("Shift+click waypoints to add them to the route");
+            } else if route.is_complete() {
+                ui.label("Route complete");
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Restart").clicked() {
+                    route.next = 0;
+                    edited = true;
+                }
+                if ui.button("Clear").clicked() {
+                    route.waypoints.clear();
+                    route.next = 0;
+                    edited = true;
+                }
+            });
+        });
+    if edited {
+        equipment_state.set_changed();
+    }
+
+    match patrol_change {
+        Some(Some(route_id)) => {
+            if let Some(route) = shared_routes.get(route_id) {
+                equipment_state.assign_patrol(&[selected_id], route);
+            }
+        }
+        Some(None) => {
+            if let Some(node) = equipment_state.find_node_mut(selected_id) {
+                node.patrol = None;
+            }
+        }
+        None => {}
+    }
+}
+
+// System to drag out a rectangle on the map as a new mining zone
+fn designate_mining_zones(
+    mut pointer: MapPointer,
+    mineral_map: Res<MineralMap>,
+    mut mining_zones: ResMut<MiningZones>,
+    mut gizmos: Gizmos,
+) {
+    if pointer.hotkey(KeyCode::KeyZ) {
+        mining_zones.designating = !mining_zones.designating;
+        mining_zones.drag_start = None;
+    }
+    if !mining_zones.designating {
+        return;
+    }
+
+    let Some(world_position) = pointer.position() else {
+        return;
+    };
+
+    if pointer.just_pressed(MouseButton::Left) && !pointer.over_ui() {
+        mining_zones.drag_start = Some(world_position);
+        pointer.consume(MouseButton::Left);
+    }
+
+    let Some(start) = mining_zones.drag_start else {
+        return;
+    };
+
+    if pointer.pressed(MouseButton::Left) {
+        // Preview the rectangle being dragged
+        let center = (start + world_position) / 2.0;
+        let size = (world_position - start).abs();
+        gizmos.rect_2d(Isometry2d::from_translation(center), size, Color::srgb(1.0, 0.9, 0.2));
+        return;
+    }
+
+    // Released: clamp both corners to the map and store the zone
+    mining_zones.drag_start = None;
+    let half = Vec2::new(mineral_map.width as f32, mineral_map.height as f32) * MAP_SCALE / 2.0;
+    let clamp = |p: Vec2| p.clamp(-half, half - Vec2::splat(0.01));
+    let (Some(a), Some(b)) = (
+        mineral_map.world_to_cell(clamp(start)),
+        mineral_map.world_to_cell(clamp(world_position)),
+    ) else {
+        return;
+    };
+
+    let id = mining_zones.next_id;
+    mining_zones.next_id += 1;
+    mining_zones.zones.push(MiningZone {
+        id,
+        min: a.min(b),
+        max: a.max(b),
+    });
+}
+
+// System for the drainage preview tool: F toggles it, dragging on the map
+// picks the source region, and the simulated flow is drawn as streamlines
+// ending in arrows, with the cells where the liquid settles shaded blue
+fn drainage_preview_system(
+    mut pointer: MapPointer,
+    mineral_map: Res<MineralMap>,
+    mut preview: ResMut<DrainagePreview>,
+    mut gizmos: Gizmos,
+) {
+    if pointer.hotkey(KeyCode::KeyF) {
+        preview.active = !preview.active;
+        preview.drag_start = None;
+    }
+    if !preview.active {
+        return;
+    }
+
+    let ctx = pointer.contexts.ctx_mut();
+    egui::Window::new("Drainage Preview")
+        .default_width(240.0)
+        .show(ctx, |ui| {
+            ui.small("Drag a rectangle over a pocket to preview where liquid released from it would flow (F to close)");
+            match (&preview.source, &preview.flow) {
+                (Some(_), Some(flow)) => {
+                    ui.label(format!("{} cells of liquid", flow.settled.len()));
+                    ui.label(format!("{} would leave the region", flow.escaped));
+                }
+                _ => {
+                    ui.label("No source region");
+                }
+            }
+        });
+
+    if let Some(world_position) = pointer.position() {
+        if pointer.just_pressed(MouseButton::Left) && !pointer.over_ui() {
+            preview.drag_start = Some(world_position);
+            pointer.consume(MouseButton::Left);
+        }
+        if let Some(start) = preview.drag_start {
+            if pointer.pressed(MouseButton::Left) {
+                let center = (start + world_position) / 2.0;
+                let size = (world_position - start).abs();
+                gizmos.rect_2d(Isometry2d::from_translation(center), size, Color::srgb(0.3, 0.6, 1.0));
+                return;
+            }
+            // Released: clamp both corners to the map and set the source
+            preview.drag_start = None;
+            let half = Vec2::new(mineral_map.width as f32, mineral_map.height as f32) * MAP_SCALE / 2.0;
+            let clamp = |p: Vec2| p.clamp(-half, half - Vec2::splat(0.01));
+            if let (Some(a), Some(b)) = (
+                mineral_map.world_to_cell(clamp(start)),
+                mineral_map.world_to_cell(clamp(world_position)),
+            ) {
+                preview.source = Some((a.min(b), a.max(b)));
+                preview.flow = None;
+            }
+        }
+    }
+
+    let Some((min, max)) = preview.source else {
+        return;
+    };
+    // Re-run whenever digging opens new paths
+    if preview.flow.is_none() || mineral_map.is_changed() {
+        preview.flow = Some(simulate_drainage(&mineral_map, min, max));
+    }
+
+    let corner = |cell: UVec2| mineral_map.cell_to_world(cell);
+    gizmos.rect_2d(
+        Isometry2d::from_translation((corner(min) + corner(max)) / 2.0),
+        (corner(max) - corner(min)).abs() + Vec2::splat(MAP_SCALE),
+        Color::srgb(0.3, 0.6, 1.0),
+    );
+    let Some(flow) = &preview.flow else {
+        return;
+    };
+    for &cell in &flow.settled {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(mineral_map.cell_to_world(cell)),
+            Vec2::splat(MAP_SCALE),
+            Color::srgba(0.2, 0.5, 1.0, 0.6),
+        );
+    }
+    for path in &flow.paths {
+        let points: Vec<Vec2> = path.iter().map(|&cell| mineral_map.cell_to_world(cell)).collect();
+        gizmos.linestrip_2d(points.iter().copied(), Color::srgba(0.6, 0.85, 1.0, 0.8));
+        if let [.., from, to] = points.as_slice() {
+            gizmos.arrow_2d(*from, *to, Color::srgb(0.6, 0.85, 1.0)).with_tip_length(MAP_SCALE * 3.0);
+        }
+    }
+}
+
+// Where the miners are, and what they are doing besides mining
+#[derive(SystemParam)]
+struct MinerRoster<'w, 's> {
+    equipment_state: Res<'w, EquipmentTreeState>,
+    sprite_query: Query<'w, 's, (&'static Transform, &'static EquipmentSprite)>,
+    move_orders: Res<'w, MoveOrders>,
+}
+
+// System to assign unclaimed zone cells to idle miners, balancing distance
+// against how many miners are already working each zone
+fn dispatch_mining_jobs(
+    time: Res<Time>,
+    mineral_map: Res<MineralMap>,
+    mining_zones: Res<MiningZones>,
+    mut dispatcher: ResMut<MiningDispatcher>,
+    roster: MinerRoster,
+    mut cell_history: ResMut<CellHistory>,
+) {
+    let MinerRoster {
+        equipment_state,
+        sprite_query,
+        move_orders,
+    } = roster;
+    // Drop jobs whose zone was removed or whose cell was mined by someone else
+    dispatcher.retain_jobs(|_, job| {
+        job.zone_id.is_none_or(|zone_id| mining_zones.zones.iter().any(|zone| zone.id == zone_id))
+            && mineral_map
+                .get(job.cell.x as usize, job.cell.y as usize)
+                .is_some_and(|cell| !cell.mined)
+    });
+
+    for (transform, equipment_sprite) in &sprite_query {
+        let id = equipment_sprite.equipment_id;
+        if dispatcher.assignments.contains_key(&id)
+            || dispatcher.manual.contains(&id)
+            || move_orders.is_moving(id)
+            || !equipment_state.is_enabled(id)
+        {
+            continue;
+        }
+        let Some(miner) = equipment_state
+            .find_node(id)
+            .and_then(|node| node.equipment_type())
+            .and_then(|equipment_type| equipment_type.miner_kind())
+        else {
+            continue;
+        };
+
+        let _span = info_span!("mining_job_search", equipment_id = id).entered();
+        let position = transform.translation.truncate();
+        let mut best: Option<(f32, UVec2, usize)> = None;
+
+        for zone in &mining_zones.zones {
+            let load_penalty = dispatcher.zone_load(zone.id) as f32 * ZONE_LOAD_PENALTY;
+
+            for cell_position in zone.cells() {
+                if !miner.can_mine_row(cell_position.y, mineral_map.height)
+                    || dispatcher.is_claimed(cell_position)
+                {
+                    continue;
+                }
+                let Some(cell) = mineral_map.get(cell_position.x as usize, cell_position.y as usize) else {
+                    continue;
+                };
+                if cell.mined || !cell.mineral_type.is_minable() {
+                    continue;
+                }
+
+                let cost = position.distance(mineral_map.cell_to_world(cell_position)) + load_penalty;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, cell_position, zone.id));
+                }
+            }
+        }
+
+        if let Some((_, cell, zone_id)) = best {
+            cell_history.record(cell, time.elapsed_secs(), CellEventKind::Targeted { unit: id });
+            dispatcher.assign(
+                id,
+                MiningJob {
+                    cell,
+                    zone_id: Some(zone_id),
+                    progress: 0.0,
+                },
+            );
+        }
+    }
+}
+
+// Digging cells out of the map for units: minerals go to the stockpile,
+// region depletion and cell history are updated, and `finish` writes the
+// dug cells into the map texture
+#[derive(SystemParam)]
+struct MapDigger<'w, 's> {
+    mineral_map: ResMut<'w, MineralMap>,
+    depletion: ResMut<'w, RegionDepletion>,
+    stockpile: ResMut<'w, Stockpile>,
+    cell_history: ResMut<'w, CellHistory>,
+    images: ResMut<'w, Assets<Image>>,
+    map_query: Query<'w, 's, &'static Sprite, With<MineralMapRenderer>>,
+    dug: Local<'s, Vec<(usize, usize)>>,
+}
+
+impl MapDigger<'_, '_> {
+    /// Dig out a cell for a unit; magma and cells already dug are left alone
+    fn dig(&mut self, cell_position: UVec2, unit: usize, now: f32) {
+        let Some((mineral, amount)) = dig_cell(&mut self.mineral_map, &mut self.depletion, cell_position) else {
+            return;
+        };
+        if mineral != MineralType::Empty {
+            *self.stockpile.amounts.entry(mineral).or_default() += amount;
+        }
+        self.cell_history.record(cell_position, now, CellEventKind::Mined { unit });
+        self.dug.push((cell_position.x as usize, cell_position.y as usize));
+    }
+
+    /// Write the cells dug since the last call into the map texture
+    fn finish(&mut self) {
+        if let Ok(map_sprite) = self.map_query.single() {
+            write_map_cells(&self.mineral_map, &mut self.images, map_sprite, &self.dug);
+        }
+        self.dug.clear();
+    }
+}
+
+// System to drive miners to their assigned cells and dig them out
+fn work_mining_jobs(
+    time: Res<Time>,
+    mut dispatcher: ResMut<MiningDispatcher>,
+    equipment_state: Res<EquipmentTreeState>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+    mut digger: MapDigger,
+) {
+    let delta = time.delta_secs();
+    let mut finished = Vec::new();
+
+    for (mut transform, equipment_sprite) in &mut sprite_query {
+        let id = equipment_sprite.equipment_id;
+        let Some(job) = dispatcher.assignments.get_mut(&id) else {
+            continue;
+        };
+        // Disabled miners keep their job but stop working on it
+        let Some(equipment_type) = equipment_state
+            .find_node(id)
+            .filter(|node| node.active)
+            .and_then(|node| node.equipment_type())
+        else {
+            continue;
+        };
+
+        let target = digger.mineral_map.cell_to_world(job.cell);
+        let current = transform.translation.truncate();
+        let step = equipment_type.move_speed() * delta;
+
+        if current.distance(target) > step {
+            let next = current + (target - current).normalize() * step;
+            transform.translation = next.extend(transform.translation.z);
+            continue;
+        }
+
+        transform.translation = target.extend(transform.translation.z);
+        job.progress += delta;
+        if job.progress < MINE_SECONDS {
+            continue;
+        }
+
+        // Dig out the target cell and its neighbours within the mining radius
+        let footprint = equipment_type
+            .miner_kind()
+            .map(|miner| mining_footprint(&digger.mineral_map, miner, job.cell))
+            .unwrap_or_default();
+        for cell_position in footprint {
+            digger.dig(cell_position, id, time.elapsed_secs());
+        }
+        finished.push(id);
+    }
+
+    for id in finished {
+        dispatcher.unassign(id);
+    }
+    digger.finish();
+}
+
+/// Write changed cells into the map texture
+fn write_map_cells(mineral_map: &MineralMap, images: &mut Assets<Image>, map_sprite: &Sprite, cells: &[(usize, usize)]) {
+    if cells.is_empty() {
+        return;
+    }
+    let _span = info_span!("map_texture_update", cells = cells.len()).entered();
+    let Some(data) = images.get_mut(&map_sprite.image).and_then(|image| image.data.as_mut()) else {
+        return;
+    };
+    for &(x, y) in cells {
+        if let Some(cell) = mineral_map.get(x, y) {
+            let offset = (y * mineral_map.width + x) * 4;
+            data[offset..offset + 4].copy_from_slice(&cell.pixel());
+        }
+    }
+}
+
+/// Cells a laser beam cuts from a drill toward a target: a straight line up
+/// to the drill's range, stopping short of the first very dense solid, which
+/// is returned as well
+fn laser_channel(map: &MineralMap, from: UVec2, to: UVec2) -> (Vec<UVec2>, Option<UVec2>) {
+    let delta = to.as_vec2() - from.as_vec2();
+    let steps = delta.x.abs().max(delta.y.abs()) as usize;
+    let mut channel = Vec::new();
+    for step in 1..=steps.min(LASER_RANGE) {
+        let cell = (from.as_vec2() + delta * (step as f32 / steps as f32)).round().as_uvec2();
+        let Some(map_cell) = map.get(cell.x as usize, cell.y as usize) else {
+            break;
+        };
+        let solid = !map_cell.mined && map_cell.mineral_type.is_minable();
+        if solid && map_cell.density >= LASER_BLOCKING_DENSITY {
+            return (channel, Some(cell));
+        }
+        channel.push(cell);
+    }
+    (channel, None)
+}
+
+// Resource tracking laser drill beams and heat, keyed by equipment ID
+#[derive(Resource, Default)]
+struct LaserDrills {
+    beams: std::collections::HashMap<usize, LaserBeam>,
+    heat: std::collections::HashMap<usize, f32>, // 0.0 cold to 1.0 overheated
+    overheated: HashSet<usize>,                   // Drills cooling down before they can fire again
+}
+
+struct LaserBeam {
+    channel: Vec<UVec2>,
+    cut: f32, // Cells of the channel cut so far, counting the one in progress
+}
+
+// System to aim the selected laser drill at the cursor, previewing the
+// channel it would cut, and to fire it with L
+fn aim_laser_drill(
+    mut pointer: MapPointer,
+    selected: SelectedUnit,
+    mineral_map: Res<MineralMap>,
+    mut lasers: ResMut<LaserDrills>,
+    mut event_log: ResMut<EventLog>,
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+) {
+    let Some(node) = selected
+        .node()
+        .filter(|node| node.equipment_type() == Some(EquipmentType::LaserDrill))
+    else {
+        return;
+    };
+    if pointer.over_ui() {
+        return;
+    }
+    let Some(origin) = node.position.and_then(|position| mineral_map.world_to_cell(position)) else {
+        return;
+    };
+    let Some(target) = pointer.position().and_then(|position| mineral_map.world_to_cell(position)) else {
+        return;
+    };
+
+    let (channel, blocked) = laser_channel(&mineral_map, origin, target);
+    if let Some(&end) = channel.last() {
+        gizmos.line_2d(
+            mineral_map.cell_to_world(origin),
+            mineral_map.cell_to_world(end),
+            Color::srgba(1.0, 0.3, 0.3, 0.5),
+        );
+    }
+    if let Some(cell) = blocked {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(mineral_map.cell_to_world(cell)),
+            Vec2::splat(MAP_SCALE * 1.5),
+            Color::srgb(0.5, 0.5, 0.5),
+        );
+    }
+
+    if pointer.hotkey(KeyCode::KeyL) && !channel.is_empty() {
+        if lasers.overheated.contains(&node.id) {
+            event_log.push(time.elapsed_secs(), format!("{} is cooling down", node.name), node.position);
+            return;
+        }
+        lasers.beams.insert(node.id, LaserBeam { channel, cut: 0.0 });
+    }
+}
+
+// System to cut laser channels a cell at a time, heating drills while they
+// fire and cooling them while idle, and to draw the beams
+fn work_laser_drills(
+    time: Res<Time>,
+    mut digger: MapDigger,
+    mut lasers: ResMut<LaserDrills>,
+    equipment_state: Res<EquipmentTreeState>,
+    mut event_log: ResMut<EventLog>,
+    mut gizmos: Gizmos,
+) {
+    let delta = time.delta_secs();
+    let lasers = &mut *lasers;
+    let mut finished = Vec::new();
+
+    for (&id, beam) in &mut lasers.beams {
+        // Disabled or removed drills stop firing
+        let Some(node) = equipment_state.find_node(id).filter(|node| node.active) else {
+            finished.push(id);
+            continue;
+        };
+        let Some(origin) = node.position else {
+            continue;
+        };
+
+        let heat = lasers.heat.entry(id).or_default();
+        *heat += LASER_HEAT_PER_SECOND * delta;
+        let before = beam.cut as usize;
+        beam.cut = (beam.cut + LASER_CELLS_PER_SECOND * delta).min(beam.channel.len() as f32);
+        // Magma is left alone; the beam passes over it
+        for &cell_position in &beam.channel[before..beam.cut as usize] {
+            digger.dig(cell_position, id, time.elapsed_secs());
+        }
+
+        // The beam runs from the drill to the cell being cut, glowing whiter as it heats
+        let head = beam.channel[(beam.cut as usize).min(beam.channel.len() - 1)];
+        let glow = heat.min(1.0);
+        gizmos.line_2d(
+            origin,
+            digger.mineral_map.cell_to_world(head),
+            Color::srgb(1.0, 0.2 + glow * 0.8, 0.2 + glow * 0.6),
+        );
+
+        if *heat >= 1.0 {
+            lasers.overheated.insert(id);
+            event_log.push(time.elapsed_secs(), format!("{} overheated and stopped", node.name), Some(origin));
+            finished.push(id);
+        } else if beam.cut as usize >= beam.channel.len() {
+            finished.push(id);
+        }
+    }
+    for id in finished {
+        lasers.beams.remove(&id);
+    }
+
+    // Idle drills cool down, and a heat gauge shows over any warm drill
+    lasers.heat.retain(|id, heat| {
+        if !lasers.beams.contains_key(id) {
+            *heat -= LASER_COOL_PER_SECOND * delta;
+        }
+        if *heat <= 0.0 {
+            lasers.overheated.remove(id);
+            return false;
+        }
+        if let Some(position) = equipment_state.find_node(*id).and_then(|node| node.position) {
+            let start = position + Vec2::new(-10.0, 20.0);
+            let color = if lasers.overheated.contains(id) {
+                Color::srgb(1.0, 0.2, 0.1)
+            } else {
+                Color::srgb(1.0, 0.7, 0.2)
+            };
+            gizmos.line_2d(start, start + Vec2::X * 20.0 * heat.min(1.0), color);
+        }
+        true
+    });
+
+    digger.finish();
+}
+
+// The selected unit's tree node
+#[derive(SystemParam)]
+struct SelectedUnit<'w> {
+    selected: Res<'w, SelectedEquipment>,
+    equipment_state: Res<'w, EquipmentTreeState>,
+}
+
+impl SelectedUnit<'_> {
+    fn node(&self) -> Option<&EquipmentTreeNode> {
+        self.selected.selected_id.and_then(|id| self.equipment_state.find_node(id))
+    }
+}
+
+// Manual dig orders: queued on the plan while planning, otherwise assigned
+// straight to the miner
+#[derive(SystemParam)]
+struct DigOrders<'w> {
+    dispatcher: ResMut<'w, MiningDispatcher>,
+    planning: ResMut<'w, PlanningMode>,
+    cell_history: ResMut<'w, CellHistory>,
+    time: Res<'w, Time>,
+}
+
+impl DigOrders<'_> {
+    fn order_dig(&mut self, unit: usize, cell: UVec2) {
+        if self.planning.active {
+            self.planning.queue_dig(unit, cell);
+            return;
+        }
+        self.cell_history.record(cell, self.time.elapsed_secs(), CellEventKind::Targeted { unit });
+        self.dispatcher.assign(
+            unit,
+            MiningJob {
+                cell,
+                zone_id: None,
+                progress: 0.0,
+            },
+        );
+    }
+}
+
+// System to preview the cells a manually controlled miner would dig at the
+// cursor, tinted by yield, and to order the dig with M
+fn preview_manual_mining(
+    mut pointer: MapPointer,
+    selection: SelectedUnit,
+    mineral_map: Res<MineralMap>,
+    depletion: Res<RegionDepletion>,
+    mut orders: DigOrders,
+    mut gizmos: Gizmos,
+) {
+    let Some(node) = selection.node() else {
+        return;
+    };
+    let selected_id = node.id;
+    if !orders.dispatcher.manual.contains(&selected_id) {
+        return;
+    }
+    let Some(miner) = node.equipment_type().and_then(|equipment_type| equipment_type.miner_kind()) else {
+        return;
+    };
+    if pointer.over_ui() {
+        return;
+    }
+    let Some(target) = pointer.position().and_then(|position| mineral_map.world_to_cell(position)) else {
+        return;
+    };
+
+    let footprint = mining_footprint(&mineral_map, miner, target);
+    let cell_size = Vec2::splat(MAP_SCALE);
+    for cell_position in &footprint {
+        let Some(cell) = mineral_map.get(cell_position.x as usize, cell_position.y as usize) else {
+            continue;
+        };
+        let expected_yield = if !cell.mineral_type.is_minable() {
+            0.0
+        } else {
+            cell.density * depletion.yield_factor(*cell_position)
+        };
+        let tint = Color::srgb(1.0 - expected_yield, expected_yield, 0.2);
+        gizmos.rect_2d(
+            Isometry2d::from_translation(mineral_map.cell_to_world(*cell_position)),
+            cell_size,
+            tint,
+        );
+    }
+
+    // Out of reach or already dug out: outline the cursor cell in grey
+    if footprint.is_empty() {
+        gizmos.rect_2d(
+            Isometry2d::from_translation(mineral_map.cell_to_world(target)),
+            cell_size * 1.5,
+            Color::srgb(0.5, 0.5, 0.5),
+        );
+        return;
+    }
+
+    if pointer.hotkey(KeyCode::KeyM) {
+        orders.order_dig(selected_id, target);
+    }
+}
+
+// System to outline mining zones and, when enabled, show miner assignments
+fn draw_mining_overlay(
+    mut gizmos: Gizmos,
+    mineral_map: Res<MineralMap>,
+    mining_zones: Res<MiningZones>,
+    dispatcher: Res<MiningDispatcher>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+) {
+    for zone in &mining_zones.zones {
+        let a = mineral_map.cell_to_world(zone.min);
+        let b = mineral_map.cell_to_world(zone.max);
+        let size = (b - a).abs() + Vec2::splat(MAP_SCALE);
+        gizmos.rect_2d(Isometry2d::from_translation((a + b) / 2.0), size, Color::srgb(1.0, 0.9, 0.2));
+    }
+
+    if !dispatcher.show_overlay {
+        return;
+    }
+    for (transform, equipment_sprite) in &sprite_query {
+        if let Some(job) = dispatcher.assignments.get(&equipment_sprite.equipment_id) {
+            let target = mineral_map.cell_to_world(job.cell);
+            gizmos.line_2d(transform.translation.truncate(), target, Color::srgb(1.0, 0.4, 0.1));
+            gizmos.circle_2d(Isometry2d::from_translation(target), MAP_SCALE * 2.0, Color::srgb(1.0, 0.4, 0.1));
+        }
+    }
+}
+
+// System for samplers surveying faults: a sampler passing near a fault
+// trace finds it, and found faults are drawn with an arrow where the
+// ground was raised most, bringing deep minerals near the surface
+fn survey_faults(
+    time: Res<Time>,
+    mut gizmos: Gizmos,
+    mut mineral_map: ResMut<MineralMap>,
+    equipment_state: Res<EquipmentTreeState>,
+    mut event_log: ResMut<EventLog>,
+) {
+    for id in equipment_state.all_ids() {
+        let Some(node) = equipment_state.find_node(id) else {
+            continue;
+        };
+        if node.equipment_type() != Some(EquipmentType::Sampler) || !node.active {
+            continue;
+        }
+        let Some(cell) = node.position.and_then(|position| mineral_map.world_to_cell(position)) else {
+            continue;
+        };
+        let Some(index) = mineral_map
+            .fault_near(cell, FAULT_SURVEY_RADIUS)
+            .filter(|&index| !mineral_map.faults[index].discovered)
+        else {
+            continue;
+        };
+        mineral_map.faults[index].discovered = true;
+        let throw = mineral_map.faults[index].throw;
+        event_log.push(
+            time.elapsed_secs(),
+            format!(
+                "{} found a fault: ground raised {} cells, deep minerals lie near the surface",
+                node.name, throw
+            ),
+            Some(mineral_map.cell_to_world(cell)),
+        );
+    }
+
+    let color = Color::srgb(1.0, 0.55, 0.1);
+    for fault in mineral_map.faults.iter().filter(|fault| fault.discovered) {
+        let start = mineral_map.cell_to_world(fault.start);
+        let end = mineral_map.cell_to_world(fault.end);
+        gizmos.line_2d(start, end, color);
+        // Arrow at the point of greatest throw, pointing up toward the surface
+        let middle = (start + end) / 2.0;
+        gizmos.arrow_2d(middle - Vec2::Y * MAP_SCALE * 6.0, middle + Vec2::Y * MAP_SCALE * 6.0, color);
+    }
+}
+
+// Orders a new move order replaces, or the plan it is queued on instead
+#[derive(SystemParam)]
+struct UnitOrders<'w> {
+    move_orders: ResMut<'w, MoveOrders>,
+    dispatcher: ResMut<'w, MiningDispatcher>,
+    planning: ResMut<'w, PlanningMode>,
+}
+
+// System to issue a move order to the selected units on right-click.
+// Units keep their formation; holding Shift packs them into a compact grid.
+fn issue_move_orders(
+    pointer: MapPointer,
+    equipment_state: Res<EquipmentTreeState>,
+    equipment_actions: Res<EquipmentTreeActions>,
+    selected: Res<SelectedEquipment>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+    orders: UnitOrders,
+) {
+    let MapPointer {
+        mut mouse_button,
+        keyboard,
+        windows,
+        camera_query,
+        map_cursor,
+        mut contexts,
+    } = pointer;
+    let UnitOrders {
+        mut move_orders,
+        mut dispatcher,
+        mut planning,
+    } = orders;
+    // Right-click, or Enter on an empty cell under the keyboard cursor
+    let destination = if map_cursor.confirmed(&keyboard) {
+        if unit_at(&sprite_query, map_cursor.position).is_some() {
+            return;
+        }
+        map_cursor.position
+    } else {
+        if !mouse_button.just_pressed(MouseButton::Right) || contexts.ctx_mut().is_pointer_over_area() {
+            return;
+        }
+        let Some(destination) = cursor_world_position(&windows, &camera_query) else {
+            return;
+        };
+        destination
+    };
+
+    // Outliner selection, with containers standing for all units inside them
+    let mut ids = Vec::new();
+    for &id in &equipment_actions.selected {
+        if let Some(node) = equipment_state.find_node(id) {
+            node.collect_ids(&mut ids);
+        }
+    }
+    if ids.is_empty() {
+        ids.extend(selected.selected_id);
+    }
+    let group: HashSet<usize> = ids.into_iter().collect();
+
+    let mut members = Vec::new();
+    let mut obstacles = Vec::new();
+    for (transform, equipment_sprite) in &sprite_query {
+        let position = transform.translation.truncate();
+        let id = equipment_sprite.equipment_id;
+        match equipment_state.find_node(id).and_then(|node| node.equipment_type()) {
+            // Disabled units ignore the order and stay where they are
+            Some(equipment_type) if group.contains(&id) && equipment_state.is_enabled(id) => {
+                members.push((id, position, equipment_type))
+            }
+            _ => obstacles.push(position),
+        }
+    }
+    if members.is_empty() {
+        return;
+    }
+    // Stable ordering so repeated orders resolve overlaps the same way
+    members.sort_by_key(|(id, _, _)| *id);
+
+    let compact = keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight);
+    let positions: Vec<Vec2> = members.iter().map(|(_, position, _)| *position).collect();
+    let mut targets = formation_targets(&positions, destination, compact || members.len() == 1);
+    resolve_destination_overlaps(&mut targets, &obstacles);
+
+    let mut formation = FormationMove {
+        targets: std::collections::HashMap::new(),
+        unit_count: members.len(),
+        speed: f32::MAX,
+        initial_distance: 0.0,
+        remaining_distance: 0.0,
+    };
+    for ((id, position, equipment_type), target) in members.iter().zip(targets) {
+        formation.speed = formation.speed.min(equipment_type.move_speed());
+        formation.initial_distance += position.distance(target);
+        formation.targets.insert(*id, target);
+    }
+    formation.remaining_distance = formation.initial_distance;
+
+    if planning.active {
+        planning.queue_move(formation);
+    } else {
+        for id in formation.targets.keys() {
+            move_orders.cancel(*id);
+            dispatcher.unassign(*id);
+        }
+        move_orders.groups.push(formation);
+    }
+
+    mouse_button.clear_just_pressed(MouseButton::Right);
+}
+
+// System to move units under a move order at their group's shared speed
+fn follow_move_orders(
+    time: Res<Time>,
+    mut move_orders: ResMut<MoveOrders>,
+    equipment_state: Res<EquipmentTreeState>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+) {
+    let delta = time.delta_secs();
+    for group in &mut move_orders.groups {
+        let step = group.speed * delta;
+        group.remaining_distance = 0.0;
+
+        for (mut transform, equipment_sprite) in &mut sprite_query {
+            let id = equipment_sprite.equipment_id;
+            let Some(&target) = group.targets.get(&id) else {
+                continue;
+            };
+
+            // Units disabled on the way drop out of the group
+            if !equipment_state.is_enabled(id) {
+                group.targets.remove(&id);
+                continue;
+            }
+            let current = transform.translation.truncate();
+            let distance = current.distance(target);
+            if distance <= step {
+                transform.translation = target.extend(transform.translation.z);
+                group.targets.remove(&id);
+            } else {
+                let next = current + (target - current) / distance * step;
+                transform.translation = next.extend(transform.translation.z);
+                group.remaining_distance += distance - step;
+            }
+        }
+    }
+    move_orders.groups.retain(|group| !group.targets.is_empty());
+}
+
+// System to mark move destinations and show each group's progress
+fn show_move_orders(
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos,
+    move_orders: Res<MoveOrders>,
+) {
+    if move_orders.groups.is_empty() {
+        return;
+    }
+
+    for group in &move_orders.groups {
+        for target in group.targets.values() {
+            gizmos.circle_2d(Isometry2d::from_translation(*target), 6.0, Color::srgb(0.3, 1.0, 0.4));
+        }
+    }
+
+    egui::Area::new(egui::Id::new("move_order_progress"))
+        .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(200.0, -40.0))
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for group in &move_orders.groups {
+                    let progress = if group.initial_distance > 0.0 {
+                        1.0 - group.remaining_distance / group.initial_distance
+                    } else {
+                        1.0
+                    };
+                    ui.add(
+                        egui::ProgressBar::new(progress.clamp(0.0, 1.0))
+                            .desired_width(180.0)
+                            .text(format!("Moving {} units", group.unit_count)),
+                    );
+                }
+            });
+        });
+}
+
+// System to draw planned orders as ghosts: arrows from each unit to its
+// planned destination or dig site
+fn draw_planned_orders(
+    planning: Res<PlanningMode>,
+    mineral_map: Res<MineralMap>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+    mut gizmos: Gizmos,
+) {
+    if !planning.active {
+        return;
+    }
+    let color = Color::srgba(0.7, 0.5, 1.0, 0.8);
+    let positions: std::collections::HashMap<usize, Vec2> = sprite_query
+        .iter()
+        .map(|(transform, sprite)| (sprite.equipment_id, transform.translation.truncate()))
+        .collect();
+
+    let targets = planning.moves.iter().flat_map(|group| group.targets.iter());
+    for (id, target) in targets {
+        gizmos.circle_2d(Isometry2d::from_translation(*target), 10.0, color);
+        if let Some(&position) = positions.get(id) {
+            gizmos.arrow_2d(position, *target, color);
+        }
+    }
+    for (id, cell) in &planning.digs {
+        let site = mineral_map.cell_to_world(*cell);
+        gizmos.rect_2d(Isometry2d::from_translation(site), Vec2::splat(MAP_SCALE * 4.0), color);
+        if let Some(&position) = positions.get(id) {
+            gizmos.arrow_2d(position, site, color);
+        }
+    }
+}
+
+// Window listing the orders queued in planning mode, with Execute and Discard
+fn planning_window(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    mut planning: ResMut<PlanningMode>,
+    mut move_orders: ResMut<MoveOrders>,
+    mut dispatcher: ResMut<MiningDispatcher>,
+    mut cell_history: ResMut<CellHistory>,
+    sprite_query: Query<(&Transform, &EquipmentSprite)>,
+) {
+    if !planning.active {
+        return;
+    }
+
+    let mut open = true;
+    let mut execute = false;
+    egui::Window::new("Planning")
+        .open(&mut open)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 40.0))
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("Orders are queued until the plan is executed");
+            ui.small("Right-click: move | M: dig with a manual miner");
+            ui.separator();
+            for group in &planning.moves {
+                ui.label(format!("Move {} units", group.targets.len()));
+            }
+            for (unit, cell) in &planning.digs {
+                ui.label(format!("Unit #{} digs ({}, {})", unit, cell.x, cell.y));
+            }
+            if planning.order_count() == 0 {
+                ui.label("No orders yet");
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                execute = ui
+                    .add_enabled(planning.order_count() > 0, egui::Button::new("Execute plan"))
+                    .clicked();
+                if ui.button("Discard").clicked() {
+                    planning.discard();
+                }
+            });
+        });
+    if !open {
+        planning.discard();
+    }
+    if !execute {
+        return;
+    }
+
+    // Run every queued order at once, measuring progress from where units are now
+    let positions: std::collections::HashMap<usize, Vec2> = sprite_query
+        .iter()
+        .map(|(transform, sprite)| (sprite.equipment_id, transform.translation.truncate()))
+        .collect();
+    for mut formation in std::mem::take(&mut planning.moves) {
+        formation.initial_distance = 0.0;
+        for (id, target) in &formation.targets {
+            move_orders.cancel(*id);
+            dispatcher.unassign(*id);
+            if let Some(position) = positions.get(id) {
+                formation.initial_distance += position.distance(*target);
+            }
+        }
+        formation.remaining_distance = formation.initial_distance;
+        move_orders.groups.push(formation);
+    }
+    // A unit has at most one planned order, but may still be on a move
+    // ordered before the plan
+    for (unit, cell) in std::mem::take(&mut planning.digs) {
+        move_orders.cancel(unit);
+        cell_history.record(cell, time.elapsed_secs(), CellEventKind::Targeted { unit });
+        dispatcher.assign(
+            unit,
+            MiningJob {
+                cell,
+                zone_id: None,
+                progress: 0.0,
+            },
+        );
+    }
+    planning.active = false;
+}
+
+// Inspect tool (I): click a cell to see what it is and what has happened to it
+fn inspect_cells(
+    pointer: MapPointer,
+    mineral_map: Res<MineralMap>,
+    depletion: Res<RegionDepletion>,
+    mut cell_history: ResMut<CellHistory>,
+    equipment_state: Res<EquipmentTreeState>,
+    mut gizmos: Gizmos,
+) {
+    let MapPointer {
+        mut mouse_button,
+        mut keyboard,
+        windows,
+        camera_query,
+        map_cursor,
+        mut contexts,
+    } = pointer;
+    let ctx = contexts.ctx_mut();
+    if keyboard.just_pressed(KeyCode::KeyI) && !ctx.wants_keyboard_input() {
+        cell_history.inspecting = !cell_history.inspecting;
+    }
+
+    if cell_history.inspecting
+        && mouse_button.just_pressed(MouseButton::Left)
+        && !ctx.is_pointer_over_area()
+    {
+        if let Some(cell) = cursor_world_position(&windows, &camera_query)
+            .and_then(|position| mineral_map.world_to_cell(position))
+        {
+            cell_history.inspected = Some(cell);
+            mouse_button.clear_just_pressed(MouseButton::Left);
+        }
+    }
+    if cell_history.inspecting && map_cursor.confirmed(&keyboard) {
+        cell_history.inspected = Some(map_cursor.cell);
+        keyboard.clear_just_pressed(KeyCode::Enter);
+    }
+
+    let Some(cell_position) = cell_history.inspected else {
+        return;
+    };
+    let Some(cell) = mineral_map.get(cell_position.x as usize, cell_position.y as usize) else {
+        return;
+    };
+    gizmos.rect_2d(
+        Isometry2d::from_translation(mineral_map.cell_to_world(cell_position)),
+        Vec2::splat(MAP_SCALE * 3.0),
+        Color::WHITE,
+    );
+
+    let unit_name = |id: usize| {
+        equipment_state
+            .find_node(id)
+            .map_or_else(|| format!("Unit #{}", id), |node| node.name.clone())
+    };
+
+    let mut open = true;
+    egui::Window::new(format!("Cell ({}, {})", cell_position.x, cell_position.y))
+        .id(egui::Id::new("cell_inspector"))
+        .open(&mut open)
+        .default_width(260.0)
+        .show(ctx, |ui| {
+            egui::Grid::new("cell_info").num_columns(2).show(ui, |ui| {
+                ui.label("Mineral");
+                ui.label(cell.mineral_type.name());
+                ui.end_row();
+                ui.label("Biome");
+                ui.label(cell.biome.name());
+                ui.end_row();
+                ui.label("Stratum");
+                ui.label(cell.stratum.name());
+                ui.end_row();
+                ui.label("Density");
+                ui.label(format!("{:.2}", cell.density));
+                ui.end_row();
+                if let Some(temperature) =
+                    mineral_map.temperature_at(cell_position.x as usize, cell_position.y as usize)
+                {
+                    ui.label("Temperature");
+                    ui.label(format!("{:.0} K", temperature));
+                    ui.end_row();
+                }
+                ui.label("Depth");
+                ui.label(format!("{:.0}%", cell_position.y as f32 / mineral_map.height as f32 * 100.0));
+                ui.end_row();
+                ui.label("State");
+                ui.label(if cell.mined { "Mined out" } else { "Intact" });
+                ui.end_row();
+                ui.label("Region yield");
+                ui.label(format!("{:.0}%", depletion.yield_factor(cell_position) * 100.0));
+                ui.end_row();
+            });
+
+            ui.separator();
+            ui.label("History");
+            let mut any = false;
+            for event in cell_history.for_cell(cell_position) {
+                any = true;
+                let description = match event.kind {
+                    CellEventKind::Targeted { unit } => format!("Targeted by {}", unit_name(unit)),
+                    CellEventKind::Mined { unit } => format!("Mined by {}", unit_name(unit)),
+                };
+                ui.label(format!("[{}] {}", format_clock(event.time), description));
+            }
+            if !any {
+                ui.label("Nothing recorded");
+            }
+        });
+
+    if !open {
+        cell_history.inspected = None;
+    }
+}
+
+// Remaining cells and average yield per zone for the Mining Zones window,
+// recomputed only after the zones, the map or region depletion change
+#[derive(Default)]
+struct ZoneSummaries {
+    zones: std::collections::HashMap<usize, (usize, f32)>,
+    valid: bool,
+}
+
+impl ZoneSummaries {
+    fn refresh(&mut self, mining_zones: &MiningZones, mineral_map: &MineralMap, depletion: &RegionDepletion) {
+        self.zones.clear();
+        for zone in &mining_zones.zones {
+            let remaining = zone
+                .cells()
+                .filter_map(|c| mineral_map.get(c.x as usize, c.y as usize))
+                .filter(|cell| !cell.mined && cell.mineral_type.is_minable())
+                .count();
+            // Average yield over the zone's cells, as regions deplete unevenly
+            let cell_count = zone.cells().count().max(1);
+            let yield_factor = zone.cells().map(|c| depletion.yield_factor(c)).sum::<f32>() / cell_count as f32;
+            self.zones.insert(zone.id, (remaining, yield_factor));
+        }
+        self.valid = true;
+    }
+}
+
+// Window listing mining zones, opened from the top bar
+fn mining_zones_window(
+    mut contexts: EguiContexts,
+    mut ui_panels: ResMut<UiPanels>,
+    mut mining_zones: ResMut<MiningZones>,
+    mut dispatcher: ResMut<MiningDispatcher>,
+    mineral_map: Res<MineralMap>,
+    depletion: Res<RegionDepletion>,
+    mut summaries: Local<ZoneSummaries>,
+) {
+    if mining_zones.is_changed() || mineral_map.is_changed() || depletion.is_changed() {
+        summaries.valid = false;
+    }
+    if ui_panels.mining_zones && !summaries.valid {
+        summaries.refresh(&mining_zones, &mineral_map, &depletion);
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Mining Zones")
+        .open(&mut ui_panels.mining_zones)
+        .default_width(260.0)
+        .show(ctx, |ui| {
+            let label = if mining_zones.designating {
+                "Designating… (Z to stop)"
+            } else {
+                "Designate Zone (Z)"
+            };
+            if ui.selectable_label(mining_zones.designating, label).clicked() {
+                mining_zones.designating = !mining_zones.designating;
+                mining_zones.drag_start = None;
+            }
+            ui.small("Drag a rectangle on the map to mark cells for the miners");
+            ui.checkbox(&mut dispatcher.show_overlay, "Show miner assignments");
+            ui.separator();
+
+            let mut remove = None;
+            for zone in &mining_zones.zones {
+                let (remaining, yield_factor) = summaries.zones.get(&zone.id).copied().unwrap_or((0, 1.0));
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Zone {}: {} cells left, {} miners, {:.0}% yield",
+                        zone.id,
+                        remaining,
+                        dispatcher.zone_load(zone.id),
+                        yield_factor * 100.0
+                    ));
+                    if ui.small_button("✖").on_hover_text("Remove zone").clicked() {
+                        remove = Some(zone.id);
+                    }
+                });
+            }
+            if let Some(id) = remove {
+                mining_zones.zones.retain(|zone| zone.id != id);
+            }
+            if mining_zones.zones.is_empty() {
+                ui.label("No zones designated");
+            }
+        });
+}
+
+// System to select equipment by clicking on them
+fn click_select_equipment(
+    pointer: MapPointer,
+    equipment_query: Query<(&Transform, &EquipmentSprite)>,
+    mut selected: ResMut<SelectedEquipment>,
+    mut equipment_actions: ResMut<EquipmentTreeActions>,
+) {
+    let MapPointer {
+        mouse_button,
+        keyboard,
+        windows,
+        camera_query,
+        map_cursor,
+        mut contexts,
+    } = pointer;
+    // Enter on the keyboard cursor selects the unit under it, keeping the
+    // selection on an empty cell so Enter can order a move there instead
+    if map_cursor.confirmed(&keyboard) {
+        if let Some(id) = unit_at(&equipment_query, map_cursor.position) {
+            selected.selected_id = Some(id);
+            equipment_actions.selected.clear();
+            equipment_actions.selected.insert(id);
+        }
+        return;
+    }
+
+    if mouse_button.just_pressed(MouseButton::Left) {
+        // Don't process clicks if hovering over UI
+        if contexts.ctx_mut().is_pointer_over_area() {
+            return;
+        }
+
+        let Ok(window) = windows.single() else {
+            return;
+        };
+        let Some(cursor_position) = window.cursor_position() else {
+            return;
+        };
+
+        // Get camera
+        let Ok((camera, camera_transform)) = camera_query.single() else {
+            return;
+        };
+
+        // Convert screen position to world position
+        let Ok(world_position) = camera
+            .viewport_to_world_2d(camera_transform, cursor_position)
+        else {
+            return;
+        };
+
+        // Check if we clicked on any equipment
+        let clicked_id = unit_at(&equipment_query, world_position);
+
+        // Update selection in both resources
+        selected.selected_id = clicked_id;
+
+        // Clear previous selection and set new one in equipment_actions
+        equipment_actions.selected.clear();
+        if let Some(id) = clicked_id {
+            equipment_actions.selected.insert(id);
+        }
+    }
+}
+
+/// The unit whose sprite is under a world position, if any
+fn unit_at(equipment_query: &Query<(&Transform, &EquipmentSprite)>, world_position: Vec2) -> Option<usize> {
+    let sprite_size = 64.0; // Equipment sprite click radius (increased for easier clicking)
+    equipment_query
+        .iter()
+        .find(|(transform, _)| world_position.distance(transform.translation.truncate()) < sprite_size)
+        .map(|(_, equipment_sprite)| equipment_sprite.equipment_id)
+}
+
+/// Panel windows on screen that can take keyboard focus, left to right
+fn focusable_windows(ctx: &egui::Context) -> Vec<egui::LayerId> {
+    let mut windows: Vec<(egui::LayerId, egui::Rect)> = ctx
+        .memory(|memory| memory.areas().visible_layer_ids())
+        .into_iter()
+        .filter(|layer| layer.order == egui::Order::Middle)
+        .filter_map(|layer| {
+            let area = egui::AreaState::load(ctx, layer.id).filter(|area| area.interactable)?;
+            Some((layer, area.rect()))
+        })
+        .collect();
+    windows.sort_by(|(_, a), (_, b)| a.left().total_cmp(&b.left()).then(a.top().total_cmp(&b.top())));
+    windows.into_iter().map(|(layer, _)| layer).collect()
+}
+
+/// Layer of the widget holding keyboard focus
+fn focused_layer(ctx: &egui::Context) -> Option<egui::LayerId> {
+    let id = ctx.memory(|memory| memory.focused())?;
+    ctx.read_response(id).map(|response| response.layer_id)
+}
+
+// System for keyboard focus: F6 (Shift+F6 backward) steps from the map
+// cursor through each panel window, then to all panels. Arrow keys move the
+// cursor (held keys repeat, Shift moves faster) and the camera follows it off
+// screen.
+fn map_cursor_system(
+    time: Res<Time<Real>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut map_cursor: ResMut<MapCursor>,
+    mineral_map: Res<MineralMap>,
+    mut camera_query: Query<(&Camera, &GlobalTransform, &mut Transform)>,
+    mut contexts: EguiContexts,
+    mut gizmos: Gizmos,
+) {
+    let Ok((camera, camera_global, mut camera_transform)) = camera_query.single_mut() else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+
+    // Typing into a panel hands focus back to the panels
+    if map_cursor.active && ctx.wants_keyboard_input() {
+        map_cursor.active = false;
+    }
+    let shift = keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight);
+    if keyboard.just_pressed(KeyCode::F6) {
+        // Stops: the map, each window, then all panels
+        let windows = focusable_windows(ctx);
+        let stops = windows.len() + 2;
+        let current = if map_cursor.active {
+            0
+        } else {
+            focused_layer(ctx)
+                .and_then(|layer| windows.iter().position(|&window| window == layer))
+                .map_or(stops - 1, |index| index + 1)
+        };
+        let next = if shift { (current + stops - 1) % stops } else { (current + 1) % stops };
+
+        ctx.memory_mut(|memory| {
+            if let Some(id) = memory.focused() {
+                memory.surrender_focus(id);
+            }
+        });
+        map_cursor.active = next == 0;
+        map_cursor.window = windows.get(next.wrapping_sub(1)).copied();
+        if let Some(window) = map_cursor.window {
+            // Focus the window's frame so Tab steps into its controls
+            ctx.move_to_top(window);
+            ctx.memory_mut(|memory| memory.request_focus(window.id.with("move")));
+        }
+        if map_cursor.active {
+            // Start at the view's center
+            let center = camera_transform.translation.truncate();
+            map_cursor.cell = mineral_map
+                .world_to_cell(center)
+                .unwrap_or(UVec2::new(mineral_map.width as u32 / 2, mineral_map.height as u32 / 2));
+        }
+    }
+    // Outline the focused window until focus leaves it
+    if let Some(window) = map_cursor.window {
+        match ctx.memory(|memory| memory.area_rect(window.id)) {
+            Some(rect) if focused_layer(ctx) == Some(window) => {
+                let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("window_focus")));
+                painter.rect_stroke(
+                    rect.expand(2.0),
+                    4.0,
+                    egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 230, 50)),
+                    egui::StrokeKind::Outside,
+                );
+                egui::Area::new(egui::Id::new("window_focus_hints"))
+                    .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -40.0))
+                    .interactable(false)
+                    .show(ctx, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label("Tab / Shift+Tab: Controls  |  Space / Enter: Activate  |  F6: Next Panel  |  Esc: Leave Panel");
+                        });
+                    });
+            }
+            _ => map_cursor.window = None,
+        }
+    }
+    if !map_cursor.active {
+        return;
+    }
+
+    let arrows = [
+        (KeyCode::ArrowLeft, IVec2::NEG_X),
+        (KeyCode::ArrowRight, IVec2::X),
+        (KeyCode::ArrowUp, IVec2::NEG_Y), // Cell rows count downward
+        (KeyCode::ArrowDown, IVec2::Y),
+    ];
+    let held: IVec2 = arrows
+        .iter()
+        .filter(|(key, _)| keyboard.pressed(*key))
+        .map(|(_, direction)| *direction)
+        .sum();
+    let pressed = arrows.iter().any(|(key, _)| keyboard.just_pressed(*key));
+
+    let mut step = pressed;
+    if held == IVec2::ZERO || pressed {
+        map_cursor.held = 0.0;
+    } else {
+        let before = map_cursor.held;
+        map_cursor.held += time.delta_secs();
+        let repeats = |held: f32| ((held - MapCursor::REPEAT_DELAY) / MapCursor::REPEAT_INTERVAL).floor();
+        step = map_cursor.held > MapCursor::REPEAT_DELAY && repeats(map_cursor.held) > repeats(before);
+    }
+
+    if step {
+        let fast = keyboard.pressed(KeyCode::ShiftLeft) || keyboard.pressed(KeyCode::ShiftRight);
+        let distance = if fast { MapCursor::FAST_STEP } else { MapCursor::STEP };
+        let max = IVec2::new(mineral_map.width as i32 - 1, mineral_map.height as i32 - 1);
+        map_cursor.cell = (map_cursor.cell.as_ivec2() + held * distance).clamp(IVec2::ZERO, max).as_uvec2();
+    }
+
+    map_cursor.position = mineral_map.cell_to_world(map_cursor.cell);
+    if !is_on_screen(camera, camera_global, map_cursor.position) {
+        camera_transform.translation.x = map_cursor.position.x;
+        camera_transform.translation.y = map_cursor.position.y;
+    }
+
+    gizmos.rect_2d(
+        Isometry2d::from_translation(map_cursor.position),
+        Vec2::splat(MAP_SCALE * 6.0),
+        Color::srgb(1.0, 0.9, 0.2),
+    );
+
+    // On-screen key hints while the cursor has focus
+    egui::Area::new(egui::Id::new("map_cursor_hints"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -40.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!(
+                    "Cell ({}, {})  |  Arrows: Move (Shift: Fast)  |  Enter: Select / Move Selected Here / Use Tool (hold to drag)",
+                    map_cursor.cell.x, map_cursor.cell.y
+                ));
+                ui.label(
+                    "Insert / Delete: Add / Remove Waypoint  |  P: Place Tree Selection  |  M: Dig  |  I + Enter: Inspect  |  F6: Next Panel",
+                );
+            });
+        });
+}
+
+// System to move selected equipment with arrow keys
+fn move_selected_equipment(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    selected: Res<SelectedEquipment>,
+    map_cursor: Res<MapCursor>,
+    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+    mut contexts: EguiContexts,
+) {
+    let Some(selected_id) = selected.selected_id else {
+        return;
+    };
+    // Arrow keys drive the keyboard cursor while it has focus
+    if map_cursor.active || contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+
+    let move_speed = 200.0 * time.delta_secs();
+
+    for (mut transform, equipment_sprite) in &mut sprite_query {
+        if equipment_sprite.equipment_id == selected_id {
+            // Move with arrow keys
+            if keyboard.pressed(KeyCode::ArrowUp) {
+                transform.translation.y += move_speed;
+            }
+            if keyboard.pressed(KeyCode::ArrowDown) {
+                transform.translation.y -= move_speed;
+            }
+            if keyboard.pressed(KeyCode::ArrowLeft) {
+                transform.translation.x -= move_speed;
+            }
+            if keyboard.pressed(KeyCode::ArrowRight) {
+                transform.translation.x += move_speed;
+            }
+            break;
+        }
+    }
+}
+
+// What the status bar summarizes
+#[derive(SystemParam)]
+struct StatusSources<'w> {
+    equipment_state: Res<'w, EquipmentTreeState>,
+    stockpile: Res<'w, Stockpile>,
+    event_log: Res<'w, EventLog>,
+    diagnostics: Res<'w, DiagnosticsStore>,
+    graphics_settings: Res<'w, GraphicsSettings>,
+}
+
+// Bottom status bar with a live summary; runs before ui_system so the bar spans the full width
+fn status_bar_system(
+    mut contexts: EguiContexts,
+    status: StatusSources,
+    time: Res<Time>,
+    mut ui_panels: ResMut<UiPanels>,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    let StatusSources {
+        equipment_state,
+        stockpile,
+        event_log,
+        diagnostics,
+        graphics_settings,
+    } = status;
+    let ctx = contexts.ctx_mut();
+
+    egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            let (working, idle, disabled) = equipment_state.equipment_counts();
+            let mut units = format!("Units: {} working / {} idle", working, idle);
+            if disabled > 0 {
+                units = format!("{} / {} disabled", units, disabled);
+            }
+            if ui
+                .link(units)
+                .on_hover_text("Show or hide the equipment panel")
+                .clicked()
+            {
+                ui_panels.equipment = !ui_panels.equipment;
+            }
+
+            ui.separator();
+            let top = stockpile.top(3);
+            if top.is_empty() {
+                ui.label("Stockpile: empty");
+            } else {
+                let summary: Vec<String> = top
+                    .iter()
+                    .map(|(mineral, amount)| format!("{} {:.0}", mineral.name(), amount))
+                    .collect();
+                if ui
+                    .link(format!("Stockpile: {}", summary.join(", ")))
+                    .on_hover_text("Open mining zones")
+                    .clicked()
+                {
+                    ui_panels.mining_zones = true;
+                }
+            }
+
+            ui.separator();
+            let fps = diagnostics
+                .get(&FrameTimeDiagnosticsPlugin::FPS)
+                .and_then(|fps| fps.smoothed());
+            let fps = fps.map_or_else(|| "--".to_string(), |fps| format!("{:.0}", fps));
+            match graphics_settings.frame_cap {
+                Some(cap) => ui.label(format!("FPS: {} / {}", fps, cap)),
+                None => ui.label(format!("FPS: {}", fps)),
+            };
+
+            ui.separator();
+            let latest = event_log
+                .latest()
+                .map(|entry| format!("[{}] {}", format_clock(entry.time), entry.message))
+                .unwrap_or_else(|| "Status: Ready".to_string());
+            if ui.link(latest).on_hover_text("Open the event log").clicked() {
+                ui_panels.event_log = true;
+            }
+        });
+    });
+
+    egui::Window::new("Event Log")
+        .open(&mut ui_panels.event_log)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for entry in event_log.entries.iter().rev() {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("[{}] {}", format_clock(entry.time), entry.message));
+                        if let Some(position) = entry.position {
+                            if ui.small_button("Go").on_hover_text("Center the camera here").clicked() {
+                                game_commands.write(GameCommand::CenterOn(position));
+                            }
+                        }
+                    });
+                }
+                if event_log.entries.is_empty() {
+                    ui.label("No events yet");
+                }
+            });
+            ui.small(format!("Session time: {}", format_clock(time.elapsed_secs())));
+        });
+}
+
+// History buffers the debug panel reports on and can trim
+#[derive(SystemParam)]
+struct HistoryBuffers<'w> {
+    event_log: ResMut<'w, EventLog>,
+    cell_history: ResMut<'w, CellHistory>,
+}
+
+// Debug window (F3) with memory estimates and a compact command
+fn debug_panel_system(
+    mut contexts: EguiContexts,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut ui_panels: ResMut<UiPanels>,
+    mineral_map: Res<MineralMap>,
+    histories: HistoryBuffers,
+    images: Res<Assets<Image>>,
+    entities: Query<Entity>,
+) {
+    let HistoryBuffers {
+        mut event_log,
+        mut cell_history,
+    } = histories;
+    if keyboard.just_pressed(KeyCode::F3) {
+        ui_panels.debug = !ui_panels.debug;
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("Debug")
+        .open(&mut ui_panels.debug)
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            let texture_bytes: usize = images
+                .iter()
+                .filter_map(|(_, image)| image.data.as_ref())
+                .map(|data| data.capacity())
+                .sum();
+            let log_bytes = event_log.entries.capacity() * std::mem::size_of::<LogEntry>()
+                + event_log.entries.iter().map(|e| e.message.capacity()).sum::<usize>();
+            let history_bytes = cell_history.events.capacity() * std::mem::size_of::<CellEvent>();
+
+            egui::Grid::new("debug_memory").num_columns(2).show(ui, |ui| {
+                ui.label("Mineral map");
+                ui.label(format_bytes(mineral_map.memory_usage()));
+                ui.end_row();
+
+                ui.label(format!("Textures ({})", images.len()));
+                ui.label(format_bytes(texture_bytes));
+                ui.end_row();
+
+                ui.label(format!("Event log ({})", event_log.entries.len()));
+                ui.label(format_bytes(log_bytes));
+                ui.end_row();
+
+                ui.label(format!("Cell history ({})", cell_history.events.len()));
+                ui.label(format_bytes(history_bytes));
+                ui.end_row();
+
+                ui.label("Entities");
+                ui.label(entities.iter().count().to_string());
+                ui.end_row();
+
+                ui.label("World seed");
+                ui.label(mineral_map.seed.to_string());
+                ui.end_row();
+
+                ui.label("Symmetry");
+                ui.label(mineral_map.symmetry.name());
+                ui.end_row();
+
+                ui.label("Preset");
+                ui.label(mineral_map.preset.name());
+                ui.end_row();
+            });
+
+            ui.separator();
+            if ui
+                .button("Compact")
+                .on_hover_text("Trim the event log and cell history and release their spare capacity")
+                .clicked()
+            {
+                event_log.compact(50);
+                cell_history.compact(1_000);
+            }
+        });
+}
+
+// Ctrl+P command palette listing every GameCommand, filtered by fuzzy search
+fn command_palette_system(
+    mut contexts: EguiContexts,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut palette: ResMut<CommandPalette>,
+    mut commands: EventWriter<GameCommand>,
+) {
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::SuperLeft, KeyCode::SuperRight]);
+    if ctrl && keyboard.just_pressed(KeyCode::KeyP) {
+        palette.open = !palette.open;
+        palette.query.clear();
+        palette.highlighted = 0;
+    }
+    if !palette.open {
+        return;
+    }
+
+    let mut matches: Vec<(i32, GameCommand)> = GameCommand::palette_entries()
+        .into_iter()
+        .filter_map(|command| fuzzy_score(&palette.query, &command.label()).map(|score| (score, command)))
+        .collect();
+    // Stable sort keeps the palette order among equal scores
+    matches.sort_by_key(|(score, _)| -score);
+
+    let ctx = contexts.ctx_mut();
+    let (up, down, enter, escape) = ctx.input(|i| {
+        (
+            i.key_pressed(egui::Key::ArrowUp),
+            i.key_pressed(egui::Key::ArrowDown),
+            i.key_pressed(egui::Key::Enter),
+            i.key_pressed(egui::Key::Escape),
+        )
+    });
+    if down {
+        palette.highlighted += 1;
+    }
+    if up {
+        palette.highlighted = palette.highlighted.saturating_sub(1);
+    }
+    palette.highlighted = palette.highlighted.min(matches.len().saturating_sub(1));
+
+    let mut chosen = None;
+    egui::Window::new("Command Palette")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+        .fixed_size(egui::vec2(360.0, 0.0))
+        .show(ctx, |ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut palette.query)
+                    .hint_text("Type a command…")
+                    .desired_width(f32::INFINITY),
+            );
+            response.request_focus();
+            if response.changed() {
+                palette.highlighted = 0;
+            }
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                for (i, (_, command)) in matches.iter().enumerate() {
+                    let highlighted = i == palette.highlighted;
+                    let row = ui.selectable_label(highlighted, command.label());
+                    if highlighted {
+                        row.scroll_to_me(None);
+                    }
+                    if row.clicked() {
+                        chosen = Some(*command);
+                    }
+                }
+                if matches.is_empty() {
+                    ui.label("No matching commands");
+                }
+            });
+        });
+
+    if enter {
+        chosen = chosen.or_else(|| matches.get(palette.highlighted).map(|(_, command)| *command));
+    }
+    if let Some(command) = chosen {
+        commands.write(command);
+    }
+    if chosen.is_some() || escape {
+        palette.open = false;
+    }
+}
+
+// The camera, selection and history that navigation commands move through
+#[derive(SystemParam)]
+struct Navigation<'w, 's> {
+    camera_query: Query<'w, 's, &'static mut Transform, With<Camera>>,
+    selected: ResMut<'w, SelectedEquipment>,
+    history: ResMut<'w, NavigationHistory>,
+    event_log: Res<'w, EventLog>,
+}
+
+// Panels, overlays and play modes that commands toggle
+#[derive(SystemParam)]
+struct GameModes<'w> {
+    ui_panels: ResMut<'w, UiPanels>,
+    dispatcher: ResMut<'w, MiningDispatcher>,
+    mining_zones: ResMut<'w, MiningZones>,
+    accessibility_settings: ResMut<'w, AccessibilitySettings>,
+    planning: ResMut<'w, PlanningMode>,
+    spectator: ResMut<'w, SpectatorMode>,
+}
+
+// System executing GameCommands from menus, hotkeys and the command palette
+fn apply_game_commands(
+    mut command_events: EventReader<GameCommand>,
+    mut equipment_state: ResMut<EquipmentTreeState>,
+    mut spawn_settings: ResMut<SpawnSettings>,
+    mut placement: ResMut<PlacementMode>,
+    navigation: Navigation,
+    modes: GameModes,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let Navigation {
+        mut camera_query,
+        mut selected,
+        mut history,
+        event_log,
+    } = navigation;
+    let GameModes {
+        mut ui_panels,
+        mut dispatcher,
+        mut mining_zones,
+        mut accessibility_settings,
+        mut planning,
+        mut spectator,
+    } = modes;
+    let Ok(mut camera_transform) = camera_query.single_mut() else {
+        return;
+    };
+
+    for command in command_events.read() {
+        let mut jump_to = None;
+        let here = NavigationEntry {
+            selection: selected.selected_id,
+            camera: camera_transform.translation.truncate(),
+        };
+        match *command {
+            GameCommand::Spawn(equipment_type, policy) => {
+                let id = equipment_state.next_id;
+                equipment_state.add_equipment(
+                    format!("{} {}", equipment_type.unit_name(), id),
+                    equipment_type,
+                );
+
+                let policy = policy.unwrap_or(spawn_settings.policy);
+                if policy == SpawnPolicy::Placement {
+                    placement.pending = Some(id);
+                } else if let Some(position) =
+                    spawn_settings.spawn_position(policy, Some(camera_transform.translation.truncate()))
+                {
+                    if let Some(node) = equipment_state.find_node_mut(id) {
+                        node.position = Some(position);
+                    }
+                }
+            }
+            GameCommand::TogglePanel(panel) => ui_panels.toggle(panel),
+            GameCommand::ToggleMiningOverlay => dispatcher.show_overlay = !dispatcher.show_overlay,
+            GameCommand::ToggleZoneDesignation => {
+                mining_zones.designating = !mining_zones.designating;
+                mining_zones.drag_start = None;
+            }
+            GameCommand::ToggleHighContrast => {
+                accessibility_settings.high_contrast = !accessibility_settings.high_contrast;
+            }
+            GameCommand::JumpToHomeBase => jump_to = spawn_settings.home_base,
+            GameCommand::JumpToSelected => {
+                jump_to = selected
+                    .selected_id
+                    .and_then(|id| equipment_state.find_node(id))
+                    .and_then(|node| node.position);
+            }
+            GameCommand::JumpToLatestEvent => {
+                jump_to = event_log.entries.iter().rev().find_map(|entry| entry.position);
+            }
+            GameCommand::TogglePause => {
+                if virtual_time.is_paused() {
+                    virtual_time.unpause();
+                } else {
+                    virtual_time.pause();
+                }
+            }
+            GameCommand::SetSpeed(speed) => virtual_time.set_relative_speed(speed),
+            GameCommand::CenterOn(position) => jump_to = Some(position),
+            GameCommand::NavigateBack => {
+                if let Some(entry) = history.entries.pop() {
+                    // Skip units deleted since the entry was recorded
+                    let selection = entry.selection.filter(|&id| equipment_state.find_node(id).is_some());
+                    selected.selected_id = selection;
+                    history.current_selection = selection;
+                    camera_transform.translation.x = entry.camera.x;
+                    camera_transform.translation.y = entry.camera.y;
+                }
+            }
+            GameCommand::TogglePlanning => {
+                if planning.active {
+                    planning.discard();
+                } else {
+                    planning.active = true;
+                }
+            }
+            GameCommand::NewWorld(..) | GameCommand::LoadMapImage => {} // Handled by regenerate_world
+            GameCommand::ToggleTemperatureOverlay => {} // Handled by toggle_temperature_overlay
+            GameCommand::ToggleSpectator => {
+                spectator.active = !spectator.active;
+                spectator.target = None;
+            }
+        }
+
+        if let Some(position) = jump_to {
+            history.push(here);
+            camera_transform.translation.x = position.x;
+            camera_transform.translation.y = position.y;
+        }
+    }
+}
+
+// Where new worlds come from: the seed settings, the registered
+// generators, and the daily challenge that locks the map
+#[derive(SystemParam)]
+struct WorldSource<'w> {
+    world_seed: ResMut<'w, WorldSeed>,
+    generators: Res<'w, GeneratorRegistry>,
+    daily_challenge: Option<Res<'w, DailyChallenge>>,
+}
+
+// Everything drawn or computed from the mineral map, rebuilt with it
+#[derive(SystemParam)]
+struct MapViews<'w, 's> {
+    world_report: ResMut<'w, WorldReport>,
+    images: ResMut<'w, Assets<Image>>,
+    map_query: Query<'w, 's, &'static mut Sprite, With<MineralMapRenderer>>,
+    overlay_query: Query<
+        'w,
+        's,
+        &'static mut Sprite,
+        (With<TemperatureOverlayRenderer>, Without<MineralMapRenderer>),
+    >,
+}
+
+impl MapViews<'_, '_> {
+    fn rebuild(&mut self, mineral_map: &MineralMap) {
+        *self.world_report = WorldReport::new(mineral_map);
+        if let Ok(mut map_sprite) = self.map_query.single_mut() {
+            self.images.remove(&map_sprite.image);
+            map_sprite.image = self.images.add(mineral_map.to_image());
+        }
+        if let Ok(mut overlay_sprite) = self.overlay_query.single_mut() {
+            self.images.remove(&overlay_sprite.image);
+            overlay_sprite.image = self.images.add(mineral_map.temperature_image());
+        }
+    }
+}
+
+// Mining state tied to the terrain, which no longer holds on a new map
+#[derive(SystemParam)]
+struct TerrainState<'w> {
+    dispatcher: ResMut<'w, MiningDispatcher>,
+    mining_zones: ResMut<'w, MiningZones>,
+    depletion: ResMut<'w, RegionDepletion>,
+    cell_history: ResMut<'w, CellHistory>,
+    planning: ResMut<'w, PlanningMode>,
+    lasers: ResMut<'w, LaserDrills>,
+    drainage: ResMut<'w, DrainagePreview>,
+}
+
+impl TerrainState<'_> {
+    fn reset(&mut self, mineral_map: &MineralMap) {
+        // Zones that no longer fit on a smaller map are dropped
+        self.mining_zones
+            .zones
+            .retain(|zone| (zone.max.x as usize) < mineral_map.width && (zone.max.y as usize) < mineral_map.height);
+        self.dispatcher.clear();
+        self.depletion.extracted.clear();
+        self.cell_history.events.clear();
+        self.cell_history.inspected = None;
+        self.planning.digs.clear();
+        // Beams run along channels cut through the old terrain, so drills start cold
+        *self.lasers = LaserDrills::default();
+        // A drainage preview simulated on the old terrain, or dragged over it
+        self.drainage.drag_start = None;
+        self.drainage.source = None;
+        self.drainage.flow = None;
+    }
+}
+
+// System to regenerate the mineral map from a seed at runtime. Units and
+// zones are kept; mining state tied to the old terrain is cleared.
+fn regenerate_world(
+    mut command_events: EventReader<GameCommand>,
+    time: Res<Time>,
+    source: WorldSource,
+    mut mineral_map: ResMut<MineralMap>,
+    mut views: MapViews,
+    mut terrain: TerrainState,
+    mut event_log: ResMut<EventLog>,
+) {
+    let WorldSource {
+        mut world_seed,
+        generators,
+        daily_challenge,
+    } = source;
+    let Some(command) = command_events.read().fold(None, |last, command| match *command {
+        GameCommand::NewWorld(..) | GameCommand::LoadMapImage => Some(*command),
+        _ => last,
+    }) else {
+        return;
+    };
+    // The daily challenge map is shared by everyone, so it can't be swapped out
+    if daily_challenge.is_some() {
+        event_log.push(time.elapsed_secs(), "New worlds are disabled during the daily challenge", None);
+        return;
+    }
+
+    let message = match command {
+        GameCommand::NewWorld(seed, symmetry, preset, size) => {
+            *mineral_map = generate_map(&generators, &world_seed.generator, seed, symmetry, preset, size);
+            world_seed.size = size;
+            format!(
+                "New world generated (seed {}, symmetry {}, preset {}, {}x{}, {} generator)",
+                seed,
+                symmetry.name(),
+                preset.name(),
+                size,
+                size,
+                world_seed.generator
+            )
+        }
+        _ => {
+            let path = world_seed.image_path.trim().to_string();
+            let loaded = std::fs::read(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|bytes| MineralMap::from_png(&bytes));
+            match loaded {
+                Ok(map) => *mineral_map = map,
+                Err(err) => {
+                    event_log.push(time.elapsed_secs(), format!("Could not load {}: {}", path, err), None);
+                    return;
+                }
+            }
+            format!("Map loaded from {} ({}x{})", path, mineral_map.width, mineral_map.height)
+        }
+    };
+    world_seed.seed = mineral_map.seed;
+    world_seed.symmetry = mineral_map.symmetry;
+    world_seed.preset = mineral_map.preset;
+    views.rebuild(&mineral_map);
+    terrain.reset(&mineral_map);
+
+    event_log.push(time.elapsed_secs(), message, None);
+}
+
+// Window showing the mineral balance of the current map as it was generated
+fn world_report_window(
+    mut contexts: EguiContexts,
+    mut ui_panels: ResMut<UiPanels>,
+    world_report: Res<WorldReport>,
+    mineral_map: Res<MineralMap>,
+) {
+    let ctx = contexts.ctx_mut();
+    egui::Window::new("World Report")
+        .open(&mut ui_panels.world_report)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Seed {}, {}x{}, {}",
+                mineral_map.seed,
+                mineral_map.width,
+                mineral_map.height,
+                mineral_map.preset.name()
+            ));
+            egui::Grid::new("world_report_totals").num_columns(2).show(ui, |ui| {
+                ui.label("Total value");
+                ui.label(format!("{:.0}", world_report.total_value));
+                ui.end_row();
+
+                ui.label("Void");
+                ui.label(format!("{:.1}%", world_report.void_fraction * 100.0));
+                ui.end_row();
+            });
+
+            egui::CollapsingHeader::new("Minerals").default_open(true).show(ui, |ui| {
+                let total = (mineral_map.width * mineral_map.height).max(1) as f32;
+                egui::Grid::new("world_report_minerals")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Mineral");
+                        ui.strong("Cells");
+                        ui.strong("Share");
+                        ui.strong("Value");
+                        ui.strong("Largest vein");
+                        ui.end_row();
+
+                        for report in &world_report.minerals {
+                            ui.label(report.mineral.name());
+                            ui.label(report.cells.to_string());
+                            ui.label(format!("{:.1}%", report.cells as f32 / total * 100.0));
+                            ui.label(format!("{:.0}", report.value));
+                            ui.label(report.largest_vein.to_string());
+                            ui.end_row();
+                        }
+                    });
+            });
+            ui.small("Computed when the map is generated; mining doesn't change it");
+        });
+}
+
+// System to record the previous selection and camera whenever the selection changes
+fn record_selection_history(
+    selected: Res<SelectedEquipment>,
+    mut history: ResMut<NavigationHistory>,
+    camera_query: Query<&Transform, With<Camera>>,
+) {
+    if selected.selected_id == history.current_selection {
+        return;
+    }
+    let Ok(camera_transform) = camera_query.single() else {
+        return;
+    };
+
+    let entry = NavigationEntry {
+        selection: history.current_selection,
+        camera: camera_transform.translation.truncate(),
+    };
+    history.push(entry);
+    history.current_selection = selected.selected_id;
+}
+
+// System to show or hide the temperature overlay
+fn toggle_temperature_overlay(
+    mut command_events: EventReader<GameCommand>,
+    mut overlay_query: Query<&mut Visibility, With<TemperatureOverlayRenderer>>,
+) {
+    for command in command_events.read() {
+        if *command != GameCommand::ToggleTemperatureOverlay {
+            continue;
+        }
+        for mut visibility in &mut overlay_query {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
+
+// System mapping navigation and overlay hotkeys to GameCommands
+fn navigation_hotkeys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut game_commands: EventWriter<GameCommand>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Backspace) {
+        game_commands.write(GameCommand::NavigateBack);
+    }
+    if keyboard.just_pressed(KeyCode::KeyJ) {
+        game_commands.write(GameCommand::JumpToLatestEvent);
+    }
+    if keyboard.just_pressed(KeyCode::KeyT) {
+        game_commands.write(GameCommand::ToggleTemperatureOverlay);
+    }
+}
+
+// Crash reports are written here, one directory per crash
+const CRASH_REPORT_DIR: &str = "crash_reports";
+// Marker file pointing at a report not yet shown to the player
+const CRASH_PENDING_FILE: &str = "PENDING";
+const CRASH_LOG_LINES: usize = 50;
+
+// Game state captured for crash reports, kept outside the ECS so the
+// panic hook can read it without access to the world
+struct CrashContext {
+    seed: u32,
+    elapsed: f32,
+    recent_events: Vec<String>,
+    events_seen: usize,
+}
+
+static CRASH_CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    seed: 0,
+    elapsed: 0.0,
+    recent_events: Vec::new(),
+    events_seen: 0,
+});
+
+// Resource holding the report left by the previous session's crash, if any
+#[derive(Resource)]
+struct PendingCrashReport(Option<PathBuf>);
+
+/// Install a panic hook that writes a bug-report directory before the
+/// default hook prints the panic
+fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_crash_report(info) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(err) => eprintln!("Failed to write crash report: {err}"),
+        }
+        default_hook(info);
+    }));
+}
+
+fn write_crash_report(info: &std::panic::PanicHookInfo) -> std::io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let dir = Path::new(CRASH_REPORT_DIR).join(format!("crash-{timestamp}"));
+    std::fs::create_dir_all(&dir)?;
+
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}", l.file(), l.line()))
+        .unwrap_or_default();
+
+    let mut report = format!(
+        "Regolith Voxel {}\nOS: {} ({})\nCPUs: {}\n\nPanic: {message}\nAt: {location}\nThread: {}\n\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::thread::available_parallelism().map_or(0, |n| n.get()),
+        std::thread::current().name().unwrap_or("unnamed"),
+        std::backtrace::Backtrace::force_capture(),
+    );
+
+    // The panic may have happened while the context was locked
+    let context = CRASH_CONTEXT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    report.push_str(&format!(
+        "World seed: {}\nSession time: {}\n\nRecent events:\n",
+        context.seed,
+        format_clock(context.elapsed)
+    ));
+    for line in &context.recent_events {
+        report.push_str(line);
+        report.push('\n');
+    }
+    drop(context);
+
+    std::fs::write(dir.join("report.txt"), report)?;
+    std::fs::write(
+        Path::new(CRASH_REPORT_DIR).join(CRASH_PENDING_FILE),
+        dir.to_string_lossy().as_bytes(),
+    )?;
+    Ok(dir)
+}
+
+/// Consume the marker left by a crash in the previous session
+fn take_pending_crash_report() -> Option<PathBuf> {
+    let marker = Path::new(CRASH_REPORT_DIR).join(CRASH_PENDING_FILE);
+    let path = std::fs::read_to_string(&marker).ok()?;
+    let _ = std::fs::remove_file(&marker);
+    Some(PathBuf::from(path.trim()))
+}
+
+// System to keep the crash context in sync with the game
+fn update_crash_context(time: Res<Time>, mineral_map: Res<MineralMap>, event_log: Res<EventLog>) {
+    let Ok(mut context) = CRASH_CONTEXT.lock() else {
+        return;
+    };
+    context.seed = mineral_map.seed;
+    context.elapsed = time.elapsed_secs();
+
+    if context.events_seen == event_log.total_pushed {
+        return;
+    }
+    let new_entries = event_log.since(context.events_seen);
+    context.events_seen = event_log.total_pushed;
+    context.recent_events.extend(
+        new_entries.iter().map(|entry| format!("[{}] {}", format_clock(entry.time), entry.message)),
+    );
+    let excess = context.recent_events.len().saturating_sub(CRASH_LOG_LINES);
+    context.recent_events.drain(..excess);
+}
+
+// Daily challenge scores are appended here as `date,seed,score` lines
+const DAILY_SCORES_FILE: &str = "daily_scores.csv";
+const DAILY_CHALLENGE_SECONDS: f32 = 600.0; // Game time allowed for a daily challenge
+const DAILY_HISTORY_LINES: usize = 10;
+
+// Resource present when playing the daily challenge: everyone gets the same
+// map and starting units on a given day, and the score is the stockpile
+// collected within the time limit
+#[derive(Resource)]
+struct DailyChallenge {
+    day: u64,
+    seed: u32,
+    final_score: Option<u32>,
+    history: Vec<String>, // Recent lines of the score file, newest first
+}
+
+impl DailyChallenge {
+    fn today() -> Self {
+        let day = current_day();
+        Self {
+            day,
+            seed: daily_seed(day),
+            final_score: None,
+            history: Vec::new(),
+        }
+    }
+
+    fn score(stockpile: &Stockpile) -> u32 {
+        (stockpile.amounts.values().sum::<f32>() * 10.0).round() as u32
+    }
+
+    /// Append the score to the history file and reload the recent history
+    fn record_score(&mut self, score: u32) -> std::io::Result<()> {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(DAILY_SCORES_FILE)?;
+        writeln!(file, "{},{},{}", format_day(self.day), self.seed, score)?;
+
+        let contents = std::fs::read_to_string(DAILY_SCORES_FILE)?;
+        self.history = contents
+            .lines()
+            .rev()
+            .take(DAILY_HISTORY_LINES)
+            .map(|line| line.replace(',', "  "))
+            .collect();
+        Ok(())
+    }
+}
+
+// Place the starting units at fixed positions so the scenario is the same for everyone
+fn setup_daily_challenge(mut equipment_state: ResMut<EquipmentTreeState>) {
+    let ids = equipment_state.all_ids();
+    let mut slot = 0.0;
+    for id in ids {
+        if let Some(node) = equipment_state.find_node_mut(id).filter(|node| !node.is_container()) {
+            node.position = Some(Vec2::new(slot * FORMATION_SPACING, 0.0));
+            slot += 1.0;
+        }
+    }
+}
+
+// Countdown and score for the daily challenge; when time runs out the game
+// pauses and the score is added to the local history
+fn daily_challenge_system(
+    mut contexts: EguiContexts,
+    time: Res<Time>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut challenge: ResMut<DailyChallenge>,
+    stockpile: Res<Stockpile>,
+) {
+    let remaining = (DAILY_CHALLENGE_SECONDS - time.elapsed_secs()).max(0.0);
+    let score = challenge.final_score.unwrap_or_else(|| DailyChallenge::score(&stockpile));
+
+    if challenge.final_score.is_none() && remaining <= 0.0 {
+        virtual_time.pause();
+        challenge.final_score = Some(score);
+        if let Err(err) = challenge.record_score(score) {
+            warn!("Could not save the daily challenge score: {}", err);
+        }
+    }
+
+    let ctx = contexts.ctx_mut();
+    egui::Area::new(egui::Id::new("daily_challenge"))
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-12.0, 40.0))
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!("Daily challenge {}", format_day(challenge.day)));
+                ui.label(format!("Time left: {}", format_clock(remaining)));
+                ui.strong(format!("Score: {}", score));
+            });
+        });
+
+    if challenge.final_score.is_some() {
+        egui::Window::new("Daily Challenge Complete")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.heading(format!("Score: {}", score));
+                ui.label(format!("Seed {} — same map for everyone today", challenge.seed));
+                ui.separator();
+                ui.label("Recent scores (date, seed, score):");
+                for line in &challenge.history {
+                    ui.monospace(line);
                 }
+            });
+    }
+}
+
+// Start the endless soundscape; its layers are silent until the first update
+fn start_ambience(mut commands: Commands, ambience: Res<Ambience>) {
+    commands.spawn((AudioPlayer(ambience.sound.clone()), PlaybackSettings::LOOP));
+}
+
+// System to set the soundscape from what is on screen, once a second: wind
+// over the regolith plains, rumble from busy units, and drips in the
+// cavities of the ice fields
+fn ambient_soundscape_system(
+    time: Res<Time<Real>>,
+    settings: Res<AmbientSettings>,
+    mut ambience: ResMut<Ambience>,
+    mineral_map: Res<MineralMap>,
+    equipment_state: Res<EquipmentTreeState>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    ambience
+        .levels
+        .set_volume(if settings.enabled { settings.volume } else { 0.0 });
+    let now = time.elapsed_secs();
+    if now < ambience.next_update {
+        return;
+    }
+    ambience.next_update = now + AMBIENT_UPDATE_SECONDS;
+
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    let Some(size) = camera.logical_viewport_size() else {
+        return;
+    };
+    let (Ok(a), Ok(b)) = (
+        camera.viewport_to_world_2d(camera_transform, Vec2::ZERO),
+        camera.viewport_to_world_2d(camera_transform, size),
+    ) else {
+        return;
+    };
+    let view = Rect::from_corners(a, b);
+
+    // Sample the visible part of the map on a coarse grid
+    let (mut samples, mut dunes, mut cavities) = (0, 0, 0);
+    for i in 0..AMBIENT_SAMPLES {
+        for j in 0..AMBIENT_SAMPLES {
+            let t = (Vec2::new(i as f32, j as f32) + 0.5) / AMBIENT_SAMPLES as f32;
+            let Some(cell) = mineral_map
+                .world_to_cell(view.min + view.size() * t)
+                .and_then(|cell| mineral_map.get(cell.x as usize, cell.y as usize))
+            else {
+                continue;
+            };
+            samples += 1;
+            if cell.mineral_type == MineralType::Empty {
+                cavities += usize::from(cell.biome == Biome::IceFields);
+            } else if cell.biome == Biome::RegolithPlains && cell.stratum == Stratum::Regolith {
+                dunes += 1;
             }
         }
+    }
 
-        let image = Image::new(
-            Extent3d {
-                width: size,
-                height: size,
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            pixel_data,
-            TextureFormat::Rgba8UnormSrgb,
-            Default::default(),
-        );
+    // Digging units rumble at full strength, moving ones less
+    let busy: f32 = equipment_state
+        .all_ids()
+        .into_iter()
+        .filter_map(|id| equipment_state.find_node(id))
+        .filter(|node| node.position.is_some_and(|position| view.contains(position)))
+        .map(|node| match node.activity {
+            UnitActivity::Digging { .. } => 1.0,
+            UnitActivity::Moving { .. } => 0.4,
+            _ => 0.0,
+        })
+        .sum();
 
-        images.add(image)
-    }
+    let share = |count: usize| if samples == 0 { 0.0 } else { count as f32 / samples as f32 };
+    ambience.levels.set(AmbientLayer::Wind, share(dunes));
+    ambience.levels.set(AmbientLayer::Rumble, busy / 4.0);
+    // Ice cavities are small, so a few on screen already drip steadily
+    ambience.levels.set(AmbientLayer::Drips, share(cavities) * 8.0);
+}
 
-    // Create colored sprites for each equipment type
-    sprites.insert(
-        EquipmentType::Sampler,
-        create_colored_sprite(&mut images, [100, 200, 255, 255]), // Light blue
-    );
-    sprites.insert(
-        EquipmentType::SurfaceMining,
-        create_colored_sprite(&mut images, [255, 200, 100, 255]), // Orange
-    );
-    sprites.insert(
-        EquipmentType::DeepMining,
-        create_colored_sprite(&mut images, [200, 100, 255, 255]), // Purple
-    );
-    sprites.insert(
-        EquipmentType::Refining,
-        create_colored_sprite(&mut images, [255, 100, 100, 255]), // Red
-    );
-    sprites.insert(
-        EquipmentType::Transport,
-        create_colored_sprite(&mut images, [100, 255, 100, 255]), // Green
-    );
+// Dialog shown after a crash in the previous session
+fn crash_report_dialog(mut contexts: EguiContexts, mut pending: ResMut<PendingCrashReport>) {
+    let Some(path) = &pending.0 else {
+        return;
+    };
 
-    commands.insert_resource(EquipmentSprites { sprites });
+    let mut dismissed = false;
+    egui::Window::new("Sorry, the game crashed last time")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label("A bug report was saved to:");
+            ui.monospace(path.display().to_string());
+            ui.label("It contains the world seed, recent events and system info.");
+            ui.label("Attaching it to an issue on GitHub helps us fix the problem.");
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Copy Path").clicked() {
+                    ui.ctx().copy_text(path.display().to_string());
+                }
+                if ui.button("Dismiss").clicked() {
+                    dismissed = true;
+                }
+            });
+        });
+
+    if dismissed {
+        pending.0 = None;
+    }
 }
 
-// System to spawn sprite entities for equipment that doesn't have one yet
-fn spawn_equipment_sprites(
-    mut commands: Commands,
-    equipment_state: Res<EquipmentTreeState>,
-    equipment_sprites: Res<EquipmentSprites>,
-    existing_sprites: Query<&EquipmentSprite>,
+// Every setting the settings window edits
+#[derive(SystemParam)]
+struct Settings<'w> {
+    background: ResMut<'w, BackgroundSettings>,
+    accessibility: ResMut<'w, AccessibilitySettings>,
+    alert: ResMut<'w, AlertSettings>,
+    layout: ResMut<'w, LayoutSettings>,
+    trail: ResMut<'w, TrailSettings>,
+    graphics: ResMut<'w, GraphicsSettings>,
+    ambient: ResMut<'w, AmbientSettings>,
+}
+
+// Settings window opened from the top bar
+fn settings_window_system(
+    mut contexts: EguiContexts,
+    mut ui_panels: ResMut<UiPanels>,
+    settings: Settings,
+    ambience: Res<Ambience>,
 ) {
-    // Get all existing equipment IDs that already have sprites
-    let existing_ids: std::collections::HashSet<usize> = existing_sprites
-        .iter()
-        .map(|sprite| sprite.equipment_id)
-        .collect();
+    let Settings {
+        background: mut background_settings,
+        accessibility: mut accessibility_settings,
+        alert: mut alert_settings,
+        layout: mut layout_settings,
+        trail: mut trail_settings,
+        graphics: mut graphics_settings,
+        ambient: mut ambient_settings,
+    } = settings;
+    let ctx = contexts.ctx_mut();
+    let was_compact = layout_settings.compact;
+    egui::Window::new("Settings")
+        .open(&mut ui_panels.settings)
+        .show(ctx, |ui| {
+            ui.heading("Graphics");
+            ui.horizontal(|ui| {
+                for (preset, name) in [
+                    (GraphicsPreset::Low, "Low"),
+                    (GraphicsPreset::Medium, "Medium"),
+                    (GraphicsPreset::High, "High"),
+                ] {
+                    if ui.selectable_label(graphics_settings.preset == preset, name).clicked() {
+                        graphics_settings.apply_preset(preset, &mut trail_settings);
+                    }
+                }
+                if graphics_settings.preset == GraphicsPreset::Custom {
+                    ui.label("(Custom)");
+                }
+            });
 
-    // Helper function to recursively spawn sprites
-    fn spawn_for_node(
-        node: &EquipmentTreeNode,
-        existing_ids: &std::collections::HashSet<usize>,
-        equipment_sprites: &EquipmentSprites,
-        commands: &mut Commands,
-    ) {
-        // If this is an equipment node (not a container)
-        if let Some(equipment_type) = node.equipment_type() {
-            if !existing_ids.contains(&node.id) {
-                // Equipment needs a sprite
-                if let Some(sprite_handle) = equipment_sprites.sprites.get(&equipment_type) {
-                    let position = node.position.unwrap_or_else(|| {
-                        // Random position on map if not set
-                        let mut rng = thread_rng();
-                        Vec2::new(
-                            rng.gen_range(-400.0..400.0),
-                            rng.gen_range(-300.0..300.0),
-                        )
-                    });
+            // Editing any quality option by hand leaves the preset
+            let graphics = graphics_settings.bypass_change_detection();
+            let mut quality_changed = ui.checkbox(&mut graphics.vsync, "VSync").changed();
+            egui::ComboBox::from_label("Frame cap")
+                .selected_text(graphics.frame_cap.map_or("Uncapped".to_string(), |fps| format!("{} FPS", fps)))
+                .show_ui(ui, |ui| {
+                    for cap in [Some(30), Some(60), Some(120), None] {
+                        let label = cap.map_or("Uncapped".to_string(), |fps| format!("{} FPS", fps));
+                        quality_changed |= ui.selectable_value(&mut graphics.frame_cap, cap, label).changed();
+                    }
+                });
+            egui::ComboBox::from_label("Overlay updates")
+                .selected_text(graphics.overlay_rate.map_or("Every frame".to_string(), |hz| format!("{} per second", hz)))
+                .show_ui(ui, |ui| {
+                    for rate in [Some(5.0), Some(15.0), None] {
+                        let label = rate.map_or("Every frame".to_string(), |hz| format!("{} per second", hz));
+                        quality_changed |= ui.selectable_value(&mut graphics.overlay_rate, rate, label).changed();
+                    }
+                });
+            if quality_changed {
+                graphics.preset = GraphicsPreset::Custom;
+            }
 
-                    commands.spawn((
-                        Sprite::from_image(sprite_handle.clone()),
-                        Transform::from_translation(position.extend(1.0)),
-                        EquipmentSprite {
-                            equipment_id: node.id,
-                        },
-                    ));
-                }
+            let mut display_changed = false;
+            ui.horizontal(|ui| {
+                display_changed |= ui.radio_value(&mut graphics.display_mode, DisplayMode::Windowed, "Windowed").changed();
+                display_changed |= ui.radio_value(&mut graphics.display_mode, DisplayMode::Borderless, "Borderless fullscreen").changed();
+            });
+            egui::ComboBox::from_label("Resolution")
+                .selected_text(format!("{}x{}", graphics.resolution.0, graphics.resolution.1))
+                .show_ui(ui, |ui| {
+                    for resolution in GraphicsSettings::RESOLUTIONS {
+                        let label = format!("{}x{}", resolution.0, resolution.1);
+                        display_changed |= ui.selectable_value(&mut graphics.resolution, resolution, label).changed();
+                    }
+                });
+            if quality_changed || display_changed {
+                graphics_settings.set_changed();
             }
-        }
 
-        // Recursively spawn for children
-        for child in &node.children {
-            spawn_for_node(child, existing_ids, equipment_sprites, commands);
+            ui.separator();
+            ui.heading("Background");
+            ui.checkbox(&mut background_settings.pause_on_focus_loss, "Pause when the window loses focus");
+            ui.checkbox(&mut background_settings.throttle_in_background, "Throttle updates when unfocused or minimized");
+
+            ui.separator();
+            ui.heading("Alerts");
+            ui.checkbox(&mut alert_settings.audio_cues, "Audio cues for off-screen events");
+            ui.checkbox(&mut alert_settings.edge_indicators, "Edge-of-screen arrows for off-screen events");
+
+            ui.separator();
+            ui.heading("Ambience");
+            ui.checkbox(&mut ambient_settings.enabled, "Ambient soundscape driven by what is on screen");
+            ui.add_enabled(
+                ambient_settings.enabled,
+                egui::Slider::new(&mut ambient_settings.volume, 0.0..=1.0).text("Volume"),
+            );
+            for layer in AmbientLayer::ALL {
+                ui.add(egui::ProgressBar::new(ambience.levels.get(layer)).text(layer.name()));
+            }
+
+            ui.separator();
+            ui.heading("Trails");
+            ui.checkbox(&mut trail_settings.enabled, "Show movement trails behind units");
+            ui.add_enabled(
+                trail_settings.enabled,
+                egui::Slider::new(&mut trail_settings.duration, 1.0..=60.0).text("Trail length (s)"),
+            );
+
+            ui.separator();
+            ui.heading("Accessibility");
+            ui.checkbox(&mut accessibility_settings.high_contrast, "High-contrast UI theme")
+                .on_hover_text("White text and outlines on black panels, yellow focus highlights");
+
+            ui.separator();
+            ui.heading("Layout");
+            ui.checkbox(&mut layout_settings.compact, "Compact layout")
+                .on_hover_text("Side panels fold into an icon strip and controls get larger hit targets, for small screens and controllers");
+            ui.add(egui::Slider::new(&mut layout_settings.ui_scale, 0.75..=2.0).text("UI scale"));
+        });
+
+    // Entering compact mode folds the equipment tree away to free up the map
+    if layout_settings.compact && !was_compact {
+        ui_panels.equipment = false;
+    }
+}
+
+// System to apply the UI theme when accessibility settings change
+fn apply_ui_theme(
+    mut contexts: EguiContexts,
+    accessibility_settings: Res<AccessibilitySettings>,
+    layout_settings: Res<LayoutSettings>,
+) {
+    if !accessibility_settings.is_changed() && !layout_settings.is_changed() {
+        return;
+    }
+
+    let visuals = if accessibility_settings.high_contrast {
+        high_contrast_visuals()
+    } else {
+        egui::Visuals::dark()
+    };
+    let ctx = contexts.ctx_mut();
+    ctx.set_visuals(visuals);
+    ctx.set_zoom_factor(layout_settings.ui_scale);
+
+    // Larger hit targets in compact mode, for touch screens and controller cursors
+    let mut spacing = egui::style::Spacing::default();
+    if layout_settings.compact {
+        spacing.interact_size.y = 32.0;
+        spacing.button_padding = egui::vec2(10.0, 8.0);
+        spacing.item_spacing = egui::vec2(10.0, 8.0);
+        spacing.icon_width = 20.0;
+    }
+    ctx.style_mut(|style| style.spacing = spacing);
+}
+
+/// Commands on the gamepad radial menu, clockwise from the top
+const RADIAL_COMMANDS: [GameCommand; 8] = [
+    GameCommand::Spawn(EquipmentType::Sampler, None),
+    GameCommand::Spawn(EquipmentType::SurfaceMining, None),
+    GameCommand::Spawn(EquipmentType::DeepMining, None),
+    GameCommand::Spawn(EquipmentType::Transport, None),
+    GameCommand::ToggleZoneDesignation,
+    GameCommand::JumpToLatestEvent,
+    GameCommand::NavigateBack,
+    GameCommand::TogglePause,
+];
+
+// Radial menu shown while a gamepad's West button is held. The left stick
+// picks a command, which runs when the button is released.
+fn gamepad_radial_menu(
+    gamepads: Query<&Gamepad>,
+    mut contexts: EguiContexts,
+    mut game_commands: EventWriter<GameCommand>,
+    mut highlighted: Local<Option<usize>>,
+) {
+    let Some(gamepad) = gamepads
+        .iter()
+        .find(|gamepad| gamepad.pressed(GamepadButton::West) || gamepad.just_released(GamepadButton::West))
+    else {
+        return;
+    };
+
+    if gamepad.just_released(GamepadButton::West) {
+        if let Some(i) = highlighted.take() {
+            game_commands.write(RADIAL_COMMANDS[i]);
         }
+        return;
     }
 
-    // Spawn sprites for all equipment nodes in the tree
-    for node in &equipment_state.nodes {
-        spawn_for_node(node, &existing_ids, &equipment_sprites, &mut commands);
+    // Angle clockwise from up, split into equal slices
+    let stick = gamepad.left_stick();
+    let slice = std::f32::consts::TAU / RADIAL_COMMANDS.len() as f32;
+    if stick.length() > 0.5 {
+        let angle = stick.x.atan2(stick.y).rem_euclid(std::f32::consts::TAU);
+        *highlighted = Some(((angle + slice / 2.0) / slice) as usize % RADIAL_COMMANDS.len());
+    }
+
+    let ctx = contexts.ctx_mut();
+    let center = ctx.screen_rect().center();
+    let radius = 130.0;
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Foreground, egui::Id::new("radial_menu")));
+    painter.circle_filled(center, radius + 50.0, egui::Color32::from_black_alpha(180));
+
+    for (i, command) in RADIAL_COMMANDS.iter().enumerate() {
+        let angle = i as f32 * slice;
+        let position = center + egui::vec2(angle.sin(), -angle.cos()) * radius;
+        let color = if *highlighted == Some(i) {
+            egui::Color32::YELLOW
+        } else {
+            egui::Color32::WHITE
+        };
+        painter.text(
+            position,
+            egui::Align2::CENTER_CENTER,
+            command.label(),
+            egui::FontId::proportional(16.0),
+            color,
+        );
     }
 }
 
-// System to update equipment positions in the state when sprites move
-fn update_equipment_positions(
-    mut equipment_state: ResMut<EquipmentTreeState>,
-    sprite_query: Query<(&Transform, &EquipmentSprite), Changed<Transform>>,
+/// Dark theme with maximum contrast between text, outlines, and backgrounds
+fn high_contrast_visuals() -> egui::Visuals {
+    let white = egui::Color32::WHITE;
+    let black = egui::Color32::BLACK;
+    let yellow = egui::Color32::YELLOW;
+
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(white);
+    visuals.hyperlink_color = yellow;
+    visuals.panel_fill = black;
+    visuals.window_fill = black;
+    visuals.window_stroke = egui::Stroke::new(2.0, white);
+    visuals.faint_bg_color = egui::Color32::from_gray(24);
+    visuals.extreme_bg_color = black;
+    visuals.selection.bg_fill = egui::Color32::from_rgb(0, 70, 200);
+    visuals.selection.stroke = egui::Stroke::new(2.0, white);
+
+    let widgets = &mut visuals.widgets;
+    widgets.noninteractive.bg_stroke = egui::Stroke::new(1.0, white);
+    widgets.noninteractive.fg_stroke = egui::Stroke::new(1.0, white);
+    for state in [&mut widgets.inactive, &mut widgets.hovered, &mut widgets.active, &mut widgets.open] {
+        state.bg_fill = black;
+        state.weak_bg_fill = black;
+        state.bg_stroke = egui::Stroke::new(1.5, white);
+        state.fg_stroke = egui::Stroke::new(1.5, white);
+    }
+    widgets.hovered.bg_stroke = egui::Stroke::new(2.5, yellow);
+    widgets.active.bg_stroke = egui::Stroke::new(2.5, yellow);
+
+    visuals
+}
+
+// System to pause the simulation when the window loses focus
+fn pause_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    background_settings: Res<BackgroundSettings>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut focus_pause: ResMut<FocusPause>,
 ) {
-    for (transform, equipment_sprite) in &sprite_query {
-        // Find the equipment node and update its position
-        if let Some(node) = equipment_state.find_node_mut(equipment_sprite.equipment_id) {
-            node.position = Some(transform.translation.truncate());
+    for event in focus_events.read() {
+        if !event.focused && background_settings.pause_on_focus_loss && !virtual_time.is_paused() {
+            virtual_time.pause();
+            focus_pause.active = true;
         }
     }
 }
 
-// System to select equipment by clicking on them
-fn click_select_equipment(
-    mouse_button: Res<ButtonInput<MouseButton>>,
-    windows: Query<&Window, With<PrimaryWindow>>,
-    camera_query: Query<(&Camera, &GlobalTransform)>,
-    equipment_query: Query<(&Transform, &EquipmentSprite)>,
-    mut selected: ResMut<SelectedEquipment>,
-    mut equipment_state: ResMut<EquipmentTreeState>,
-    mut equipment_actions: ResMut<EquipmentTreeActions>,
-    mut contexts: bevy_egui::EguiContexts,
+// System to apply display settings to the window
+fn apply_graphics_settings(
+    graphics_settings: Res<GraphicsSettings>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut applied_display: Local<Option<(DisplayMode, (u32, u32))>>,
 ) {
-    if mouse_button.just_pressed(MouseButton::Left) {
-        // Don't process clicks if hovering over UI
-        if contexts.ctx_mut().is_pointer_over_area() {
-            return;
+    if !graphics_settings.is_changed() {
+        return;
+    }
+
+    let Ok(mut window) = windows.single_mut() else {
+        return;
+    };
+    window.present_mode = if graphics_settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+
+    // Only resize when the display options change, so other edits keep a hand-resized window
+    let display = (graphics_settings.display_mode, graphics_settings.resolution);
+    if *applied_display == Some(display) {
+        return;
+    }
+    *applied_display = Some(display);
+    window.mode = match graphics_settings.display_mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Borderless => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+    };
+    let (width, height) = graphics_settings.resolution;
+    window.resolution.set(width as f32, height as f32);
+}
+
+// System holding frames to the frame cap: at the end of each frame it
+// sleeps off whatever is left of the frame's time budget
+fn limit_frame_rate(graphics_settings: Res<GraphicsSettings>, mut frame_start: Local<Option<Instant>>) {
+    if let (Some(fps), Some(start)) = (graphics_settings.frame_cap, *frame_start) {
+        let budget = Duration::from_secs_f64(1.0 / fps as f64);
+        if let Some(remaining) = budget.checked_sub(start.elapsed()) {
+            std::thread::sleep(remaining);
         }
+    }
+    *frame_start = Some(Instant::now());
+}
 
-        let Ok(window) = windows.single() else {
-            return;
-        };
-        let Some(cursor_position) = window.cursor_position() else {
-            return;
-        };
+/// Run condition limiting map overlays to the configured refresh rate
+fn overlay_refresh_due(
+    time: Res<Time<Real>>,
+    graphics_settings: Res<GraphicsSettings>,
+    mut last_refresh: Local<f32>,
+) -> bool {
+    let now = time.elapsed_secs();
+    match graphics_settings.overlay_rate {
+        Some(rate) if now - *last_refresh < 1.0 / rate => false,
+        _ => {
+            *last_refresh = now;
+            true
+        }
+    }
+}
 
-        // Get camera
-        let Ok((camera, camera_transform)) = camera_query.single() else {
-            return;
-        };
+// System to lower the update rate while the window is in the background
+fn apply_background_throttle(
+    background_settings: Res<BackgroundSettings>,
+    mut winit_settings: ResMut<WinitSettings>,
+) {
+    if !background_settings.is_changed() {
+        return;
+    }
 
-        // Convert screen position to world position
-        let Ok(world_position) = camera
-            .viewport_to_world_2d(camera_transform, cursor_position)
-        else {
-            return;
-        };
+    // Minimized windows are unfocused too, so this covers both cases
+    winit_settings.unfocused_mode = if background_settings.throttle_in_background {
+        UpdateMode::reactive_low_power(Duration::from_millis(250))
+    } else {
+        UpdateMode::Continuous
+    };
+}
 
-        // Check if we clicked on any equipment
-        let mut clicked_id: Option<usize> = None;
-        let sprite_size = 64.0; // Equipment sprite click radius (increased for easier clicking)
+// Banner shown after returning to a window that paused on focus loss
+fn focus_pause_banner(
+    mut contexts: EguiContexts,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut focus_pause: ResMut<FocusPause>,
+) {
+    if !focus_pause.active {
+        return;
+    }
 
-        for (transform, equipment_sprite) in &equipment_query {
-            let sprite_pos = transform.translation.truncate();
-            let distance = world_position.distance(sprite_pos);
+    let ctx = contexts.ctx_mut();
+    let mut resume = keyboard.just_pressed(KeyCode::Space);
+    egui::Area::new(egui::Id::new("focus_pause_banner"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Paused (unfocused)");
+                    resume |= ui.button("Resume (Space)").clicked();
+                });
+            });
+        });
 
-            if distance < sprite_size {
-                clicked_id = Some(equipment_sprite.equipment_id);
-                break;
-            }
-        }
+    if resume {
+        virtual_time.unpause();
+        focus_pause.active = false;
+    }
+}
 
-        // Update selection in both resources
-        selected.selected_id = clicked_id;
+// Overlays the spectator bar can toggle
+#[derive(SystemParam)]
+struct SpectatorOverlays<'w> {
+    dispatcher: ResMut<'w, MiningDispatcher>,
+    trail_settings: ResMut<'w, TrailSettings>,
+}
 
-        // Clear previous selection and set new one in equipment_actions
-        equipment_actions.selected.clear();
-        if let Some(id) = clicked_id {
-            equipment_actions.selected.insert(id);
-        }
+// System for spectator mode: a bar with the overlay toggles and a way out,
+// and the auto-camera cutting between moving and digging units
+fn spectator_system(
+    time: Res<Time<Real>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut spectator: ResMut<SpectatorMode>,
+    equipment_state: Res<EquipmentTreeState>,
+    overlays: SpectatorOverlays,
+    mut camera_query: Query<&mut Transform, With<Camera>>,
+    mut contexts: EguiContexts,
+) {
+    let SpectatorOverlays {
+        mut dispatcher,
+        mut trail_settings,
+    } = overlays;
+    if !spectator.active {
+        return;
+    }
+    let Ok(mut camera_transform) = camera_query.single_mut() else {
+        return;
+    };
+
+    let ctx = contexts.ctx_mut();
+    let mut leave = keyboard.just_pressed(KeyCode::Escape);
+    egui::Area::new(egui::Id::new("spectator_bar"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Spectating");
+                    ui.separator();
+                    ui.checkbox(&mut spectator.auto_camera, "Auto camera");
+                    ui.checkbox(&mut dispatcher.show_overlay, "Miner overlay");
+                    ui.checkbox(&mut trail_settings.enabled, "Trails");
+                    ui.separator();
+                    leave |= ui.button("Leave (Esc)").clicked();
+                });
+            });
+        });
+    if leave {
+        spectator.active = false;
+        return;
+    }
+
+    // Panning by hand takes the camera back from the tour
+    let pan_keys = [KeyCode::KeyW, KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD];
+    if keyboard.any_just_pressed(pan_keys) {
+        spectator.auto_camera = false;
+    }
+    if !spectator.auto_camera {
+        return;
+    }
+
+    let position_of = |id: usize| equipment_state.find_node(id).and_then(|node| node.position);
+    let now = time.elapsed_secs();
+    if now >= spectator.next_cut || spectator.target.and_then(position_of).is_none() {
+        // Cut to the next busy unit, or any placed unit if none are busy
+        let placed: Vec<usize> = equipment_state
+            .all_ids()
+            .into_iter()
+            .filter(|&id| position_of(id).is_some())
+            .collect();
+        let busy: Vec<usize> = placed
+            .iter()
+            .copied()
+            .filter(|&id| {
+                equipment_state.find_node(id).is_some_and(|node| {
+                    matches!(node.activity, UnitActivity::Moving { .. } | UnitActivity::Digging { .. })
+                })
+            })
+            .collect();
+        let mut candidates = if busy.is_empty() { placed } else { busy };
+        candidates.sort_unstable();
+        spectator.target = candidates
+            .iter()
+            .copied()
+            .find(|&id| spectator.target.is_some_and(|current| id > current))
+            .or(candidates.first().copied());
+        spectator.next_cut = now + SpectatorMode::DWELL;
+    }
+
+    if let Some(target) = spectator.target.and_then(position_of) {
+        let current = camera_transform.translation.truncate();
+        let eased = current.lerp(target, 1.0 - (-2.0 * time.delta_secs()).exp());
+        camera_transform.translation.x = eased.x;
+        camera_transform.translation.y = eased.y;
+    }
+}
 
-        // Activate/deactivate equipment - helper function to recursively update
-        fn update_active_state(node: &mut EquipmentTreeNode, active_id: usize) {
-            node.active = node.id == active_id;
-            for child in &mut node.children {
-                update_active_state(child, active_id);
-            }
-        }
+// System to raise alerts for positioned events that happen outside the view
+fn detect_offscreen_events(
+    mut commands: Commands,
+    event_log: Res<EventLog>,
+    alert_settings: Res<AlertSettings>,
+    mut offscreen_alerts: ResMut<OffscreenAlerts>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    time: Res<Time<Real>>,
+) {
+    let Ok((camera, camera_transform)) = camera_query.single() else {
+        return;
+    };
+    let now = time.elapsed_secs();
 
-        if let Some(id) = clicked_id {
-            for node in &mut equipment_state.nodes {
-                update_active_state(node, id);
-            }
+    let new_positions: Vec<Vec2> = event_log
+        .since(offscreen_alerts.seen_events)
+        .iter()
+        .filter_map(|entry| entry.position)
+        .filter(|&position| !is_on_screen(camera, camera_transform, position))
+        .collect();
+    offscreen_alerts.seen_events = event_log.total_pushed;
+
+    for position in new_positions {
+        if alert_settings.audio_cues {
+            // Spatial playback pans the cue toward the event relative to the camera
+            commands.spawn((
+                AudioPlayer(offscreen_alerts.cue.clone()),
+                PlaybackSettings::DESPAWN
+                    .with_spatial(true)
+                    .with_spatial_scale(SpatialScale::new_2d(AUDIO_SPATIAL_SCALE)),
+                Transform::from_translation(position.extend(0.0)),
+            ));
         }
+        offscreen_alerts.alerts.push(OffscreenAlert {
+            position,
+            expires_at: now + 6.0,
+        });
     }
+
+    // Alerts end when they expire or once the event comes into view
+    offscreen_alerts.alerts.retain(|alert| {
+        alert.expires_at > now && !is_on_screen(camera, camera_transform, alert.position)
+    });
 }
 
-// System to move selected equipment with arrow keys
-fn move_selected_equipment(
-    keyboard: Res<ButtonInput<KeyCode>>,
-    time: Res<Time>,
-    selected: Res<SelectedEquipment>,
-    mut sprite_query: Query<(&mut Transform, &EquipmentSprite)>,
+/// Whether a world position is inside the camera's viewport
+fn is_on_screen(camera: &Camera, camera_transform: &GlobalTransform, position: Vec2) -> bool {
+    let (Ok(viewport_position), Some(size)) = (
+        camera.world_to_viewport(camera_transform, position.extend(0.0)),
+        camera.logical_viewport_size(),
+    ) else {
+        return false;
+    };
+
+    viewport_position.cmpge(Vec2::ZERO).all() && viewport_position.cmple(size).all()
+}
+
+// System to draw pulsing arrows at the screen edge pointing at off-screen alerts
+fn draw_offscreen_indicators(
+    mut contexts: EguiContexts,
+    alert_settings: Res<AlertSettings>,
+    offscreen_alerts: Res<OffscreenAlerts>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    time: Res<Time<Real>>,
 ) {
-    let Some(selected_id) = selected.selected_id else {
+    if !alert_settings.edge_indicators || offscreen_alerts.alerts.is_empty() {
+        return;
+    }
+    let Ok((camera, camera_transform)) = camera_query.single() else {
         return;
     };
 
-    let move_speed = 200.0 * time.delta_secs();
+    let ctx = contexts.ctx_mut();
+    let screen = ctx.screen_rect().shrink(24.0);
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("offscreen_indicators"),
+    ));
+    let pulse = 0.6 + 0.4 * (time.elapsed_secs() * 6.0).sin();
+    let color = egui::Color32::from_rgba_unmultiplied(255, 200, 0, (pulse * 255.0) as u8);
 
-    for (mut transform, equipment_sprite) in &mut sprite_query {
-        if equipment_sprite.equipment_id == selected_id {
-            // Move with arrow keys
-            if keyboard.pressed(KeyCode::ArrowUp) {
-                transform.translation.y += move_speed;
-            }
-            if keyboard.pressed(KeyCode::ArrowDown) {
-                transform.translation.y -= move_speed;
-            }
-            if keyboard.pressed(KeyCode::ArrowLeft) {
-                transform.translation.x -= move_speed;
-            }
-            if keyboard.pressed(KeyCode::ArrowRight) {
-                transform.translation.x += move_speed;
-            }
-            break;
+    for alert in &offscreen_alerts.alerts {
+        let Ok(target) = camera.world_to_viewport(camera_transform, alert.position.extend(0.0)) else {
+            continue;
+        };
+
+        // Walk from the screen center toward the target until hitting the inset edge
+        let center = screen.center();
+        let direction = egui::vec2(target.x - center.x, target.y - center.y).normalized();
+        if !direction.x.is_finite() || !direction.y.is_finite() {
+            continue;
         }
+        let reach_x = if direction.x != 0.0 { screen.width() / 2.0 / direction.x.abs() } else { f32::INFINITY };
+        let reach_y = if direction.y != 0.0 { screen.height() / 2.0 / direction.y.abs() } else { f32::INFINITY };
+        let tip = center + direction * reach_x.min(reach_y);
+
+        let side = direction.rot90() * 9.0;
+        let base = tip - direction * 18.0;
+        painter.add(egui::Shape::convex_polygon(
+            vec![tip, base + side, base - side],
+            color,
+            egui::Stroke::new(1.5, egui::Color32::BLACK),
+        ));
     }
 }
 
+/// Format a byte count with a binary unit suffix
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Format seconds as m:ss for log timestamps
+fn format_clock(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// The outliner's tree, selection, drag and filter state for ui_system
+#[derive(SystemParam)]
+struct OutlinerState<'w, 's> {
+    equipment_state: ResMut<'w, EquipmentTreeState>,
+    equipment_actions: ResMut<'w, EquipmentTreeActions>,
+    selected: Res<'w, SelectedEquipment>,
+    tree_drag: ResMut<'w, TreeDragToMap>,
+    move_error: Local<'s, Option<TreeMoveError>>,
+    tag_filter: Local<'s, Option<ColorTag>>,
+}
+
+// Spawn policy, batch spawn and click-to-place state behind the equipment
+// panel's spawn controls
+#[derive(SystemParam)]
+struct SpawnControls<'w> {
+    spawn_settings: ResMut<'w, SpawnSettings>,
+    batch_spawn: ResMut<'w, BatchSpawn>,
+    placement: Res<'w, PlacementMode>,
+}
+
+// Which panels are open and how they are laid out
+#[derive(SystemParam)]
+struct PanelLayout<'w> {
+    ui_panels: ResMut<'w, UiPanels>,
+    layout_settings: Res<'w, LayoutSettings>,
+}
+
+// Dialogs opened from the equipment panel and the menu bar
+#[derive(SystemParam)]
+struct Dialogs<'w> {
+    rename_dialog: ResMut<'w, BatchRenameDialog>,
+    world_seed: ResMut<'w, WorldSeed>,
+}
+
 fn ui_system(
     mut contexts: EguiContexts,
-    mut equipment_state: ResMut<EquipmentTreeState>,
-    mut equipment_actions: ResMut<EquipmentTreeActions>,
-    selected: Res<SelectedEquipment>,
+    outliner: OutlinerState,
+    spawn_controls: SpawnControls,
+    panels: PanelLayout,
+    dialogs: Dialogs,
+    camera_query: Query<&Transform, With<Camera>>,
+    mut game_commands: EventWriter<GameCommand>,
 ) {
+    let OutlinerState {
+        mut equipment_state,
+        mut equipment_actions,
+        selected,
+        mut tree_drag,
+        mut move_error,
+        mut tag_filter,
+    } = outliner;
+    let SpawnControls {
+        mut spawn_settings,
+        mut batch_spawn,
+        placement,
+    } = spawn_controls;
+    let PanelLayout {
+        mut ui_panels,
+        layout_settings,
+    } = panels;
+    let Dialogs {
+        mut rename_dialog,
+        mut world_seed,
+    } = dialogs;
     let ctx = contexts.ctx_mut();
+    let camera_center = camera_query
+        .single()
+        .ok()
+        .map(|transform| transform.translation.truncate());
 
     // Top panel
     egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
         ui.horizontal(|ui| {
             ui.label("Regolith Voxel - Mining Operations");
             ui.separator();
-            ui.label("WASD: Pan | Q/E: Zoom | Click: Select | Arrows: Move");
+            ui.label("WASD: Pan | Q/E: Zoom | Click: Select | Arrows: Move | F6: Keyboard Focus");
+            ui.separator();
+            if ui.button("Settings").clicked() {
+                ui_panels.settings = !ui_panels.settings;
+            }
+            if ui.button("Mining Zones").clicked() {
+                ui_panels.mining_zones = !ui_panels.mining_zones;
+            }
+            if ui.button("Routes").clicked() {
+                ui_panels.routes = !ui_panels.routes;
+            }
+            if ui.button("Plan").on_hover_text("Queue orders and execute them together").clicked() {
+                game_commands.write(GameCommand::TogglePlanning);
+            }
+            if ui.button("Spectate").on_hover_text("Lock everything but the camera and overlays").clicked() {
+                game_commands.write(GameCommand::ToggleSpectator);
+            }
+            if ui.button("New World").clicked() {
+                world_seed.dialog_open = !world_seed.dialog_open;
+            }
 
             if let Some(selected_id) = selected.selected_id {
                 ui.separator();
                 ui.label(format!("Selected: Unit #{}", selected_id));
             }
+
+            if let Some(pending_id) = placement.pending {
+                ui.separator();
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!("Click the map to place Unit #{} (Esc: cancel)", pending_id),
+                );
+            }
         });
     });
 
-    // Bottom panel
-    egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-        ui.horizontal(|ui| {
-            ui.label("Status: Ready");
+    // Compact layout: panels fold into an icon strip, with the legend as a tooltip
+    if layout_settings.compact {
+        egui::SidePanel::left("icon_strip").resizable(false).exact_width(48.0).show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.button("■").on_hover_ui(|ui| {
+                    for mineral in MineralType::LEGEND {
+                        let [r, g, b, _] = mineral.color().to_srgba().to_u8_array();
+                        ui.colored_label(egui::Color32::from_rgb(r, g, b), format!("■ {}", mineral.name()));
+                    }
+                });
+                ui.separator();
+                for panel in UiPanel::ALL {
+                    if ui
+                        .button(egui::RichText::new(panel.icon()).size(20.0))
+                        .on_hover_text(panel.name())
+                        .clicked()
+                    {
+                        ui_panels.toggle(panel);
+                    }
+                }
+            });
         });
-    });
+    }
 
     // Left panel - Legend
-    egui::SidePanel::left("left_panel").show(ctx, |ui| {
+    egui::SidePanel::left("left_panel").show_animated(ctx, !layout_settings.compact, |ui| {
         ui.heading("Minerals");
         ui.separator();
 
         ui.label("Legend:");
-        ui.colored_label(egui::Color32::from_rgb(204, 102, 51), "■ Iron");
-        ui.colored_label(egui::Color32::from_rgb(184, 115, 51), "■ Copper");
-        ui.colored_label(egui::Color32::from_rgb(255, 215, 0), "■ Gold");
-        ui.colored_label(egui::Color32::from_rgb(192, 192, 192), "■ Silver");
-        ui.colored_label(egui::Color32::from_rgb(51, 204, 51), "■ Uranium");
-        ui.colored_label(egui::Color32::from_rgb(102, 204, 255), "■ Diamond");
-        ui.colored_label(egui::Color32::from_rgb(51, 51, 51), "■ Coal");
+        for mineral in MineralType::LEGEND {
+            let [r, g, b, _] = mineral.color().to_srgba().to_u8_array();
+            ui.colored_label(egui::Color32::from_rgb(r, g, b), format!("■ {}", mineral.name()));
+        }
     });
 
     // Right panel - Equipment Tree with Outliner
-    egui::SidePanel::right("right_panel").min_width(300.0).show(ctx, |ui| {
+    egui::SidePanel::right("right_panel").min_width(300.0).show_animated(ctx, ui_panels.equipment, |ui| {
         ui.heading("Mining Equipment");
         ui.separator();
 
@@ -937,63 +7145,205 @@ fn ui_system(
             }
 
             ui.menu_button("+ New Equipment", |ui| {
-                if ui.button("Sampler").clicked() {
-                    let id = equipment_state.next_id;
-                    equipment_state.add_equipment(
-                        format!("Sampler {}", id),
-                        EquipmentType::Sampler
-                    );
-                    ui.close_menu();
+                for equipment_type in EquipmentType::ALL {
+                    if ui.button(equipment_type.name()).clicked() {
+                        // Modifier keys override the configured spawn policy
+                        let modifiers = ui.input(|i| i.modifiers);
+                        let policy = if modifiers.shift {
+                            Some(SpawnPolicy::CameraCenter)
+                        } else if modifiers.alt {
+                            Some(SpawnPolicy::HomeBase)
+                        } else if modifiers.command {
+                            Some(SpawnPolicy::Placement)
+                        } else {
+                            None
+                        };
+                        game_commands.write(GameCommand::Spawn(equipment_type, policy));
+                        ui.close_menu();
+                    }
                 }
-                if ui.button("Surface Mining").clicked() {
-                    let id = equipment_state.next_id;
-                    equipment_state.add_equipment(
-                        format!("Surface Miner {}", id),
-                        EquipmentType::SurfaceMining
-                    );
+            });
+        });
+
+        ui.collapsing("Spawn Location", |ui| {
+            ui.radio_value(&mut spawn_settings.policy, SpawnPolicy::Random, "Random");
+            ui.radio_value(&mut spawn_settings.policy, SpawnPolicy::CameraCenter, "Camera center");
+            ui.radio_value(&mut spawn_settings.policy, SpawnPolicy::HomeBase, "Home Base");
+            ui.radio_value(&mut spawn_settings.policy, SpawnPolicy::Placement, "Click to place");
+
+            if ui.button("Set Home Base at camera").clicked() {
+                spawn_settings.home_base = camera_center;
+            }
+            ui.small("Shift: camera center | Alt: Home Base | Ctrl: click to place");
+        });
+
+        ui.collapsing("Batch Spawn", |ui| {
+            egui::ComboBox::from_label("Type")
+                .selected_text(batch_spawn.equipment_type.name())
+                .show_ui(ui, |ui| {
+                    for equipment_type in EquipmentType::ALL {
+                        ui.selectable_value(&mut batch_spawn.equipment_type, equipment_type, equipment_type.name());
+                    }
+                });
+            ui.add(egui::Slider::new(&mut batch_spawn.count, 1..=50).text("Count"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut batch_spawn.shape, BatchShape::Line, "Line");
+                ui.radio_value(&mut batch_spawn.shape, BatchShape::Grid, "Grid");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Names:");
+                ui.text_edit_singleline(&mut batch_spawn.name_template);
+            });
+
+            let label = if batch_spawn.placing { "Cancel" } else { "Place on map…" };
+            if ui.button(label).clicked() {
+                batch_spawn.placing = !batch_spawn.placing;
+                batch_spawn.drag_start = None;
+            }
+            if batch_spawn.placing {
+                let hint = match batch_spawn.shape {
+                    BatchShape::Line => "Drag a line on the map (Esc to cancel)",
+                    BatchShape::Grid => "Drag a rectangle on the map (Esc to cancel)",
+                };
+                ui.small(hint);
+            }
+        });
+
+        // Tree layout tools
+        ui.horizontal(|ui| {
+            ui.menu_button("Sort", |ui| {
+                if ui.button("Alphabetical").clicked() {
+                    equipment_state.sort(TreeSortMode::Alphabetical, None);
                     ui.close_menu();
                 }
-                if ui.button("Deep Mining").clicked() {
-                    let id = equipment_state.next_id;
-                    equipment_state.add_equipment(
-                        format!("Deep Miner {}", id),
-                        EquipmentType::DeepMining
-                    );
+                if ui.button("By Type").clicked() {
+                    equipment_state.sort(TreeSortMode::ByType, None);
                     ui.close_menu();
                 }
-                if ui.button("Refining").clicked() {
-                    let id = equipment_state.next_id;
-                    equipment_state.add_equipment(
-                        format!("Refinery {}", id),
-                        EquipmentType::Refining
-                    );
+
+                // Distance is measured from the selected unit
+                let origin = selected
+                    .selected_id
+                    .and_then(|id| equipment_state.find_node(id))
+                    .and_then(|node| node.position);
+                if ui
+                    .add_enabled(origin.is_some(), egui::Button::new("By Distance from Selected"))
+                    .clicked()
+                {
+                    equipment_state.sort(TreeSortMode::ByDistance, origin);
                     ui.close_menu();
                 }
-                if ui.button("Transport").clicked() {
-                    let id = equipment_state.next_id;
-                    equipment_state.add_equipment(
-                        format!("Transport {}", id),
-                        EquipmentType::Transport
-                    );
+
+                if ui.button("By Activity").clicked() {
+                    equipment_state.sort(TreeSortMode::ByActivity, None);
                     ui.close_menu();
                 }
             });
+
+            if ui.button("Auto-group by type").clicked() {
+                equipment_state.auto_group_by_type();
+            }
+
+            if ui
+                .add_enabled(!equipment_actions.selected.is_empty(), egui::Button::new("Rename Selected…"))
+                .clicked()
+            {
+                rename_dialog.open = true;
+            }
+
+            // Ctrl+Z undoes the last drag-drop move unless a text field has focus
+            let undo_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+            let undo_pressed = !ui.ctx().wants_keyboard_input()
+                && ui.input_mut(|i| i.consume_shortcut(&undo_shortcut));
+            let undo_clicked = ui
+                .add_enabled(!equipment_state.undo_moves.is_empty(), egui::Button::new("Undo Move"))
+                .on_hover_text("Ctrl+Z")
+                .clicked();
+            if (undo_clicked || undo_pressed) && equipment_state.undo_move() {
+                *move_error = None;
+            }
+        });
+
+        if let Some(error) = *move_error {
+            ui.colored_label(egui::Color32::from_rgb(230, 120, 80), error.message());
+        }
+
+        // Color tags: tag the outliner selection, or show only one tag
+        ui.horizontal(|ui| {
+            ui.add_enabled_ui(!equipment_actions.selected.is_empty(), |ui| {
+                ui.menu_button("Tag", |ui| {
+                    ui.horizontal(|ui| {
+                        for tag in std::iter::once(None).chain(ColorTag::ALL.map(Some)) {
+                            if tag_swatch(ui, tag, false).clicked() {
+                                for &id in &equipment_actions.selected {
+                                    if let Some(node) = equipment_state.find_node_mut(id) {
+                                        node.tag = tag;
+                                    }
+                                }
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+            });
+
+            ui.label("Show:");
+            for tag in std::iter::once(None).chain(ColorTag::ALL.map(Some)) {
+                if tag_swatch(ui, tag, *tag_filter == tag).clicked() {
+                    *tag_filter = tag;
+                }
+            }
         });
+        if let Some(tag) = *tag_filter {
+            if ui.button(format!("Select all {} units", tag.name())).clicked() {
+                equipment_actions.selected = equipment_state
+                    .all_ids()
+                    .into_iter()
+                    .filter(|&id| {
+                        equipment_state.find_node(id).is_some_and(|node| !node.is_container())
+                            && equipment_state.effective_tag(id) == Some(tag)
+                    })
+                    .collect();
+            }
+        }
 
         ui.separator();
 
         // Show the outliner with the tree
         egui::ScrollArea::vertical().show(ui, |ui| {
-            let response = Outliner::new("equipment_outliner")
-                .show(ui, &equipment_state.nodes, &mut *equipment_actions);
+            let filtered: Vec<EquipmentTreeNode>;
+            let nodes = match *tag_filter {
+                Some(tag) => {
+                    filtered = equipment_state
+                        .nodes
+                        .iter()
+                        .filter_map(|node| node.filter_by_tag(tag, None))
+                        .collect();
+                    &filtered
+                }
+                None => &equipment_state.nodes,
+            };
+            let response = Outliner::new("equipment_outliner").show(ui, nodes, &mut *equipment_actions);
+
+            // Remember what is being dragged in case it is dropped on the map
+            let (pointer_down, pointer_released) =
+                ui.input(|i| (i.pointer.primary_down(), i.pointer.primary_released()));
+            if pointer_down && !response.dragging_nodes().is_empty() {
+                tree_drag.nodes = response.dragging_nodes().to_vec();
+            } else if !pointer_released {
+                tree_drag.nodes.clear();
+            }
+
+            // Handle enable/disable toggles from the tree
+            for id in std::mem::take(&mut equipment_actions.power_toggles) {
+                if let Some(node) = equipment_state.find_node_mut(id) {
+                    node.active = !node.active;
+                }
+            }
 
             // Handle rename events
             if let Some((node_id, new_name)) = response.renamed() {
-                for root in &mut equipment_state.nodes {
-                    if root.rename_node(*node_id, new_name.to_string()) {
-                        break;
-                    }
-                }
+                equipment_state.rename_node(*node_id, new_name.to_string());
             }
 
             // Handle drag-drop events
@@ -1004,121 +7354,144 @@ fn ui_system(
                 // Get all nodes being dragged
                 let dragging_ids = response.dragging_nodes();
 
-                if !dragging_ids.is_empty() {
-                    // Use TreeOperations to handle the move
-                    for drag_id in dragging_ids {
-                        // Find and remove the dragged node
-                        let mut removed_node = None;
-
-                        // Try to remove from root level
-                        if let Some(idx) = equipment_state.nodes.iter().position(|n| n.id == *drag_id) {
-                            removed_node = Some(equipment_state.nodes.remove(idx));
-                        } else {
-                            // Search recursively in children
-                            for root in &mut equipment_state.nodes {
-                                if let Some(node) = EquipmentTreeNode::remove_node(root, *drag_id) {
-                                    removed_node = Some(node);
-                                    break;
-                                }
-                            }
-                        }
-
-                        // Insert the node at the new position
-                        if let Some(node) = removed_node {
-                            let mut inserted = false;
-
-                            // Try to insert relative to target
-                            for root in &mut equipment_state.nodes {
-                                if EquipmentTreeNode::insert_node(root, *target_id, node.clone(), position) {
-                                    inserted = true;
-                                    break;
-                                }
-                            }
-
-                            // If not inserted, add back to root level
-                            if !inserted {
-                                equipment_state.nodes.push(node);
-                            }
-                        }
-                    }
-                }
+                *move_error = equipment_state
+                    .move_nodes(dragging_ids, *target_id, position)
+                    .err();
             }
         });
     });
 
-    // No central panel needed - game renders in the background
-    // This allows clicks to reach the game without being intercepted by egui
-}
-
-// Helper methods for EquipmentTreeNode to support drag-drop
-impl EquipmentTreeNode {
-    fn remove_node(parent: &mut EquipmentTreeNode, id: usize) -> Option<EquipmentTreeNode> {
-        // Check direct children
-        if let Some(idx) = parent.children.iter().position(|n| n.id == id) {
-            return Some(parent.children.remove(idx));
-        }
-
-        // Search recursively
-        for child in &mut parent.children {
-            if let Some(node) = Self::remove_node(child, id) {
-                return Some(node);
+    // New World dialog: regenerate the map from a typed or random seed
+    let mut new_world_open = world_seed.dialog_open;
+    egui::Window::new("New World")
+        .open(&mut new_world_open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!("Current seed: {}", world_seed.seed));
+            ui.horizontal(|ui| {
+                ui.label("Seed:");
+                ui.text_edit_singleline(&mut world_seed.input);
+                if ui.button("🎲").on_hover_text("Random seed").clicked() {
+                    world_seed.input = thread_rng().gen::<u32>().to_string();
+                }
+            });
+            let mut symmetry = world_seed.symmetry;
+            egui::ComboBox::from_label("Symmetry")
+                .selected_text(symmetry.name())
+                .show_ui(ui, |ui| {
+                    for option in MapSymmetry::ALL {
+                        ui.selectable_value(&mut symmetry, option, option.name());
+                    }
+                });
+            world_seed.symmetry = symmetry;
+            let mut preset = world_seed.preset;
+            egui::ComboBox::from_label("Preset")
+                .selected_text(preset.name())
+                .show_ui(ui, |ui| {
+                    for option in WorldGenPreset::ALL {
+                        ui.selectable_value(&mut preset, option, option.name());
+                    }
+                });
+            world_seed.preset = preset;
+            let mut size = world_seed.size;
+            egui::ComboBox::from_label("Size")
+                .selected_text(format!("{0}x{0}", size))
+                .show_ui(ui, |ui| {
+                    for option in MAP_SIZES {
+                        ui.selectable_value(&mut size, option, format!("{0}x{0}", option));
+                    }
+                });
+            world_seed.size = size;
+            if size > 1024 {
+                ui.small("Large maps take a while to generate and use a lot of memory.");
             }
-        }
 
-        None
+            let seed = world_seed.input.trim().parse::<u32>();
+            if seed.is_err() {
+                ui.colored_label(egui::Color32::RED, "Seed must be a whole number from 0 to 4294967295");
+            }
+            ui.small("Units and zones are kept; mining progress on the old map is cleared.");
+            ui.separator();
+            if ui.add_enabled(seed.is_ok(), egui::Button::new("Generate")).clicked() {
+                if let Ok(seed) = seed {
+                    game_commands.write(GameCommand::NewWorld(
+                        seed,
+                        world_seed.symmetry,
+                        world_seed.preset,
+                        world_seed.size,
+                    ));
+                    world_seed.dialog_open = false;
+                }
+            }
+            ui.separator();
+            ui.label("Or play on an image (grayscale heightmap, or a map rendered by regolith-cli):");
+            ui.horizontal(|ui| {
+                ui.label("PNG:");
+                ui.text_edit_singleline(&mut world_seed.image_path);
+            });
+            let has_path = !world_seed.image_path.trim().is_empty();
+            if ui.add_enabled(has_path, egui::Button::new("Load Map Image…")).clicked() {
+                game_commands.write(GameCommand::LoadMapImage);
+                world_seed.dialog_open = false;
+            }
+        });
+    if !new_world_open {
+        world_seed.dialog_open = false;
     }
 
-    fn insert_node(
-        parent: &mut EquipmentTreeNode,
-        target_id: usize,
-        node: EquipmentTreeNode,
-        position: DropPosition,
-    ) -> bool {
-        // If this is the target
-        if parent.id == target_id {
-            match position {
-                DropPosition::Inside => {
-                    if parent.is_container() {
-                        parent.children.push(node);
-                        return true;
+    // Batch rename dialog for the current multi-selection
+    let mut dialog_open = rename_dialog.open;
+    egui::Window::new("Batch Rename")
+        .open(&mut dialog_open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Template:");
+                ui.text_edit_singleline(&mut rename_dialog.template);
+            });
+            ui.small("Placeholders: {type} {container} {index} {n} {id}, e.g. {index:03}");
+            ui.separator();
+
+            let ids: Vec<usize> = equipment_state
+                .all_ids()
+                .into_iter()
+                .filter(|id| equipment_actions.selected.contains(id))
+                .collect();
+            let renames = equipment_state.preview_batch_rename(&ids, &rename_dialog.template);
+            let errors = equipment_state.validate_batch_rename(&renames);
+
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                for (id, new_name) in &renames {
+                    if let Some(node) = equipment_state.find_node(*id) {
+                        ui.label(format!("{} → {}", node.name, new_name));
                     }
                 }
-                _ => {
-                    // Can't insert before/after root
-                    return false;
-                }
+            });
+
+            for error in &errors {
+                ui.colored_label(egui::Color32::RED, error);
             }
-        }
 
-        // Check if target is in direct children
-        if let Some(idx) = parent.children.iter().position(|n| n.id == target_id) {
-            match position {
-                DropPosition::Before => {
-                    parent.children.insert(idx, node);
-                    return true;
-                }
-                DropPosition::After => {
-                    parent.children.insert(idx + 1, node);
-                    return true;
-                }
-                DropPosition::Inside => {
-                    if parent.children[idx].is_container() {
-                        parent.children[idx].children.push(node);
-                        return true;
+            ui.separator();
+            if ui
+                .add_enabled(errors.is_empty() && !renames.is_empty(), egui::Button::new("Apply"))
+                .clicked()
+            {
+                for (id, new_name) in renames {
+                    if let Some(node) = equipment_state.find_node_mut(id) {
+                        node.name = new_name;
                     }
                 }
+                rename_dialog.open = false;
             }
-        }
-
-        // Search recursively
-        for child in &mut parent.children {
-            if Self::insert_node(child, target_id, node.clone(), position) {
-                return true;
-            }
-        }
-
-        false
+        });
+    if !dialog_open {
+        rename_dialog.open = false;
     }
+
+    // No central panel needed - game renders in the background
+    // This allows clicks to reach the game without being intercepted by egui
 }
 
 // System to manage selection outlines for selected equipment
@@ -1215,3 +7588,209 @@ fn update_selection_outlines(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::BTreeSet;
+
+    // Tree edits as the UI performs them. Node references are indices into
+    // the current ID list so they always land on a node that exists.
+    #[derive(Debug, Clone)]
+    enum TreeOp {
+        AddContainer,
+        AddEquipment,
+        Remove(usize),
+        Rename(usize, String),
+        Move(Vec<usize>, usize, u8),
+    }
+
+    fn tree_op() -> impl Strategy<Value = TreeOp> {
+        prop_oneof![
+            Just(TreeOp::AddContainer),
+            Just(TreeOp::AddEquipment),
+            any::<usize>().prop_map(TreeOp::Remove),
+            (any::<usize>(), "[a-z ]{0,8}").prop_map(|(idx, name)| TreeOp::Rename(idx, name)),
+            (prop::collection::vec(any::<usize>(), 1..4), any::<usize>(), 0u8..3)
+                .prop_map(|(dragged, target, position)| TreeOp::Move(dragged, target, position)),
+        ]
+    }
+
+    fn drop_position(position: u8) -> DropPosition {
+        match position {
+            0 => DropPosition::Before,
+            1 => DropPosition::After,
+            _ => DropPosition::Inside,
+        }
+    }
+
+    // Every node with its parent, in display order
+    fn layout(state: &EquipmentTreeState) -> Vec<(usize, Option<usize>)> {
+        state
+            .all_ids()
+            .into_iter()
+            .map(|id| (id, state.find_parent(id).map(|parent| parent.id)))
+            .collect()
+    }
+
+    fn pick(ids: &[usize], idx: usize) -> Option<usize> {
+        (!ids.is_empty()).then(|| ids[idx % ids.len()])
+    }
+
+    proptest! {
+        #[test]
+        fn tree_ops_never_lose_or_duplicate_ids(ops in prop::collection::vec(tree_op(), 0..40)) {
+            let mut state = EquipmentTreeState::default();
+            let mut expected: BTreeSet<usize> = state.all_ids().into_iter().collect();
+
+            for op in ops {
+                let ids = state.all_ids();
+                match op {
+                    TreeOp::AddContainer => {
+                        expected.insert(state.next_id);
+                        state.add_container("Group".to_string());
+                    }
+                    TreeOp::AddEquipment => {
+                        expected.insert(state.add_equipment("Unit".to_string(), EquipmentType::Sampler));
+                    }
+                    TreeOp::Remove(idx) => {
+                        if let Some(id) = pick(&ids, idx) {
+                            let removed = state.take_node(id).expect("listed node can be taken");
+                            let mut removed_ids = Vec::new();
+                            removed.collect_ids(&mut removed_ids);
+                            for removed_id in removed_ids {
+                                prop_assert!(expected.remove(&removed_id));
+                            }
+                        }
+                    }
+                    TreeOp::Rename(idx, name) => {
+                        if let Some(id) = pick(&ids, idx) {
+                            prop_assert!(state.rename_node(id, name.clone()));
+                            prop_assert_eq!(&state.find_node(id).unwrap().name, &name);
+                        }
+                    }
+                    TreeOp::Move(dragged, target, position) => {
+                        if let Some(target_id) = pick(&ids, target) {
+                            let dragged: Vec<usize> = dragged.iter().map(|idx| ids[idx % ids.len()]).collect();
+                            let position = drop_position(position);
+                            let before = layout(&state);
+
+                            match state.move_nodes(&dragged, target_id, position) {
+                                Ok(()) => {
+                                    // Undo restores the exact layout, then redo the move
+                                    prop_assert!(state.undo_move());
+                                    prop_assert_eq!(layout(&state), before);
+                                    prop_assert!(state.move_nodes(&dragged, target_id, position).is_ok());
+                                }
+                                Err(_) => prop_assert_eq!(layout(&state), before),
+                            }
+                        }
+                    }
+                }
+
+                let ids = state.all_ids();
+                let unique: BTreeSet<usize> = ids.iter().copied().collect();
+                prop_assert_eq!(unique.len(), ids.len(), "duplicate node ids: {:?}", ids);
+                prop_assert_eq!(&unique, &expected);
+                prop_assert!(ids.iter().all(|id| *id < state.next_id));
+            }
+        }
+    }
+
+    #[test]
+    fn container_cannot_be_dropped_into_its_descendant() {
+        let mut state = EquipmentTreeState::default();
+        state.add_container("Outer".to_string());
+        let outer = state.next_id - 1;
+        state.add_container("Inner".to_string());
+        let inner = state.next_id - 1;
+        state.move_nodes(&[inner], outer, DropPosition::Inside).unwrap();
+
+        let before = layout(&state);
+        assert_eq!(
+            state.move_nodes(&[outer], inner, DropPosition::Inside),
+            Err(TreeMoveError::IntoOwnDescendant(outer))
+        );
+        assert_eq!(layout(&state), before);
+    }
+
+    #[test]
+    fn root_level_drop_keeps_position() {
+        let mut state = EquipmentTreeState::default();
+        let unit = state.add_equipment("Unit".to_string(), EquipmentType::Transport);
+        let first = state.nodes[0].id;
+
+        state.move_nodes(&[unit], first, DropPosition::Before).unwrap();
+        assert_eq!(state.nodes[0].id, unit);
+
+        assert!(state.undo_move());
+        assert_eq!(state.nodes.last().map(|node| node.id), Some(unit));
+    }
+
+    #[test]
+    fn removing_waypoints_keeps_routes_on_their_stop() {
+        let mut route = Route {
+            waypoints: vec![10, 11, 12],
+            mode: RouteMode::Patrol,
+            next: 2,
+        };
+        route.remove_at(0);
+        assert_eq!(route.waypoints[route.next], 12);
+
+        // A patrol losing the stop it was heading to, the last, wraps to the first
+        route.remove_at(1);
+        assert_eq!(route.next, 0);
+        assert!(!route.is_complete());
+
+        // A finished one-shot route stays finished
+        let mut route = Route {
+            waypoints: vec![10, 11],
+            mode: RouteMode::OneShot,
+            next: 2,
+        };
+        route.remove_at(1);
+        assert!(route.is_complete());
+    }
+
+    #[test]
+    fn dispatcher_claims_follow_assignments() {
+        let job = |x| MiningJob {
+            cell: UVec2::new(x, 0),
+            zone_id: None,
+            progress: 0.0,
+        };
+        let mut dispatcher = MiningDispatcher::default();
+        dispatcher.assign(1, job(5));
+        dispatcher.assign(2, job(5));
+        dispatcher.assign(1, job(6));
+        assert!(dispatcher.is_claimed(UVec2::new(5, 0)), "still claimed by the second miner");
+
+        dispatcher.unassign(2);
+        assert!(!dispatcher.is_claimed(UVec2::new(5, 0)));
+        dispatcher.retain_jobs(|_, job| job.cell.x != 6);
+        assert!(!dispatcher.is_claimed(UVec2::new(6, 0)));
+        assert!(dispatcher.claimed.is_empty());
+    }
+
+    #[test]
+    fn planned_units_keep_only_their_latest_order() {
+        let formation = |ids: &[usize]| FormationMove {
+            targets: ids.iter().map(|&id| (id, Vec2::ZERO)).collect(),
+            unit_count: ids.len(),
+            speed: 1.0,
+            initial_distance: 0.0,
+            remaining_distance: 0.0,
+        };
+        let mut planning = PlanningMode::default();
+        planning.queue_dig(1, UVec2::ZERO);
+        planning.queue_move(formation(&[1, 2]));
+        assert!(planning.digs.is_empty());
+
+        planning.queue_dig(2, UVec2::ONE);
+        assert_eq!(planning.moves[0].targets.keys().collect::<Vec<_>>(), [&1]);
+        planning.queue_dig(1, UVec2::ONE);
+        assert!(planning.moves.is_empty());
+        assert_eq!(planning.order_count(), 2);
+    }
+}
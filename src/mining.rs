@@ -0,0 +1,99 @@
+// Digging rules shared by the game and the training environment: which
+// cells a miner reaches, and how much a worked region still yields.
+
+use crate::world::{MineralMap, MineralType};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+const REGION_CELLS: u32 = 32; // Side of the square regions depletion is tracked over
+const DEPLETION_HALF_YIELD: f32 = 100.0; // Extracted density at which a region's yield halves
+
+// The kinds of miner, by the depths and area they dig
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MinerKind {
+    Surface,
+    Deep,
+}
+
+impl MinerKind {
+    /// Radius in cells dug out around the target cell
+    pub fn mining_radius(&self) -> u32 {
+        match self {
+            MinerKind::Surface => 2,
+            MinerKind::Deep => 0,
+        }
+    }
+
+    /// Whether this miner can dig at a given map row.
+    /// Surface miners only reach the upper half of the map.
+    pub fn can_mine_row(&self, y: u32, map_height: usize) -> bool {
+        match self {
+            MinerKind::Surface => (y as usize) < map_height / 2,
+            MinerKind::Deep => true,
+        }
+    }
+}
+
+/// Unmined cells a miner would dig out when working the given cell,
+/// limited by its mining radius and the depths it can reach
+pub fn mining_footprint(map: &MineralMap, miner: MinerKind, center: UVec2) -> Vec<UVec2> {
+    let radius = miner.mining_radius() as i32;
+    let mut cells = Vec::new();
+    for dy in -radius..=radius {
+        for dx in -radius..=radius {
+            if dx * dx + dy * dy > radius * radius {
+                continue;
+            }
+            let x = center.x as i32 + dx;
+            let y = center.y as i32 + dy;
+            if x < 0 || y < 0 || !miner.can_mine_row(y as u32, map.height) {
+                continue;
+            }
+            if map.get(x as usize, y as usize).is_some_and(|cell| !cell.mined) {
+                cells.push(UVec2::new(x as u32, y as u32));
+            }
+        }
+    }
+    cells
+}
+
+/// Dig out one cell, returning its mineral and the amount it yielded after
+/// depletion. None when the cell is off the map, already dug, or magma,
+/// which miners leave alone.
+pub fn dig_cell(map: &mut MineralMap, depletion: &mut RegionDepletion, position: UVec2) -> Option<(MineralType, f32)> {
+    let cell = map.get_mut(position.x as usize, position.y as usize)?;
+    if cell.mined || cell.mineral_type == MineralType::Magma {
+        return None;
+    }
+    cell.mined = true;
+    if cell.mineral_type == MineralType::Empty {
+        return Some((MineralType::Empty, 0.0));
+    }
+    let amount = cell.density * depletion.yield_factor(position);
+    depletion.record(position, cell.density);
+    Some((cell.mineral_type, amount))
+}
+
+// Cumulative extraction per map region. Later extraction from the same
+// region yields less, so mining has to spread outward.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct RegionDepletion {
+    pub extracted: HashMap<UVec2, f32>,
+}
+
+impl RegionDepletion {
+    fn region(cell: UVec2) -> UVec2 {
+        cell / REGION_CELLS
+    }
+
+    /// Yield multiplier for a cell's region: 1.0 when untouched, falling off
+    /// smoothly as more is extracted
+    pub fn yield_factor(&self, cell: UVec2) -> f32 {
+        let extracted = self.extracted.get(&Self::region(cell)).copied().unwrap_or(0.0);
+        1.0 / (1.0 + extracted / DEPLETION_HALF_YIELD)
+    }
+
+    pub fn record(&mut self, cell: UVec2, amount: f32) {
+        *self.extracted.entry(Self::region(cell)).or_default() += amount;
+    }
+}
@@ -0,0 +1,250 @@
+// Remote control over a local socket (feature "remote").
+//
+// External tools, bots and experiments drive the game with the same
+// GameCommands as the menus and the command palette, one per line:
+//   $ nc 127.0.0.1 7879
+//   help
+//   spawn surface-mining home-base
+//   ok spawn surface-mining home-base
+// Each line is answered with `ok <command>` once queued, or `error <reason>`.
+// The address can be changed with the REGOLITH_REMOTE_ADDR environment variable.
+
+use super::{EquipmentType, GameCommand, SpawnPolicy, UiPanel};
+use bevy::prelude::*;
+use regolith_voxel::world::{MapSymmetry, WorldGenPreset, MAP_SIZES};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7879";
+
+pub struct RemotePlugin;
+
+impl Plugin for RemotePlugin {
+    fn build(&self, app: &mut App) {
+        let (sender, receiver) = channel();
+        let addr = std::env::var("REGOLITH_REMOTE_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+
+        match TcpListener::bind(&addr) {
+            Ok(listener) => {
+                info!("Remote control listening on {}", addr);
+                std::thread::spawn(move || serve(listener, sender));
+            }
+            Err(err) => warn!("Remote control disabled, could not bind {}: {}", addr, err),
+        }
+
+        app.insert_resource(RemoteCommands(Mutex::new(receiver)))
+            .add_systems(Update, forward_remote_commands.before(super::apply_game_commands));
+    }
+}
+
+// Resource receiving the commands decoded by the connection threads
+#[derive(Resource)]
+struct RemoteCommands(Mutex<Receiver<GameCommand>>);
+
+fn serve(listener: TcpListener, sender: Sender<GameCommand>) {
+    for stream in listener.incoming().flatten() {
+        let sender = sender.clone();
+        // One thread per client so a long-lived bot doesn't block others
+        std::thread::spawn(move || {
+            if let Err(err) = handle_client(stream, sender) {
+                debug!("Remote client disconnected: {}", err);
+            }
+        });
+    }
+}
+
+fn handle_client(stream: TcpStream, sender: Sender<GameCommand>) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "help" {
+            for command in GameCommand::palette_entries() {
+                writeln!(writer, "{}", encode(&command))?;
+            }
+            writeln!(writer, "center-on <x> <y>")?;
+            writeln!(writer, "new-world <seed> <symmetry> <preset> <size>")?;
+            writeln!(writer, "ok help")?;
+            continue;
+        }
+        match decode(line) {
+            Ok(command) => {
+                if sender.send(command).is_err() {
+                    // The game is shutting down
+                    return Ok(());
+                }
+                writeln!(writer, "ok {}", encode(&command))?;
+            }
+            Err(reason) => writeln!(writer, "error {}", reason)?,
+        }
+    }
+    Ok(())
+}
+
+fn forward_remote_commands(remote: Res<RemoteCommands>, mut game_commands: EventWriter<GameCommand>) {
+    let Ok(receiver) = remote.0.lock() else {
+        return;
+    };
+    for command in receiver.try_iter() {
+        game_commands.write(command);
+    }
+}
+
+/// Names on the wire are the display names in lowercase kebab-case
+fn slug(name: &str) -> String {
+    name.to_lowercase().replace(' ', "-")
+}
+
+fn spawn_policy_name(policy: SpawnPolicy) -> &'static str {
+    match policy {
+        SpawnPolicy::Random => "random",
+        SpawnPolicy::CameraCenter => "camera-center",
+        SpawnPolicy::HomeBase => "home-base",
+        SpawnPolicy::Placement => "placement",
+    }
+}
+
+/// The wire form of a command: a keyword followed by its arguments
+fn encode(command: &GameCommand) -> String {
+    match command {
+        GameCommand::Spawn(equipment_type, policy) => {
+            let mut line = format!("spawn {}", slug(equipment_type.name()));
+            if let Some(policy) = policy {
+                line = format!("{} {}", line, spawn_policy_name(*policy));
+            }
+            line
+        }
+        GameCommand::TogglePanel(panel) => format!("toggle-panel {}", slug(panel.name())),
+        GameCommand::ToggleMiningOverlay => "toggle-mining-overlay".to_string(),
+        GameCommand::ToggleTemperatureOverlay => "toggle-temperature-overlay".to_string(),
+        GameCommand::ToggleZoneDesignation => "toggle-zone-designation".to_string(),
+        GameCommand::ToggleHighContrast => "toggle-high-contrast".to_string(),
+        GameCommand::JumpToHomeBase => "jump-to-home-base".to_string(),
+        GameCommand::JumpToSelected => "jump-to-selected".to_string(),
+        GameCommand::JumpToLatestEvent => "jump-to-latest-event".to_string(),
+        GameCommand::TogglePause => "toggle-pause".to_string(),
+        GameCommand::SetSpeed(speed) => format!("set-speed {}", speed),
+        GameCommand::CenterOn(position) => format!("center-on {} {}", position.x, position.y),
+        GameCommand::NavigateBack => "navigate-back".to_string(),
+        GameCommand::TogglePlanning => "toggle-planning".to_string(),
+        GameCommand::NewWorld(seed, symmetry, preset, size) => {
+            format!("new-world {} {} {} {}", seed, symmetry.name(), preset.name(), size)
+        }
+        GameCommand::LoadMapImage => "load-map-image".to_string(),
+        GameCommand::ToggleSpectator => "toggle-spectator".to_string(),
+    }
+}
+
+/// Parse the wire form of a command
+fn decode(line: &str) -> Result<GameCommand, String> {
+    let mut words = line.split_whitespace();
+    let keyword = words.next().ok_or("empty command")?;
+    let args: Vec<&str> = words.collect();
+    let number = |index: usize| -> Result<f32, String> {
+        let word = args.get(index).ok_or(format!("{} needs more arguments", keyword))?;
+        word.parse().map_err(|_| format!("'{}' is not a number", word))
+    };
+
+    let command = match keyword {
+        "spawn" => {
+            let name = args.first().ok_or("spawn needs an equipment type")?;
+            let equipment_type = EquipmentType::ALL
+                .into_iter()
+                .find(|equipment_type| slug(equipment_type.name()) == *name)
+                .ok_or(format!("unknown equipment type '{}'", name))?;
+            let policy = match args.get(1) {
+                Some(name) => Some(
+                    [SpawnPolicy::Random, SpawnPolicy::CameraCenter, SpawnPolicy::HomeBase, SpawnPolicy::Placement]
+                        .into_iter()
+                        .find(|&policy| spawn_policy_name(policy) == *name)
+                        .ok_or(format!("unknown spawn policy '{}'", name))?,
+                ),
+                None => None,
+            };
+            GameCommand::Spawn(equipment_type, policy)
+        }
+        "toggle-panel" => {
+            let name = args.first().ok_or("toggle-panel needs a panel")?;
+            let panel = UiPanel::ALL
+                .into_iter()
+                .find(|panel| slug(panel.name()) == *name)
+                .ok_or(format!("unknown panel '{}'", name))?;
+            GameCommand::TogglePanel(panel)
+        }
+        "toggle-mining-overlay" => GameCommand::ToggleMiningOverlay,
+        "toggle-temperature-overlay" => GameCommand::ToggleTemperatureOverlay,
+        "toggle-zone-designation" => GameCommand::ToggleZoneDesignation,
+        "toggle-high-contrast" => GameCommand::ToggleHighContrast,
+        "jump-to-home-base" => GameCommand::JumpToHomeBase,
+        "jump-to-selected" => GameCommand::JumpToSelected,
+        "jump-to-latest-event" => GameCommand::JumpToLatestEvent,
+        "toggle-pause" => GameCommand::TogglePause,
+        "set-speed" => {
+            let speed = number(0)?;
+            if !(speed > 0.0 && speed <= 16.0) {
+                return Err(format!("speed {} is out of range", speed));
+            }
+            GameCommand::SetSpeed(speed)
+        }
+        "center-on" => GameCommand::CenterOn(Vec2::new(number(0)?, number(1)?)),
+        "navigate-back" => GameCommand::NavigateBack,
+        "toggle-planning" => GameCommand::TogglePlanning,
+        "new-world" => {
+            let [seed, symmetry, preset, size] = args.as_slice() else {
+                return Err("new-world needs a seed, symmetry, preset and size".to_string());
+            };
+            let seed = seed.parse().map_err(|_| format!("invalid seed '{}'", seed))?;
+            let symmetry = MapSymmetry::from_name(symmetry).ok_or(format!("unknown symmetry '{}'", symmetry))?;
+            let preset = WorldGenPreset::from_name(preset).ok_or(format!("unknown preset '{}'", preset))?;
+            let size = size
+                .parse()
+                .ok()
+                .filter(|size| MAP_SIZES.contains(size))
+                .ok_or(format!("unsupported size '{}'", size))?;
+            GameCommand::NewWorld(seed, symmetry, preset, size)
+        }
+        // The image path lives in the New World dialog, so loading is local only
+        "load-map-image" => return Err("load-map-image is only available in the game".to_string()),
+        "toggle-spectator" => GameCommand::ToggleSpectator,
+        _ => return Err(format!("unknown command '{}' (try 'help')", keyword)),
+    };
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(command: GameCommand) {
+        let line = encode(&command);
+        assert_eq!(decode(&line), Ok(command), "'{}' did not decode back", line);
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        for command in GameCommand::palette_entries() {
+            round_trip(command);
+        }
+        for policy in [SpawnPolicy::Random, SpawnPolicy::CameraCenter, SpawnPolicy::HomeBase, SpawnPolicy::Placement] {
+            round_trip(GameCommand::Spawn(EquipmentType::Sampler, Some(policy)));
+        }
+        round_trip(GameCommand::CenterOn(Vec2::new(-120.5, 3000.0)));
+        for symmetry in MapSymmetry::ALL {
+            for preset in WorldGenPreset::ALL {
+                for size in MAP_SIZES {
+                    round_trip(GameCommand::NewWorld(42, symmetry, preset, size));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn load_map_image_is_refused() {
+        assert!(decode(&encode(&GameCommand::LoadMapImage)).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+// Rare hand-authored structures stamped into the map after generation.
+// New structures are added by registering a template; the generator core
+// only runs the registry's placement pass.
+
+use crate::world::{Biome, MineralCell, MineralMap, MineralType, Stratum};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const PLACEMENT_ATTEMPTS: usize = 200; // Random sites tried per structure before giving up
+
+// What a template character stamps into a cell
+#[derive(Debug, Clone, Copy)]
+pub struct StructureCell {
+    pub symbol: char,
+    pub mineral: MineralType,
+    pub density: f32,
+}
+
+// A structure as rows of symbols; any symbol missing from the legend leaves
+// the generated cell untouched
+#[derive(Debug, Clone)]
+pub struct StructureTemplate {
+    pub name: &'static str,
+    pub pattern: &'static [&'static str],
+    pub legend: &'static [StructureCell],
+    pub strata: &'static [Stratum], // Strata the structure's center may sit in
+    pub biome: Option<Biome>,       // Biome the center must be in, if any
+    pub count: usize,               // How many to place on a map of the default size
+}
+
+impl StructureTemplate {
+    fn size(&self) -> (usize, usize) {
+        let width = self.pattern.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        (width, self.pattern.len())
+    }
+
+    fn fits(&self, cell: &MineralCell) -> bool {
+        self.strata.contains(&cell.stratum) && self.biome.is_none_or(|biome| cell.biome == biome)
+    }
+}
+
+// A hollow geode lined with diamond
+const DIAMOND_GEODE: StructureTemplate = StructureTemplate {
+    name: "Diamond geode",
+    pattern: &[
+        "...sss...",
+        ".ssddds..",
+        ".sd   ds.",
+        "sd     ds",
+        "sd     ds",
+        "sd     ds",
+        ".sd   ds.",
+        "..sddds..",
+        "...sss...",
+    ],
+    legend: &[
+        StructureCell { symbol: 's', mineral: MineralType::Silver, density: 0.7 },
+        StructureCell { symbol: 'd', mineral: MineralType::Diamond, density: 1.0 },
+        StructureCell { symbol: ' ', mineral: MineralType::Empty, density: 0.0 },
+    ],
+    strata: &[Stratum::Bedrock],
+    biome: None,
+    count: 3,
+};
+
+// A dense uranium pocket in an iron-rich shell
+const URANIUM_POCKET: StructureTemplate = StructureTemplate {
+    name: "Uranium pocket",
+    pattern: &[
+        "..iii..",
+        ".iuuui.",
+        "iuuUuui",
+        "iuUUUui",
+        "iuuUuui",
+        ".iuuui.",
+        "..iii..",
+    ],
+    legend: &[
+        StructureCell { symbol: 'i', mineral: MineralType::Iron, density: 0.8 },
+        StructureCell { symbol: 'u', mineral: MineralType::Uranium, density: 0.85 },
+        StructureCell { symbol: 'U', mineral: MineralType::Uranium, density: 1.0 },
+    ],
+    strata: &[Stratum::Sedimentary, Stratum::Bedrock],
+    biome: None,
+    count: 4,
+};
+
+// A frozen lake bed with gold settled on the bottom
+const FROZEN_GOLD_LAKE: StructureTemplate = StructureTemplate {
+    name: "Frozen gold lake",
+    pattern: &[
+        "....eeeeeee....",
+        "..eeggggggggee.",
+        ".eggggGGGgggge.",
+        "eggggGGGGGGggge",
+        ".egggGGGGGgggge",
+        "..eegggggggee..",
+        "....eeeeeee....",
+    ],
+    legend: &[
+        StructureCell { symbol: 'e', mineral: MineralType::Empty, density: 0.0 },
+        StructureCell { symbol: 'g', mineral: MineralType::Gold, density: 0.8 },
+        StructureCell { symbol: 'G', mineral: MineralType::Gold, density: 1.0 },
+    ],
+    strata: &[Stratum::Regolith, Stratum::Sedimentary],
+    biome: Some(Biome::IceFields),
+    count: 2,
+};
+
+// The structures placed on generated maps
+pub struct StructureRegistry {
+    templates: Vec<StructureTemplate>,
+}
+
+impl Default for StructureRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(DIAMOND_GEODE);
+        registry.register(URANIUM_POCKET);
+        registry.register(FROZEN_GOLD_LAKE);
+        registry
+    }
+}
+
+impl StructureRegistry {
+    pub fn empty() -> Self {
+        Self { templates: Vec::new() }
+    }
+
+    pub fn register(&mut self, template: StructureTemplate) {
+        self.templates.push(template);
+    }
+
+    pub fn templates(&self) -> &[StructureTemplate] {
+        &self.templates
+    }
+
+    /// Stamp every registered structure into the map at seeded sites. Sites
+    /// are picked on the generated side of a symmetric map and copied to
+    /// its mirror images. Each placement is recorded in `map.structures`.
+    pub fn place(&self, map: &mut MineralMap) {
+        let _span = info_span!("worldgen_structures").entered();
+        let mut rng = StdRng::seed_from_u64(u64::from(map.seed) ^ 0x5EED_57C7);
+        let mut occupied: Vec<URect> = Vec::new();
+
+        for template in &self.templates {
+            let (width, height) = template.size();
+            if width == 0 || width >= map.width || height >= map.height {
+                continue;
+            }
+            for _ in 0..map.scaled_count(template.count) {
+                let site = (0..PLACEMENT_ATTEMPTS).find_map(|_| {
+                    let x = rng.gen_range(0..map.width - width);
+                    let y = rng.gen_range(0..map.height - height);
+                    let (cx, cy) = (x + width / 2, y + height / 2);
+                    let on_generated_side = [(x, y), (x + width - 1, y + height - 1)]
+                        .iter()
+                        .all(|&(x, y)| map.symmetry.source(x, y, map.width, map.height).is_none());
+                    let fits = map.get(cx, cy).is_some_and(|cell| template.fits(cell));
+                    let area = URect::new(x as u32, y as u32, (x + width) as u32, (y + height) as u32);
+                    let free = occupied.iter().all(|other| other.intersect(area).is_empty());
+                    (on_generated_side && fits && free).then_some((x, y, area))
+                });
+                let Some((x, y, area)) = site else {
+                    break;
+                };
+                occupied.push(area);
+                stamp(map, template, x, y);
+                let center = UVec2::new((x + width / 2) as u32, (y + height / 2) as u32);
+                map.structures.push((template.name, center));
+            }
+        }
+    }
+}
+
+fn stamp(map: &mut MineralMap, template: &StructureTemplate, left: usize, top: usize) {
+    for (dy, row) in template.pattern.iter().enumerate() {
+        for (dx, symbol) in row.chars().enumerate() {
+            let Some(stamp) = template.legend.iter().find(|cell| cell.symbol == symbol) else {
+                continue;
+            };
+            let (x, y) = (left + dx, top + dy);
+            let mut targets = map.symmetry.images(x, y, map.width, map.height);
+            targets.push((x, y));
+            for (x, y) in targets {
+                if let Some(cell) = map.get_mut(x, y) {
+                    cell.mineral_type = stamp.mineral;
+                    cell.density = stamp.density;
+                }
+            }
+        }
+    }
+}
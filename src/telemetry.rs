@@ -0,0 +1,144 @@
+// Read-only JSON telemetry endpoint for external dashboards (feature "telemetry").
+//
+// Serves the latest simulation snapshot over plain HTTP:
+//   curl http://127.0.0.1:7878/stats
+// The address can be changed with the REGOLITH_TELEMETRY_ADDR environment variable.
+
+use super::{format_clock, EquipmentTreeState, EventLog, Stockpile};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(1);
+const FEED_LENGTH: usize = 20;
+
+pub struct TelemetryPlugin;
+
+impl Plugin for TelemetryPlugin {
+    fn build(&self, app: &mut App) {
+        let snapshot = Arc::new(Mutex::new(String::from("{}")));
+        let addr = std::env::var("REGOLITH_TELEMETRY_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+
+        match TcpListener::bind(&addr) {
+            Ok(listener) => {
+                info!("Telemetry listening on http://{}/stats", addr);
+                let shared = snapshot.clone();
+                std::thread::spawn(move || serve(listener, shared));
+            }
+            Err(err) => warn!("Telemetry disabled, could not bind {}: {}", addr, err),
+        }
+
+        app.insert_resource(TelemetrySnapshot {
+            json: snapshot,
+            previous_stock: None,
+        })
+        .add_systems(Update, update_snapshot.run_if(on_timer(SNAPSHOT_INTERVAL)));
+    }
+}
+
+// Resource sharing the latest snapshot with the server thread
+#[derive(Resource)]
+struct TelemetrySnapshot {
+    json: Arc<Mutex<String>>,
+    previous_stock: Option<(f32, f32)>, // (elapsed seconds, total stockpile) at the last snapshot
+}
+
+fn serve(listener: TcpListener, snapshot: Arc<Mutex<String>>) {
+    for stream in listener.incoming().flatten() {
+        if let Err(err) = respond(stream, &snapshot) {
+            debug!("Telemetry request failed: {}", err);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, snapshot: &Mutex<String>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+
+    // Read-only: GET /stats is the only thing served
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/stats")) => {
+            let json = snapshot.lock().map(|json| json.clone()).unwrap_or_default();
+            ("200 OK", json)
+        }
+        (Some("GET"), _) => ("404 Not Found", r#"{"error":"not found"}"#.to_string()),
+        _ => ("405 Method Not Allowed", r#"{"error":"read-only"}"#.to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nAccess-Control-Allow-Origin: *\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+fn update_snapshot(
+    time: Res<Time>,
+    mut snapshot: ResMut<TelemetrySnapshot>,
+    equipment_state: Res<EquipmentTreeState>,
+    stockpile: Res<Stockpile>,
+    event_log: Res<EventLog>,
+) {
+    let elapsed = time.elapsed_secs();
+    let (working_units, idle_units, disabled_units) = equipment_state.equipment_counts();
+    let active_units = working_units + idle_units;
+    let total_units = active_units + disabled_units;
+
+    let mut reserves: Vec<_> = stockpile.amounts.iter().collect();
+    reserves.sort_by_key(|(mineral, _)| mineral.name());
+    let reserves = reserves
+        .iter()
+        .map(|(mineral, amount)| format!("\"{}\":{:.2}", mineral.name(), amount))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    // Production rate from the change in total stock since the last snapshot
+    let total_stock: f32 = stockpile.amounts.values().sum();
+    let per_minute = match snapshot.previous_stock {
+        Some((previous_time, previous_stock)) if elapsed > previous_time => {
+            (total_stock - previous_stock) / (elapsed - previous_time) * 60.0
+        }
+        _ => 0.0,
+    };
+    snapshot.previous_stock = Some((elapsed, total_stock));
+
+    let skip = event_log.entries.len().saturating_sub(FEED_LENGTH);
+    let events = event_log.entries[skip..]
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"time\":\"{}\",\"message\":\"{}\"}}",
+                format_clock(entry.time),
+                escape_json(&entry.message)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let json = format!(
+        "{{\"elapsed\":{elapsed:.1},\"units\":{{\"total\":{total_units},\"active\":{active_units},\"working\":{working_units},\"idle\":{idle_units},\"disabled\":{disabled_units}}},\"reserves\":{{{reserves}}},\"production_per_minute\":{per_minute:.2},\"events\":[{events}]}}"
+    );
+    if let Ok(mut shared) = snapshot.json.lock() {
+        *shared = json;
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
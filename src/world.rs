@@ -0,0 +1,1356 @@
+// World model: the mineral map, its cells, and procedural generation.
+// Shared by the game and the command-line tool.
+
+use bevy::prelude::*;
+use crate::generator::{NoiseGenerator, WorldGenerator};
+use image::imageops::FilterType;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_MAP_SIZE: usize = 512; // Cells on a side of a square map
+pub const MAP_SIZES: [usize; 5] = [256, 512, 1024, 2048, 4096]; // Sizes offered for new maps
+pub const MAP_SCALE: f32 = 2.0; // World units per map cell
+const SEAM_BLEND_CELLS: f64 = 12.0; // Width of the blend band along symmetry seams
+const CRATER_RADIUS: (usize, usize) = (6, 22); // Range of crater basin radii in cells
+const CRATER_EJECTA_REACH: f32 = 2.0; // Outer edge of the ejecta blanket, in crater radii
+const FAULT_THROW: (usize, usize) = (16, 48); // Range of fault uplift in cells, on a map of the default size
+const FAULT_BLOCK_WIDTH: (usize, usize) = (30, 90); // Range of uplifted block widths, likewise
+const FAULT_LENGTH: (f32, f32) = (0.3, 0.6); // Range of fault trace lengths, as a fraction of the map height
+const STRATA_SCALE: f64 = 0.01; // Frequency of the undulation along strata boundaries
+const STRATA_WAVE: f64 = 0.05; // Amplitude of that undulation, as a fraction of the map height
+const VEIN_MASK_SCALE: f64 = 0.008; // Frequency of the mask that breaks veins into separate runs
+const VEIN_MASK_THRESHOLD: f64 = 0.15; // Mask value a vein needs to be present at all
+const SURFACE_TEMPERATURE: f32 = 150.0; // Kelvin at the top of the map
+const GEOTHERMAL_GRADIENT: f32 = 250.0; // Kelvin gained from the top of the map to the bottom
+const HOTSPOT_SCALE: f64 = 0.006; // Frequency of the noise placing hot plumes
+const HOTSPOT_THRESHOLD: f64 = 0.5; // Hotspot noise value where a plume starts
+const HOTSPOT_HEAT: f64 = 2500.0; // Kelvin added per unit of hotspot noise above the threshold
+pub const MOLTEN_TEMPERATURE: f32 = 1000.0; // Kelvin at which rock melts into magma
+pub const FROZEN_TEMPERATURE: f32 = 110.0; // Kelvin below which voids fill with ice
+
+// Mineral types with distinct colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MineralType {
+    Empty,      // Black/dark gray
+    Iron,       // Rusty orange
+    Copper,     // Copper color
+    Gold,       // Gold/yellow
+    Silver,     // Light gray/silver
+    Uranium,    // Green
+    Diamond,    // Cyan/blue
+    Coal,       // Dark gray
+    Ice,        // Pale blue-white, frozen into cold voids
+    Magma,      // Glowing red-orange, too hot to dig
+}
+
+impl MineralType {
+    /// Minerals shown in the map legend
+    pub const LEGEND: [MineralType; 9] = [
+        MineralType::Iron,
+        MineralType::Copper,
+        MineralType::Gold,
+        MineralType::Silver,
+        MineralType::Uranium,
+        MineralType::Diamond,
+        MineralType::Coal,
+        MineralType::Ice,
+        MineralType::Magma,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            MineralType::Empty => "Empty",
+            MineralType::Iron => "Iron",
+            MineralType::Copper => "Copper",
+            MineralType::Gold => "Gold",
+            MineralType::Silver => "Silver",
+            MineralType::Uranium => "Uranium",
+            MineralType::Diamond => "Diamond",
+            MineralType::Coal => "Coal",
+            MineralType::Ice => "Ice",
+            MineralType::Magma => "Magma",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            MineralType::Empty => Color::srgb(0.1, 0.1, 0.15),
+            MineralType::Iron => Color::srgb(0.8, 0.4, 0.2),
+            MineralType::Copper => Color::srgb(0.72, 0.45, 0.2),
+            MineralType::Gold => Color::srgb(1.0, 0.84, 0.0),
+            MineralType::Silver => Color::srgb(0.75, 0.75, 0.75),
+            MineralType::Uranium => Color::srgb(0.2, 0.8, 0.2),
+            MineralType::Diamond => Color::srgb(0.4, 0.8, 1.0),
+            MineralType::Coal => Color::srgb(0.2, 0.2, 0.2),
+            MineralType::Ice => Color::srgb(0.85, 0.93, 1.0),
+            MineralType::Magma => Color::srgb(1.0, 0.25, 0.05),
+        }
+    }
+
+    /// Worth of one unit of density once mined, for balance reports and rewards
+    pub fn value(&self) -> f32 {
+        match self {
+            MineralType::Empty | MineralType::Magma => 0.0,
+            MineralType::Iron => 1.0,
+            MineralType::Copper => 1.5,
+            MineralType::Gold => 5.0,
+            MineralType::Silver => 3.0,
+            MineralType::Uranium => 4.0,
+            MineralType::Diamond => 8.0,
+            MineralType::Coal | MineralType::Ice => 0.5,
+        }
+    }
+
+    /// Whether miners can dig this cell for anything; magma is too hot
+    pub fn is_minable(&self) -> bool {
+        !matches!(self, MineralType::Empty | MineralType::Magma)
+    }
+
+    /// Mineral and density for a map texture pixel: the palette color
+    /// closest to the pixel at any of the brightnesses `MineralCell::pixel`
+    /// draws
+    pub fn from_pixel(rgb: [u8; 3]) -> (Self, f32) {
+        let pixel = Vec3::from_array(rgb.map(|channel| channel as f32 / 255.0));
+        std::iter::once(MineralType::Empty)
+            .chain(MineralType::LEGEND)
+            .map(|mineral| {
+                let color = mineral.color().to_srgba();
+                let color = Vec3::new(color.red, color.green, color.blue);
+                let brightness = (pixel.dot(color) / color.length_squared()).clamp(0.5, 1.0);
+                let density = if mineral == MineralType::Empty { 0.0 } else { (brightness - 0.5) * 2.0 };
+                (mineral, density, pixel.distance_squared(color * brightness))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(mineral, density, _)| (mineral, density))
+            .unwrap_or((MineralType::Empty, 0.0))
+    }
+
+    pub fn from_noise_value(value: f64, stratum: Stratum, biome: Biome) -> Self {
+        // Strata limit which minerals can form: coal only in the upper
+        // sediments, uranium only down in the bedrock
+        let sediment = stratum != Stratum::Bedrock;
+        let bedrock = stratum == Stratum::Bedrock;
+
+        // Gold, Silver and Diamond are laid down separately as veins
+        match biome {
+            // The baseline table
+            Biome::RegolithPlains => match value {
+                v if v < -0.4 => MineralType::Empty,
+                v if v < -0.2 && bedrock => MineralType::Uranium,
+                v if v < 0.0 && sediment => MineralType::Coal,
+                v if v < 0.2 => MineralType::Iron,
+                v if v < 0.5 => MineralType::Copper,
+                _ => MineralType::Empty,
+            },
+            // Dense volcanic rock: mostly iron and copper, little coal
+            Biome::BasaltFlats => match value {
+                v if v < -0.5 => MineralType::Empty,
+                v if v < -0.35 && sediment => MineralType::Coal,
+                v if v < 0.15 => MineralType::Iron,
+                v if v < 0.45 => MineralType::Copper,
+                _ => MineralType::Iron,
+            },
+            // Frozen ground: largely barren, with uranium at depth
+            Biome::IceFields => match value {
+                v if v < 0.0 => MineralType::Empty,
+                v if v < 0.2 && sediment => MineralType::Coal,
+                v if v < 0.5 && bedrock => MineralType::Uranium,
+                _ => MineralType::Empty,
+            },
+            // Crater debris: broken ground between wide veins
+            Biome::ImpactEjecta => match value {
+                v if v < -0.3 => MineralType::Empty,
+                v if v < 0.2 => MineralType::Iron,
+                v if v < 0.5 => MineralType::Copper,
+                v if v < 0.9 => MineralType::Uranium,
+                _ => MineralType::Empty,
+            },
+        }
+    }
+}
+
+// A precious mineral laid down in narrow veins rather than by thresholding
+struct VeinKind {
+    mineral: MineralType,
+    seed_offset: u32,
+    min_stratum: Stratum, // Shallowest stratum the vein appears in
+}
+
+// Rarest last, so it wins where veins cross
+const VEINS: [VeinKind; 3] = [
+    VeinKind { mineral: MineralType::Silver, seed_offset: 2, min_stratum: Stratum::Sedimentary },
+    VeinKind { mineral: MineralType::Gold, seed_offset: 3, min_stratum: Stratum::Sedimentary },
+    VeinKind { mineral: MineralType::Diamond, seed_offset: 4, min_stratum: Stratum::Bedrock },
+];
+
+// Large-scale terrain regions, each with its own mineral distribution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Biome {
+    #[default]
+    RegolithPlains,
+    BasaltFlats,
+    IceFields,
+    ImpactEjecta,
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [
+        Biome::RegolithPlains,
+        Biome::BasaltFlats,
+        Biome::IceFields,
+        Biome::ImpactEjecta,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Biome::RegolithPlains => "Regolith Plains",
+            Biome::BasaltFlats => "Basalt Flats",
+            Biome::IceFields => "Ice Fields",
+            Biome::ImpactEjecta => "Impact Ejecta",
+        }
+    }
+
+    /// Kelvin added to the temperature field in this biome
+    pub fn temperature_offset(&self) -> f32 {
+        match self {
+            Biome::RegolithPlains => 0.0,
+            Biome::BasaltFlats => 40.0,
+            Biome::IceFields => -90.0,
+            Biome::ImpactEjecta => 15.0,
+        }
+    }
+
+    /// Multiplier on vein width; impacts fracture the ground and widen veins
+    pub fn vein_width(&self) -> f64 {
+        match self {
+            Biome::RegolithPlains | Biome::BasaltFlats => 1.0,
+            Biome::IceFields => 0.6,
+            Biome::ImpactEjecta => 1.8,
+        }
+    }
+
+    /// Biome for a value of the low-frequency biome noise channel
+    pub fn from_noise_value(value: f64) -> Self {
+        match value {
+            v if v < -0.25 => Biome::IceFields,
+            v if v < 0.2 => Biome::RegolithPlains,
+            v if v < 0.45 => Biome::BasaltFlats,
+            _ => Biome::ImpactEjecta,
+        }
+    }
+}
+
+// Horizontal geological layers, shallowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Stratum {
+    #[default]
+    Regolith,
+    Sedimentary,
+    Bedrock,
+}
+
+impl Stratum {
+    pub const ALL: [Stratum; 3] = [Stratum::Regolith, Stratum::Sedimentary, Stratum::Bedrock];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Stratum::Regolith => "Regolith",
+            Stratum::Sedimentary => "Sedimentary",
+            Stratum::Bedrock => "Bedrock",
+        }
+    }
+
+    /// Mean depth of the stratum's lower boundary, as a fraction of the map height
+    fn base_depth(&self) -> f64 {
+        match self {
+            Stratum::Regolith => 0.2,
+            Stratum::Sedimentary => 0.55,
+            Stratum::Bedrock => 1.0,
+        }
+    }
+
+    /// Stratum at a depth, given how far the boundaries are displaced at this column
+    pub fn at_depth(depth_factor: f64, displacement: [f64; 2]) -> Self {
+        if depth_factor < Stratum::Regolith.base_depth() + displacement[0] {
+            Stratum::Regolith
+        } else if depth_factor < Stratum::Sedimentary.base_depth() + displacement[1] {
+            Stratum::Sedimentary
+        } else {
+            Stratum::Bedrock
+        }
+    }
+}
+
+// Data for each cell/pixel in the map
+#[derive(Debug, Clone)]
+pub struct MineralCell {
+    pub mineral_type: MineralType,
+    pub biome: Biome,
+    pub stratum: Stratum,
+    pub density: f32,      // 0.0 to 1.0, how much mineral is present
+    pub sampled: bool,     // Has this cell been sampled?
+    pub mined: bool,       // Has this cell been mined?
+}
+
+impl MineralCell {
+    /// RGBA pixel for this cell in the map texture
+    pub fn pixel(&self) -> [u8; 4] {
+        let color = self.mineral_type.color().to_srgba();
+        // Adjust brightness by density; mined-out cells are dimmed
+        let brightness = if self.mined {
+            0.25
+        } else {
+            0.5 + self.density * 0.5
+        };
+        [
+            (color.red * brightness * 255.0) as u8,
+            (color.green * brightness * 255.0) as u8,
+            (color.blue * brightness * 255.0) as u8,
+            255,
+        ]
+    }
+}
+
+impl Default for MineralCell {
+    fn default() -> Self {
+        Self {
+            mineral_type: MineralType::Empty,
+            biome: Biome::RegolithPlains,
+            stratum: Stratum::Regolith,
+            density: 0.0,
+            sampled: false,
+            mined: false,
+        }
+    }
+}
+
+// The main mineral map resource
+#[derive(Resource)]
+pub struct MineralMap {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u32,
+    pub symmetry: MapSymmetry,
+    pub preset: WorldGenPreset,
+    pub data: Vec<MineralCell>,
+    pub temperature: Vec<f32>, // Kelvin per cell, in the same order as `data`
+    pub craters: Vec<(UVec2, usize)>, // Center cell and basin radius of each crater
+    pub structures: Vec<(&'static str, UVec2)>, // Name and center cell of each placed structure
+    pub faults: Vec<Fault>,
+}
+
+// Mineral balance of a generated map, for tuning generators and scenarios
+#[derive(Resource, Debug, Clone, Default)]
+pub struct WorldReport {
+    pub minerals: Vec<MineralReport>, // Every mineral on the map, most valuable first
+    pub total_value: f32,
+    pub void_fraction: f32, // Share of the map that is empty, 0.0 to 1.0
+}
+
+#[derive(Debug, Clone)]
+pub struct MineralReport {
+    pub mineral: MineralType,
+    pub cells: usize,
+    pub value: f32,           // Density times the mineral's value, summed
+    pub largest_vein: usize,  // Cells in the largest connected deposit
+}
+
+impl WorldReport {
+    pub fn new(map: &MineralMap) -> Self {
+        let _span = info_span!("world_report").entered();
+        let mut minerals: Vec<MineralReport> = Vec::new();
+        let mut visited = vec![false; map.data.len()];
+        let mut stack = Vec::new();
+        let mut empty = 0;
+
+        for start in 0..map.data.len() {
+            let mineral = map.data[start].mineral_type;
+            if mineral == MineralType::Empty {
+                empty += 1;
+                continue;
+            }
+            let report = match minerals.iter().position(|report| report.mineral == mineral) {
+                Some(index) => &mut minerals[index],
+                None => {
+                    minerals.push(MineralReport { mineral, cells: 0, value: 0.0, largest_vein: 0 });
+                    minerals.last_mut().unwrap()
+                }
+            };
+            report.cells += 1;
+            report.value += map.data[start].density * mineral.value();
+            if visited[start] {
+                continue;
+            }
+
+            // Flood fill the deposit this cell starts, four-connected
+            visited[start] = true;
+            stack.push(start);
+            let mut size = 0;
+            while let Some(index) = stack.pop() {
+                size += 1;
+                let (x, y) = (index % map.width, index / map.width);
+                let neighbours = [
+                    (x > 0).then(|| index - 1),
+                    (x + 1 < map.width).then(|| index + 1),
+                    (y > 0).then(|| index - map.width),
+                    (y + 1 < map.height).then(|| index + map.width),
+                ];
+                for neighbour in neighbours.into_iter().flatten() {
+                    if !visited[neighbour] && map.data[neighbour].mineral_type == mineral {
+                        visited[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            report.largest_vein = report.largest_vein.max(size);
+        }
+
+        minerals.sort_by(|a, b| b.value.total_cmp(&a.value));
+        Self {
+            total_value: minerals.iter().map(|report| report.value).sum(),
+            minerals,
+            void_fraction: empty as f32 / map.data.len().max(1) as f32,
+        }
+    }
+}
+
+// A fault trace with the block of ground beside it (to its right) pushed
+// up, bringing deeper strata toward the surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fault {
+    pub start: UVec2, // Upper end of the trace
+    pub end: UVec2,   // Lower end of the trace
+    pub throw: usize, // Cells the block was raised at the middle of the trace
+    pub discovered: bool, // Found by a sampler
+}
+
+impl Fault {
+    /// Distance in cells from a cell to the fault trace
+    pub fn distance_to(&self, cell: UVec2) -> f32 {
+        let (a, b, p) = (self.start.as_vec2(), self.end.as_vec2(), cell.as_vec2());
+        let t = ((p - a).dot(b - a) / (b - a).length_squared().max(1.0)).clamp(0.0, 1.0);
+        p.distance(a + (b - a) * t)
+    }
+}
+
+// Tunable world generation parameters, normally taken from a preset
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldGenParams {
+    pub seed: u32,
+    pub symmetry: MapSymmetry,
+    pub size: usize,          // Cells on a side of the square map
+    pub terrain_scale: f64,   // Frequency of the base terrain noise
+    pub detail_scale: f64,    // Frequency of the fine detail noise
+    pub detail_strength: f64, // How much fine detail is added to the terrain
+    pub biome_scale: f64,     // Frequency of the biome noise; low so biomes span large regions
+    pub biome_bias: f64,      // Shifts the biome mix: negative favors ice, positive basalt and ejecta
+    pub vein_scale: f64,      // Frequency of the vein noise; sets how tightly veins wind
+    pub vein_width: f64,      // Half-width of a vein in noise units around the zero line
+    pub void_fraction: f64,   // Share of the map carved out as empty space, 0.0 to 1.0
+    pub crater_count: usize,  // Impact craters carved into a map of the default size
+    pub fault_count: usize,   // Faults sheared into a map of the default size
+}
+
+impl Default for WorldGenParams {
+    fn default() -> Self {
+        WorldGenPreset::Standard.params()
+    }
+}
+
+// Named world generation parameter sets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WorldGenPreset {
+    #[default]
+    Standard,
+    Asteroid, // Rubble pile: broken terrain, large voids, ejecta and rich veins
+    Lunar,    // Cratered plains with ice in the shadows
+    Mars,     // Volcanic basalt with little ice and thin veins
+}
+
+impl WorldGenPreset {
+    pub const ALL: [WorldGenPreset; 4] = [
+        WorldGenPreset::Standard,
+        WorldGenPreset::Asteroid,
+        WorldGenPreset::Lunar,
+        WorldGenPreset::Mars,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            WorldGenPreset::Standard => "standard",
+            WorldGenPreset::Asteroid => "asteroid",
+            WorldGenPreset::Lunar => "lunar",
+            WorldGenPreset::Mars => "mars",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    /// Read `--preset <name>` from the command line
+    pub fn from_args() -> Self {
+        let Some(value) = arg_value("--preset") else {
+            return WorldGenPreset::Standard;
+        };
+
+        Self::from_name(&value).unwrap_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|p| p.name()).collect();
+            eprintln!("Unknown preset '{}', expected one of: {}", value, names.join(", "));
+            WorldGenPreset::Standard
+        })
+    }
+
+    pub fn params(&self) -> WorldGenParams {
+        let standard = WorldGenParams {
+            seed: 0,
+            symmetry: MapSymmetry::None,
+            size: DEFAULT_MAP_SIZE,
+            terrain_scale: 0.02,
+            detail_scale: 0.1,
+            detail_strength: 0.2,
+            biome_scale: 0.006,
+            biome_bias: 0.0,
+            vein_scale: 0.015,
+            vein_width: 0.035,
+            void_fraction: 0.0,
+            crater_count: 4,
+            fault_count: 3,
+        };
+        match self {
+            WorldGenPreset::Standard => standard,
+            WorldGenPreset::Asteroid => WorldGenParams {
+                terrain_scale: 0.035,
+                detail_strength: 0.35,
+                biome_scale: 0.012,
+                biome_bias: 0.35,
+                vein_width: 0.05,
+                void_fraction: 0.3,
+                crater_count: 10,
+                fault_count: 5,
+                ..standard
+            },
+            WorldGenPreset::Lunar => WorldGenParams {
+                biome_scale: 0.01,
+                biome_bias: -0.05,
+                void_fraction: 0.05,
+                crater_count: 14,
+                fault_count: 2,
+                ..standard
+            },
+            WorldGenPreset::Mars => WorldGenParams {
+                terrain_scale: 0.015,
+                detail_strength: 0.3,
+                biome_bias: 0.2,
+                vein_width: 0.025,
+                crater_count: 6,
+                fault_count: 4,
+                ..standard
+            },
+        }
+    }
+}
+
+/// Read `--size <cells>` from the command line, one of `MAP_SIZES`
+pub fn map_size_from_args() -> usize {
+    let Some(value) = arg_value("--size") else {
+        return DEFAULT_MAP_SIZE;
+    };
+
+    match value.parse() {
+        Ok(size) if MAP_SIZES.contains(&size) => size,
+        _ => {
+            eprintln!("Unsupported map size '{}', expected one of: {:?}", value, MAP_SIZES);
+            DEFAULT_MAP_SIZE
+        }
+    }
+}
+
+/// Read `--seed <n>` from the command line, for replaying a known map
+pub fn seed_from_args() -> Option<u32> {
+    let value = arg_value("--seed")?;
+    match value.parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            eprintln!("Invalid seed '{}', using a random one", value);
+            None
+        }
+    }
+}
+
+/// Value following a command-line flag, e.g. `--symmetry quad`
+pub fn arg_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip_while(|arg| arg != flag);
+    args.next()?;
+    args.next()
+}
+
+// Symmetry applied to the generated map for fair competitive layouts
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MapSymmetry {
+    #[default]
+    None,
+    MirrorHorizontal, // Right half mirrors the left
+    MirrorVertical,   // Bottom half mirrors the top
+    Rotational,       // Bottom half is the top rotated 180 degrees
+    Quadrant,         // All four quadrants mirror the top-left
+}
+
+impl MapSymmetry {
+    pub const ALL: [MapSymmetry; 5] = [
+        MapSymmetry::None,
+        MapSymmetry::MirrorHorizontal,
+        MapSymmetry::MirrorVertical,
+        MapSymmetry::Rotational,
+        MapSymmetry::Quadrant,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            MapSymmetry::None => "none",
+            MapSymmetry::MirrorHorizontal => "mirror-x",
+            MapSymmetry::MirrorVertical => "mirror-y",
+            MapSymmetry::Rotational => "rotate",
+            MapSymmetry::Quadrant => "quad",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|symmetry| symmetry.name() == name)
+    }
+
+    /// Read `--symmetry <mode>` from the command line
+    pub fn from_args() -> Self {
+        let Some(value) = arg_value("--symmetry") else {
+            return MapSymmetry::None;
+        };
+
+        Self::from_name(&value).unwrap_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|s| s.name()).collect();
+            eprintln!("Unknown symmetry '{}', expected one of: {}", value, names.join(", "));
+            MapSymmetry::None
+        })
+    }
+
+    /// Mirrored copies of a cell on the generated side
+    pub fn images(&self, x: usize, y: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let (mx, my) = (width - 1 - x, height - 1 - y);
+        match self {
+            MapSymmetry::None => Vec::new(),
+            MapSymmetry::MirrorHorizontal => vec![(mx, y)],
+            MapSymmetry::MirrorVertical => vec![(x, my)],
+            MapSymmetry::Rotational => vec![(mx, my)],
+            MapSymmetry::Quadrant => vec![(mx, y), (x, my), (mx, my)],
+        }
+    }
+
+    /// For a cell on the mirrored side, the source cell it copies and its
+    /// distance in cells from the seam. None for cells on the generated side.
+    pub fn source(&self, x: usize, y: usize, width: usize, height: usize) -> Option<(usize, usize, usize)> {
+        let mirror_x = x >= width / 2;
+        let mirror_y = y >= height / 2;
+        match self {
+            MapSymmetry::None => None,
+            MapSymmetry::MirrorHorizontal => mirror_x.then(|| (width - 1 - x, y, x - width / 2)),
+            MapSymmetry::MirrorVertical => mirror_y.then(|| (x, height - 1 - y, y - height / 2)),
+            MapSymmetry::Rotational => mirror_y.then(|| (width - 1 - x, height - 1 - y, y - height / 2)),
+            MapSymmetry::Quadrant => {
+                if !mirror_x && !mirror_y {
+                    return None;
+                }
+                let sx = if mirror_x { width - 1 - x } else { x };
+                let sy = if mirror_y { height - 1 - y } else { y };
+                let seam_distance = match (mirror_x, mirror_y) {
+                    (true, true) => (x - width / 2).min(y - height / 2),
+                    (true, false) => x - width / 2,
+                    _ => y - height / 2,
+                };
+                Some((sx, sy, seam_distance))
+            }
+        }
+    }
+}
+
+/// Days since the Unix epoch (UTC), which picks the daily challenge seed
+pub fn current_day() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400)
+}
+
+/// Seed shared by everyone playing the daily challenge on a given day. Uses a
+/// fixed mixing function (splitmix64) so it doesn't depend on the rand version.
+pub fn daily_seed(day: u64) -> u32 {
+    let mut z = day.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) as u32
+}
+
+/// Calendar date (YYYY-MM-DD) of a day number since the Unix epoch
+pub fn format_day(day: u64) -> String {
+    // Civil-from-days conversion on the proleptic Gregorian calendar
+    let z = day as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // March is 0
+    let day_of_month = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day_of_month)
+}
+
+impl Default for MineralMap {
+    fn default() -> Self {
+        Self::generate(MapSymmetry::None, WorldGenPreset::Standard, DEFAULT_MAP_SIZE)
+    }
+}
+
+impl MineralMap {
+    /// Generate a square map of `size` cells on a side from a random seed
+    pub fn generate(symmetry: MapSymmetry, preset: WorldGenPreset, size: usize) -> Self {
+        Self::generate_with_preset(thread_rng().gen(), symmetry, preset, size)
+    }
+
+    /// Generate a standard map of the default size
+    pub fn generate_with_seed(seed: u32, symmetry: MapSymmetry) -> Self {
+        Self::generate_with_preset(seed, symmetry, WorldGenPreset::Standard, DEFAULT_MAP_SIZE)
+    }
+
+    pub fn generate_with_preset(seed: u32, symmetry: MapSymmetry, preset: WorldGenPreset, size: usize) -> Self {
+        let params = WorldGenParams {
+            seed,
+            symmetry,
+            size,
+            ..preset.params()
+        };
+        let mut map = NoiseGenerator.generate(&params);
+        map.preset = preset;
+        map
+    }
+
+    /// A map built from cells laid out row by row, with the depth and biome
+    /// temperature gradient and nothing else; for generators that place
+    /// their own cells
+    pub fn from_cells(width: usize, height: usize, seed: u32, data: Vec<MineralCell>) -> Self {
+        let mut map = Self {
+            width,
+            height,
+            seed,
+            symmetry: MapSymmetry::None,
+            preset: WorldGenPreset::Standard,
+            data,
+            temperature: Vec::new(),
+            craters: Vec::new(),
+            structures: Vec::new(),
+            faults: Vec::new(),
+        };
+        map.temperature = map.temperature_field(false);
+        map
+    }
+
+    /// Terrain, biomes, strata and veins from noise; the first pass of the
+    /// noise generator
+    pub(crate) fn generate_with_params(params: &WorldGenParams) -> Self {
+        let (seed, symmetry, size) = (params.seed, params.symmetry, params.size);
+        let _span = info_span!("worldgen", size).entered();
+        let (width, height) = (size, size);
+
+        // Create noise generators
+        let perlin = Perlin::new(seed);
+        let fbm = Fbm::<Perlin>::new(seed);
+        let biome_noise = Perlin::new(seed.wrapping_add(1));
+        let strata_noise = Perlin::new(seed.wrapping_add(5));
+        // A few octaves keep the vein lines from crossing on the noise lattice
+        let vein_noise: Vec<(Fbm<Perlin>, Perlin)> = VEINS
+            .iter()
+            .map(|vein| {
+                let vein_seed = seed.wrapping_add(vein.seed_offset);
+                (
+                    Fbm::<Perlin>::new(vein_seed).set_octaves(3),
+                    Perlin::new(vein_seed.wrapping_add(100)),
+                )
+            })
+            .collect();
+
+        let mut values = Vec::with_capacity(width * height);
+        let mut biomes = Vec::with_capacity(width * height);
+        let mut veins = Vec::with_capacity(width * height);
+        let mut strata = Vec::with_capacity(width * height);
+
+        // Strata boundaries undulate along the map, each on its own noise row
+        let displacement: Vec<[f64; 2]> = (0..width)
+            .map(|x| {
+                let along = x as f64 * STRATA_SCALE;
+                [
+                    strata_noise.get([along, 0.5]) * STRATA_WAVE,
+                    strata_noise.get([along, 10.5]) * STRATA_WAVE,
+                ]
+            })
+            .collect();
+
+        for y in 0..height {
+            for (x, &boundaries) in displacement.iter().enumerate() {
+                // Use multiple octaves of noise for varied terrain
+                let scale = params.terrain_scale;
+                let noise_value = fbm.get([x as f64 * scale, y as f64 * scale]);
+
+                // Add some fine detail
+                let scale = params.detail_scale;
+                let detail = perlin.get([x as f64 * scale, y as f64 * scale]) * params.detail_strength;
+                values.push(noise_value + detail);
+
+                // Second, low-frequency channel for the biome layer
+                let scale = params.biome_scale;
+                let biome_value = biome_noise.get([x as f64 * scale, y as f64 * scale]);
+                let biome = Biome::from_noise_value(biome_value + params.biome_bias);
+                biomes.push(biome);
+
+                let stratum = Stratum::at_depth(y as f64 / height as f64, boundaries);
+                strata.push(stratum);
+
+                // Vein carving: ridged noise (closeness to a noise zero line)
+                // traces thin winding bands, gated by stratum and a sparse mask
+                let mut vein = None;
+                for (kind, (line, mask)) in VEINS.iter().zip(&vein_noise) {
+                    if stratum < kind.min_stratum
+                        || mask.get([x as f64 * VEIN_MASK_SCALE, y as f64 * VEIN_MASK_SCALE]) < VEIN_MASK_THRESHOLD
+                    {
+                        continue;
+                    }
+                    let width = params.vein_width * biome.vein_width();
+                    let scale = params.vein_scale;
+                    let ridge = 1.0 - line.get([x as f64 * scale, y as f64 * scale]).abs() / width;
+                    if ridge > 0.0 {
+                        // Densest along the vein's center line
+                        vein = Some((kind.mineral, (0.8 + ridge * 0.2) as f32));
+                    }
+                }
+                veins.push(vein);
+            }
+        }
+
+        // Voids: the lowest terrain values, up to the preset's fraction of the map
+        let void_threshold = if params.void_fraction > 0.0 {
+            let mut sorted = values.clone();
+            let rank = ((sorted.len() as f64 * params.void_fraction) as usize).min(sorted.len() - 1);
+            *sorted.select_nth_unstable_by(rank, f64::total_cmp).1
+        } else {
+            f64::NEG_INFINITY
+        };
+
+        // Symmetry post-pass, blend: near a seam, each cell on the generated
+        // side is pulled toward the mean of itself and its mirrored copies to
+        // soften the fold. The copies take the blended value below, so the
+        // halves match exactly.
+        let blended: Vec<f64> = (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width, index / width);
+                let own = values[index];
+                if symmetry.source(x, y, width, height).is_some() {
+                    return own; // Replaced by its source's value below
+                }
+                let images = symmetry.images(x, y, width, height);
+                let Some(seam_distance) = images
+                    .iter()
+                    .filter_map(|&(ix, iy)| symmetry.source(ix, iy, width, height))
+                    .map(|(_, _, distance)| distance)
+                    .min()
+                else {
+                    return own;
+                };
+                let mean = (own + images.iter().map(|&(ix, iy)| values[iy * width + ix]).sum::<f64>())
+                    / (images.len() + 1) as f64;
+                let t = (seam_distance as f64 / SEAM_BLEND_CELLS).min(1.0);
+                let t = t * t * (3.0 - 2.0 * t);
+                mean + (own - mean) * t
+            })
+            .collect();
+
+        let mut data = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                // Symmetry post-pass, copy: mirrored cells take everything
+                // from their source cell on the generated side
+                let source = symmetry
+                    .source(x, y, width, height)
+                    .map_or(y * width + x, |(sx, sy, _)| sy * width + sx);
+                let (combined, stratum, biome, vein) = (blended[source], strata[source], biomes[source], veins[source]);
+
+                let (mineral_type, density) = match vein {
+                    _ if combined < void_threshold => (MineralType::Empty, 0.0),
+                    Some(vein) => vein,
+                    None => (
+                        MineralType::from_noise_value(combined, stratum, biome),
+                        ((combined + 1.0) / 2.0) as f32, // Normalize to 0-1
+                    ),
+                };
+
+                data.push(MineralCell {
+                    mineral_type,
+                    biome,
+                    stratum,
+                    density,
+                    sampled: false,
+                    mined: false,
+                });
+            }
+        }
+
+        Self {
+            width,
+            height,
+            seed,
+            symmetry,
+            preset: WorldGenPreset::Standard,
+            data,
+            temperature: Vec::new(),
+            craters: Vec::new(),
+            structures: Vec::new(),
+            faults: Vec::new(),
+        }
+    }
+
+    /// Temperature of every cell: cold at the surface and warming with
+    /// depth, offset by biome, with hot plumes over seeded hotspots.
+    /// Symmetric maps copy the field from the generated side.
+    pub(crate) fn temperature_field(&self, hotspots: bool) -> Vec<f32> {
+        let _span = info_span!("worldgen_temperature").entered();
+        let hotspot_noise = Perlin::new(self.seed.wrapping_add(6));
+        let mut field = Vec::with_capacity(self.data.len());
+        for y in 0..self.height {
+            let depth = y as f32 / self.height as f32;
+            for x in 0..self.width {
+                let biome = self.data[y * self.width + x].biome;
+                let mut temperature = SURFACE_TEMPERATURE + depth * GEOTHERMAL_GRADIENT + biome.temperature_offset();
+                if hotspots {
+                    let plume = hotspot_noise.get([x as f64 * HOTSPOT_SCALE, y as f64 * HOTSPOT_SCALE]);
+                    temperature += ((plume - HOTSPOT_THRESHOLD).max(0.0) * HOTSPOT_HEAT) as f32;
+                }
+                field.push(temperature);
+            }
+        }
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some((sx, sy, _)) = self.symmetry.source(x, y, self.width, self.height) {
+                    field[y * self.width + x] = field[sy * self.width + sx];
+                }
+            }
+        }
+        field
+    }
+
+    /// Melt the hottest cells into magma and freeze ice into the coldest voids
+    pub(crate) fn place_thermal_deposits(&mut self) {
+        for (cell, &temperature) in self.data.iter_mut().zip(&self.temperature) {
+            if temperature >= MOLTEN_TEMPERATURE {
+                cell.mineral_type = MineralType::Magma;
+                cell.density = ((temperature - MOLTEN_TEMPERATURE) / 300.0).clamp(0.3, 1.0);
+            } else if temperature <= FROZEN_TEMPERATURE && cell.mineral_type == MineralType::Empty {
+                cell.mineral_type = MineralType::Ice;
+                cell.density = ((FROZEN_TEMPERATURE - temperature) / 40.0).clamp(0.3, 1.0);
+            }
+        }
+    }
+
+    /// Temperature at a cell in Kelvin
+    pub fn temperature_at(&self, x: usize, y: usize) -> Option<f32> {
+        (x < self.width).then(|| self.temperature.get(y * self.width + x).copied()).flatten()
+    }
+
+    /// Semi-transparent RGBA overlay of the temperature field, from blue
+    /// for the coldest cells through white to red at the melting point
+    pub fn temperature_image(&self) -> Image {
+        let mut image_data = Vec::with_capacity(self.width * self.height * 4);
+        for &temperature in &self.temperature {
+            let t = ((temperature - FROZEN_TEMPERATURE) / (MOLTEN_TEMPERATURE - FROZEN_TEMPERATURE)).clamp(0.0, 1.0);
+            // Most of the map sits well below melting, so stretch the cold end
+            let t = t.sqrt();
+            let color = if t < 0.5 {
+                Vec3::new(0.2, 0.4, 1.0).lerp(Vec3::ONE, t * 2.0)
+            } else {
+                Vec3::ONE.lerp(Vec3::new(1.0, 0.15, 0.0), t * 2.0 - 1.0)
+            };
+            image_data.extend_from_slice(&[
+                (color.x * 255.0) as u8,
+                (color.y * 255.0) as u8,
+                (color.z * 255.0) as u8,
+                150,
+            ]);
+        }
+
+        Image::new(
+            Extent3d {
+                width: self.width as u32,
+                height: self.height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            image_data,
+            TextureFormat::Rgba8UnormSrgb,
+            Default::default(),
+        )
+    }
+
+    /// A count given for a map of the default size, scaled to this map's area
+    pub fn scaled_count(&self, count: usize) -> usize {
+        let area = (self.width * self.height) as f32 / (DEFAULT_MAP_SIZE * DEFAULT_MAP_SIZE) as f32;
+        (count as f32 * area).round() as usize
+    }
+
+    /// Shear faults into the map: beside each fault trace a block of ground
+    /// is pushed up, most at the middle of the trace and tapering to its
+    /// ends, so cells and their strata are offset across the trace. Faults
+    /// sit on the generated side of a symmetric map and are mirrored.
+    pub(crate) fn shear_faults(&mut self, count: usize) {
+        let _span = info_span!("worldgen_faults").entered();
+        let mut rng = StdRng::seed_from_u64(u64::from(self.seed) ^ 0xFA17_5EA2);
+        let scale = self.height as f32 / DEFAULT_MAP_SIZE as f32;
+
+        for _ in 0..count {
+            let throw = (rng.gen_range(FAULT_THROW.0..=FAULT_THROW.1) as f32 * scale) as usize;
+            let block = (rng.gen_range(FAULT_BLOCK_WIDTH.0..=FAULT_BLOCK_WIDTH.1) as f32 * scale) as usize;
+            let length = (rng.gen_range(FAULT_LENGTH.0..FAULT_LENGTH.1) * self.height as f32) as usize;
+            let slope = rng.gen_range(-0.3..0.3_f32); // Cells across per cell down
+            let lean = (slope * length as f32).round() as isize;
+            let span = block + lean.unsigned_abs();
+            if span + 1 >= self.width || length + 1 >= self.height {
+                continue;
+            }
+
+            let site = (0..100).find_map(|_| {
+                let left = rng.gen_range(0..self.width - span);
+                let top = rng.gen_range(0..self.height - length);
+                [(left, top), (left + span, top + length)]
+                    .iter()
+                    .all(|&(x, y)| self.symmetry.source(x, y, self.width, self.height).is_none())
+                    .then_some((left, top))
+            });
+            let Some((left, top)) = site else {
+                continue;
+            };
+            let x0 = left + lean.min(0).unsigned_abs(); // Trace position at the top
+
+            for x in left..=left + span {
+                // Gather the column's displaced cells before writing any
+                let mut moved = Vec::new();
+                for y in top..=top + length {
+                    let along = (y - top) as f32 / length as f32;
+                    let trace = x0 as f32 + slope * (y - top) as f32;
+                    if (x as f32) < trace || x as f32 >= trace + block as f32 {
+                        continue;
+                    }
+                    let shift = (throw as f32 * (along * std::f32::consts::PI).sin()).round() as usize;
+                    if let Some(cell) = self.get(x, y + shift) {
+                        moved.push((y, cell.clone()));
+                    }
+                }
+                for (y, cell) in moved {
+                    let mut targets = self.symmetry.images(x, y, self.width, self.height);
+                    targets.push((x, y));
+                    for (x, y) in targets {
+                        if let Some(target) = self.get_mut(x, y) {
+                            *target = cell.clone();
+                        }
+                    }
+                }
+            }
+
+            let start = (x0, top);
+            let end = ((x0 as isize + lean) as usize, top + length);
+            let mut traces = vec![(start, end)];
+            let start_images = self.symmetry.images(start.0, start.1, self.width, self.height);
+            let end_images = self.symmetry.images(end.0, end.1, self.width, self.height);
+            traces.extend(start_images.into_iter().zip(end_images));
+            for ((sx, sy), (ex, ey)) in traces {
+                self.faults.push(Fault {
+                    start: UVec2::new(sx as u32, sy as u32),
+                    end: UVec2::new(ex as u32, ey as u32),
+                    throw,
+                    discovered: false,
+                });
+            }
+        }
+    }
+
+    /// Index of a fault whose trace passes within `radius` cells of a cell
+    pub fn fault_near(&self, cell: UVec2, radius: f32) -> Option<usize> {
+        self.faults.iter().position(|fault| fault.distance_to(cell) <= radius)
+    }
+
+    /// Carve impact craters: an emptied basin, a dense rim of iron and coal
+    /// thrown up around it, and ejecta scattered thinning outward. Craters
+    /// sit on the generated side of a symmetric map and are mirrored.
+    pub(crate) fn carve_craters(&mut self, count: usize) {
+        let _span = info_span!("worldgen_craters").entered();
+        let mut rng = StdRng::seed_from_u64(u64::from(self.seed) ^ 0xC2A7_E125);
+
+        for _ in 0..count {
+            let radius = rng.gen_range(CRATER_RADIUS.0..=CRATER_RADIUS.1);
+            let reach = (radius as f32 * CRATER_EJECTA_REACH).ceil() as usize;
+            if 2 * reach + 1 >= self.width.min(self.height) {
+                continue;
+            }
+            let site = (0..100).find_map(|_| {
+                let x = rng.gen_range(reach..self.width - reach);
+                let y = rng.gen_range(reach..self.height - reach);
+                [(x - reach, y - reach), (x + reach, y + reach)]
+                    .iter()
+                    .all(|&(x, y)| self.symmetry.source(x, y, self.width, self.height).is_none())
+                    .then_some((x, y))
+            });
+            let Some((cx, cy)) = site else {
+                continue;
+            };
+
+            for y in cy - reach..=cy + reach {
+                for x in cx - reach..=cx + reach {
+                    let distance = Vec2::new(x as f32 - cx as f32, y as f32 - cy as f32).length() / radius as f32;
+                    let rim = (distance - 1.0).abs();
+                    let (mineral, density) = if distance < 0.85 {
+                        // Basin floor, deepest at the center
+                        (MineralType::Empty, distance * 0.2)
+                    } else if rim < 0.2 {
+                        let mineral = if rng.gen_bool(0.65) { MineralType::Iron } else { MineralType::Coal };
+                        (mineral, 0.95 - rim)
+                    } else if distance <= CRATER_EJECTA_REACH
+                        && rng.gen_bool(f64::from((CRATER_EJECTA_REACH - distance) / CRATER_EJECTA_REACH * 0.5))
+                    {
+                        let mineral = if rng.gen_bool(0.65) { MineralType::Iron } else { MineralType::Coal };
+                        (mineral, 0.7)
+                    } else {
+                        continue;
+                    };
+
+                    let mut targets = self.symmetry.images(x, y, self.width, self.height);
+                    targets.push((x, y));
+                    for (x, y) in targets {
+                        if let Some(cell) = self.get_mut(x, y) {
+                            cell.mineral_type = mineral;
+                            cell.density = density;
+                            cell.biome = Biome::ImpactEjecta;
+                        }
+                    }
+                }
+            }
+            self.craters.push((UVec2::new(cx as u32, cy as u32), radius));
+        }
+    }
+
+    /// Map cell under a world position (the map sprite is centered on the origin)
+    pub fn world_to_cell(&self, position: Vec2) -> Option<UVec2> {
+        let x = position.x / MAP_SCALE + self.width as f32 / 2.0;
+        let y = self.height as f32 / 2.0 - position.y / MAP_SCALE;
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some(UVec2::new(x as u32, y as u32))
+    }
+
+    /// World position of a cell's center
+    pub fn cell_to_world(&self, cell: UVec2) -> Vec2 {
+        Vec2::new(
+            (cell.x as f32 + 0.5 - self.width as f32 / 2.0) * MAP_SCALE,
+            (self.height as f32 / 2.0 - cell.y as f32 - 0.5) * MAP_SCALE,
+        )
+    }
+
+    /// RGBA texture of the map, one pixel per cell
+    pub fn to_image(&self) -> Image {
+        let mut image_data = Vec::with_capacity(self.width * self.height * 4);
+        info_span!("map_texture_build").in_scope(|| {
+            for cell in &self.data {
+                image_data.extend_from_slice(&cell.pixel());
+            }
+        });
+
+        Image::new(
+            Extent3d {
+                width: self.width as u32,
+                height: self.height as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            image_data,
+            TextureFormat::Rgba8UnormSrgb,
+            Default::default(),
+        )
+    }
+
+    /// Build a map from PNG data instead of noise. A grayscale image is a
+    /// heightmap: high ground is regolith and low ground exposes bedrock,
+    /// with minerals picked from the height as from terrain noise. 16-bit
+    /// DEMs keep their full height precision. Color images are matched to
+    /// the mineral palette, with brightness as density, so maps rendered by
+    /// `regolith-cli render` load back in. Images larger than the largest
+    /// map size are scaled down to fit.
+    pub fn from_png(bytes: &[u8]) -> Result<Self, String> {
+        let _span = info_span!("worldgen_import").entered();
+        let mut image =
+            image::load_from_memory(bytes).map_err(|err| format!("Could not decode the image: {}", err))?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err("Image is empty".to_string());
+        }
+        let max_size = MAP_SIZES[MAP_SIZES.len() - 1] as u32;
+        if image.width() > max_size || image.height() > max_size {
+            // Heights are averaged, but palette colors must stay exact
+            let filter = if image.color().has_color() {
+                FilterType::Nearest
+            } else {
+                FilterType::Triangle
+            };
+            image = image.resize(max_size, max_size, filter);
+        }
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image.to_rgba8();
+
+        let heightmap = pixels.pixels().all(|p| p[0] == p[1] && p[1] == p[2]);
+        let heights = image.to_luma16();
+        let data = pixels
+            .pixels()
+            .zip(heights.pixels())
+            .map(|(p, luma)| {
+                if heightmap {
+                    let height = luma[0] as f64 / 65535.0;
+                    let stratum = Stratum::at_depth(1.0 - height, [0.0, 0.0]);
+                    let biome = Biome::RegolithPlains;
+                    MineralCell {
+                        mineral_type: MineralType::from_noise_value(height * 2.0 - 1.0, stratum, biome),
+                        biome,
+                        stratum,
+                        density: height as f32,
+                        ..default()
+                    }
+                } else {
+                    let (mineral_type, density) = MineralType::from_pixel([p[0], p[1], p[2]]);
+                    MineralCell {
+                        mineral_type,
+                        density,
+                        ..default()
+                    }
+                }
+            })
+            .collect();
+
+        // The seed only feeds seeded passes run later, so a hash of the
+        // file keeps them the same each time an image is loaded
+        let seed = bytes
+            .iter()
+            .fold(0x811C_9DC5_u32, |hash, &byte| (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193));
+
+        // Images carry no heat, so only the depth and biome gradient apply
+        Ok(Self::from_cells(width, height, seed, data))
+    }
+
+    /// Cell count and total density per mineral type, most common first
+    pub fn mineral_stats(&self) -> Vec<(MineralType, usize, f32)> {
+        let mut stats: Vec<(MineralType, usize, f32)> = Vec::new();
+        for cell in &self.data {
+            match stats.iter_mut().find(|(mineral, _, _)| *mineral == cell.mineral_type) {
+                Some((_, count, density)) => {
+                    *count += 1;
+                    *density += cell.density;
+                }
+                None => stats.push((cell.mineral_type, 1, cell.density)),
+            }
+        }
+        stats.sort_by_key(|(_, count, _)| std::cmp::Reverse(*count));
+        stats
+    }
+
+    /// Biome of the cell at the given coordinates
+    pub fn biome_at(&self, x: usize, y: usize) -> Option<Biome> {
+        self.get(x, y).map(|cell| cell.biome)
+    }
+
+    /// Number of cells in each biome, in `Biome::ALL` order
+    pub fn biome_stats(&self) -> Vec<(Biome, usize)> {
+        Biome::ALL
+            .into_iter()
+            .map(|biome| (biome, self.data.iter().filter(|cell| cell.biome == biome).count()))
+            .collect()
+    }
+
+    /// Number of cells in each stratum, shallowest first
+    pub fn strata_stats(&self) -> Vec<(Stratum, usize)> {
+        Stratum::ALL
+            .into_iter()
+            .map(|stratum| (stratum, self.data.iter().filter(|cell| cell.stratum == stratum).count()))
+            .collect()
+    }
+
+    /// Estimated heap memory used by the cell array, in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.data.capacity() * std::mem::size_of::<MineralCell>()
+    }
+
+    /// Hash of the cell state in each square region of the map, row-major.
+    /// Cheap enough to run every few frames; comparing two runs' hashes
+    /// points at the region where they diverged.
+    pub fn region_hashes(&self, region: usize) -> Vec<u64> {
+        let (across, down) = (self.width.div_ceil(region), self.height.div_ceil(region));
+        let mut hashes = vec![0xCBF2_9CE4_8422_2325_u64; across * down];
+        for (index, cell) in self.data.iter().enumerate() {
+            let (x, y) = (index % self.width, index / self.width);
+            let hash = &mut hashes[(y / region) * across + x / region];
+            let state = [cell.mineral_type as u32, cell.density.to_bits(), cell.mined as u32, cell.sampled as u32];
+            for word in state {
+                *hash = (*hash ^ u64::from(word)).wrapping_mul(0x0000_0100_0000_01B3);
+            }
+        }
+        hashes
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&MineralCell> {
+        if x < self.width && y < self.height {
+            Some(&self.data[y * self.width + x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut MineralCell> {
+        if x < self.width && y < self.height {
+            Some(&mut self.data[y * self.width + x])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_maps_match_their_mirrored_halves() {
+        for symmetry in MapSymmetry::ALL {
+            let map = MineralMap::generate_with_preset(7, symmetry, WorldGenPreset::Standard, 256);
+            for y in 0..map.height {
+                for x in 0..map.width {
+                    let Some((sx, sy, _)) = symmetry.source(x, y, map.width, map.height) else {
+                        continue;
+                    };
+                    let (cell, source) = (map.get(x, y).unwrap(), map.get(sx, sy).unwrap());
+                    assert_eq!(
+                        (cell.mineral_type, cell.density, cell.biome, cell.stratum),
+                        (source.mineral_type, source.density, source.biome, source.stratum),
+                        "{} symmetry: ({}, {}) differs from its source ({}, {})",
+                        symmetry.name(),
+                        x,
+                        y,
+                        sx,
+                        sy
+                    );
+                }
+            }
+        }
+    }
+}